@@ -1,16 +1,27 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
 use crate::domain::dns_packet_buffer::DnsPacketBuffer;
+use crate::domain::dns_packet_builder::{QueryBuilder, ResponseBuilder};
+use crate::domain::dns_packet_ref::Labels;
+use crate::domain::dns_packet_writer::DnsPacketWriter;
+use crate::error::{DnsError, Section};
+use crate::protocol::dns_edns::Edns;
 use crate::protocol::dns_header::DnsHeader;
+use crate::protocol::dns_name::Name;
 use crate::protocol::dns_question::DnsQuestion;
 use crate::protocol::dns_resource_record::DnsRecord;
-use crate::protocol::opcode_enum::OpCode;
+use crate::protocol::edns_option_enum::EdnsOption;
 use crate::protocol::question_class_enum::QuestionClass;
 use crate::protocol::question_type_enum::QuestionType;
 use crate::protocol::rcode_enum::RCode;
 use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
 use crate::protocol::resource_type_enum::ResourceType;
+use crate::protocol::svc_param_enum::SvcParam;
 
 /**
-```
+```text
 +---------------------+
 |        Header       |
 +---------------------+
@@ -31,6 +42,13 @@ pub struct DnsPacket {
     pub answers: Vec<DnsRecord>,
     pub authorities: Vec<DnsRecord>,
     pub resources: Vec<DnsRecord>,
+    /**
+        The EDNS(0) information, taken out of the OPT pseudo-record of the
+        additional section on decode and put back as the last additional
+        record on encode. `resources` never holds that OPT record. Messages
+        with a second OPT record, or one in another section, do not decode.
+    */
+    pub edns: Option<Edns>,
 }
 
 impl DnsPacket {
@@ -41,34 +59,118 @@ impl DnsPacket {
             answers: Vec::new(),
             authorities: Vec::new(),
             resources: Vec::new(),
+            edns: None,
+        }
+    }
+
+    /**
+        The full 12 bit RCODE: the 4 bits of the header, extended by the
+        upper 8 bits carried in the OPT record when there is one.
+    */
+    pub fn extended_r_code(&self) -> RCode {
+        let upper = self.edns.as_ref().map_or(0, |edns| edns.extended_r_code);
+        (((upper as u16) << 4) | (u16::from(self.header.r_code) & 0x0F)).into()
+    }
+
+    /**
+        Split a 12 bit RCODE between the header and the OPT record. An RCODE
+        above 15 can only be sent with EDNS, so an OPT record is added to the
+        message if it has none.
+    */
+    pub fn set_extended_r_code(&mut self, r_code: RCode) {
+        let value = u16::from(r_code);
+        self.header.r_code = (value & 0x0F).into();
+
+        let upper = (value >> 4) as u8;
+        match &mut self.edns {
+            Some(edns) => edns.extended_r_code = upper,
+            None if upper != 0 => {
+                self.edns = Some(Edns {
+                    extended_r_code: upper,
+                    ..Edns::new()
+                })
+            }
+            None => {}
         }
     }
 
-    pub fn decode(buffer: &mut DnsPacketBuffer) -> Result<DnsPacket, &'static str> {
+    /// Start building a query, see `QueryBuilder`
+    pub fn query() -> QueryBuilder {
+        QueryBuilder::new()
+    }
+
+    /// Start building the response to `query`, see `ResponseBuilder`
+    pub fn response_to(query: &DnsPacket) -> ResponseBuilder {
+        ResponseBuilder::new(query)
+    }
+
+    /**
+        Whether this message answers `query`: a response with the same ID
+        and the same questions, names compared ignoring case (RFC 5452
+        section 9.1).
+    */
+    pub fn is_response_to(&self, query: &DnsPacket) -> bool {
+        self.header.response
+            && self.header.id == query.header.id
+            && self.questions.len() == query.questions.len()
+            && self
+                .questions
+                .iter()
+                .zip(&query.questions)
+                .all(|(a, b)| a.name == b.name && a.q_type == b.q_type && a.q_class == b.q_class)
+    }
+
+    /// Decode a complete message of any size
+    pub fn from_bytes(bytes: &[u8]) -> Result<DnsPacket, DnsError> {
+        Self::decode(&mut DnsPacketBuffer::new(bytes))
+    }
+
+    /// Encode the message with name compression
+    pub fn to_bytes(&self) -> Result<Vec<u8>, DnsError> {
+        let mut buffer = DnsPacketWriter::new();
+        self.encode(&mut buffer)?;
+
+        Ok(buffer.into_bytes())
+    }
+
+    pub fn decode(buffer: &mut DnsPacketBuffer) -> Result<DnsPacket, DnsError> {
         let header = Self::decode_header(buffer)?;
 
         let mut questions = Vec::new();
-        for _ in 0..header.questions {
-            let question = Self::decode_question(buffer)?;
+        for index in 0..header.questions as usize {
+            let question =
+                Self::decode_question(buffer).map_err(|e| e.in_record(Section::Question, index))?;
             questions.push(question);
         }
 
         let mut answers = Vec::new();
-        for _ in 0..header.answers {
-            let answer = Self::decode_record(buffer)?;
+        for index in 0..header.answers as usize {
+            let answer = Self::decode_record(buffer)
+                .and_then(Self::not_opt)
+                .map_err(|e| e.in_record(Section::Answer, index))?;
             answers.push(answer);
         }
 
         let mut authorities = Vec::new();
-        for _ in 0..header.authoritative_entries {
-            let authority = Self::decode_record(buffer)?;
+        for index in 0..header.authoritative_entries as usize {
+            let authority = Self::decode_record(buffer)
+                .and_then(Self::not_opt)
+                .map_err(|e| e.in_record(Section::Authority, index))?;
             authorities.push(authority);
         }
 
         let mut resources = Vec::new();
-        for _ in 0..header.resource_entries {
-            let resource = Self::decode_record(buffer)?;
-            resources.push(resource);
+        let mut edns = None;
+        for index in 0..header.resource_entries as usize {
+            let resource =
+                Self::decode_record(buffer).map_err(|e| e.in_record(Section::Additional, index))?;
+            match Edns::from_record(&resource) {
+                Some(_) if edns.is_some() => {
+                    return Err(DnsError::DuplicateOpt.in_record(Section::Additional, index))
+                }
+                Some(opt) if resource.name.is_root() => edns = Some(opt),
+                _ => resources.push(resource),
+            }
         }
 
         Ok(DnsPacket {
@@ -77,52 +179,23 @@ impl DnsPacket {
             answers,
             authorities,
             resources,
+            edns,
         })
     }
 
-    fn decode_header(buffer: &mut DnsPacketBuffer) -> Result<DnsHeader, &'static str> {
-        let id = buffer.read_u16()?;
-
-        let flags = buffer.read_u16()?;
-        let h = (flags >> 8) as u8;
-        let l = (flags & 0xFF) as u8;
-
-        let response = (h & (1 << 7)) == 1;
-        let opcode: OpCode = ((h >> 3) & 0x0F).try_into()?;
-        let authoritative_answer = (h & (1 << 2)) == 1;
-        let truncated_message = (h & (1 << 1)) == 1;
-        let recursion_desired = (h & (1 << 0)) == 1;
-
-        let recursion_available = (l & (1 << 7)) == 1;
-        let z = (l & (1 << 6)) == 1;
-        let authed_data = (l & (1 << 5)) == 1;
-        let checking_disabled = (l & (1 << 4)) == 1;
-        let r_code: RCode = (l & 0x0F).try_into()?;
-
-        let questions = buffer.read_u16()?;
-        let answers = buffer.read_u16()?;
-        let authoritative_entries = buffer.read_u16()?;
-        let resource_entries = buffer.read_u16()?;
-
-        Ok(DnsHeader {
-            id,
-
-            response,
-            opcode,
-            authoritative_answer,
-            truncated_message,
-            recursion_desired,
-            recursion_available,
-            z,
-            authed_data,
-            checking_disabled,
-            r_code,
+    /// `record`, unless it is an OPT pseudo-record, which only the additional section may hold
+    fn not_opt(record: DnsRecord) -> Result<DnsRecord, DnsError> {
+        match record.r_type {
+            ResourceType::OPT => Err(DnsError::MisplacedOpt),
+            _ => Ok(record),
+        }
+    }
 
-            questions,
-            answers,
-            authoritative_entries,
-            resource_entries,
-        })
+    pub(crate) fn decode_header(buffer: &mut DnsPacketBuffer) -> Result<DnsHeader, DnsError> {
+        let header = DnsHeader::from_bytes(buffer.get_range(buffer.pos(), 12)?)?;
+        buffer.step(12)?;
+
+        Ok(header)
     }
 
     /**
@@ -148,8 +221,8 @@ impl DnsPacket {
         with zero parity. Non-alphabetic codes must match exactly.
 
         The tricky part: Reading domain names, taking labels into consideration.
-        Will take something like [3]www[6]google[3]com[0] and append
-        www.google.com
+        Will take something like [3]www[6]google[3]com[0] and make the name
+        www.google.com. of it, keeping the case and octets of every label.
 
         In order to reduce the size of messages, the domain system utilizes a
         compression scheme which eliminates the repetition of domain names in a
@@ -157,100 +230,1015 @@ impl DnsPacket {
         the end of a domain name is replaced with a pointer to a prior occurance
         of the same name.
     */
-    fn decode_name(buffer: &mut DnsPacketBuffer) -> Result<String, &'static str> {
-        let mut domain_name = String::new();
+    fn decode_name(buffer: &mut DnsPacketBuffer) -> Result<Name, DnsError> {
+        let mut labels = Labels::new(buffer.buf, buffer.pos());
+        let name = Name::from_labels((&mut labels).collect::<Result<Vec<_>, _>>()?)?;
+
+        if let Some(end) = labels.end() {
+            buffer.seek(end)?;
+        }
+
+        Ok(name)
+    }
+
+    fn decode_question(buffer: &mut DnsPacketBuffer) -> Result<DnsQuestion, DnsError> {
+        let name = Self::decode_name(buffer)?;
+        let q_type: QuestionType = buffer.read_u16()?.into();
+        let q_class: QuestionClass = buffer.read_u16()?.into();
+        Ok(DnsQuestion {
+            name,
+            q_type,
+            q_class,
+        })
+    }
+
+    pub(crate) fn decode_record(buffer: &mut DnsPacketBuffer) -> Result<DnsRecord, DnsError> {
+        let domain = Self::decode_name(buffer)?;
 
-        let mut pos = buffer.pos();
+        let resource_type: ResourceType = buffer.read_u16()?.into();
+        let resource_class: ResourceClass = buffer.read_u16()?.into();
 
-        let mut jumped = false;
-        let max_jumps = 5;
-        let mut jumps_performed = 0;
+        let ttl = buffer.read_u32()?;
+        let rd_length = buffer.read_u16()?;
 
-        let mut delimiter = "";
+        let r_data = Self::decode_r_data(buffer, resource_type, resource_class, rd_length)?;
 
-        loop {
-            let len = buffer.get(pos)?;
+        Ok(DnsRecord {
+            name: domain,
+            r_type: resource_type,
+            r_class: resource_class,
+            ttl,
+            rd_length,
+            r_data,
+        })
+    }
 
-            /*
-            The pointer takes the form of a two octet sequence:
-                +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-                | 1  1|                OFFSET                   |
-                +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
-             */
-            if (len & 0xC0) == 0xC0 {
-                if jumps_performed > max_jumps {
-                    return Err("Limit of {} jumps exceeded");
+    /**
+        Decodes the RDLENGTH octets of RDATA at the current position according
+        to the record TYPE. Names inside the RDATA may be compressed, so they
+        are read with `decode_name` against the whole message.
+
+        RDATA of an unknown TYPE, or of a TYPE whose format is only defined for
+        the Internet class (A, WKS, AAAA) in another CLASS, is kept opaque as
+        RFC 3597 prescribes.
+    */
+    pub(crate) fn decode_r_data(
+        buffer: &mut DnsPacketBuffer,
+        r_type: ResourceType,
+        r_class: ResourceClass,
+        rd_length: u16,
+    ) -> Result<RData, DnsError> {
+        let start = buffer.pos();
+        let end = start + rd_length as usize;
+
+        let r_data = match r_type {
+            ResourceType::A | ResourceType::WKS | ResourceType::AAAA
+                if r_class != ResourceClass::IN =>
+            {
+                RData::Unknown {
+                    r_type,
+                    data: Self::decode_bytes(buffer, rd_length as usize)?,
+                }
+            }
+            ResourceType::Unknown(_) => RData::Unknown {
+                r_type,
+                data: Self::decode_bytes(buffer, rd_length as usize)?,
+            },
+            ResourceType::A => RData::A(Self::decode_ipv4(buffer)?),
+            ResourceType::NS => RData::NS(Self::decode_name(buffer)?),
+            ResourceType::MD => RData::MD(Self::decode_name(buffer)?),
+            ResourceType::MF => RData::MF(Self::decode_name(buffer)?),
+            ResourceType::CName => RData::CName(Self::decode_name(buffer)?),
+            ResourceType::SOA => RData::SOA {
+                m_name: Self::decode_name(buffer)?,
+                r_name: Self::decode_name(buffer)?,
+                serial: buffer.read_u32()?,
+                refresh: buffer.read_u32()?,
+                retry: buffer.read_u32()?,
+                expire: buffer.read_u32()?,
+                minimum: buffer.read_u32()?,
+            },
+            ResourceType::MB => RData::MB(Self::decode_name(buffer)?),
+            ResourceType::MG => RData::MG(Self::decode_name(buffer)?),
+            ResourceType::MR => RData::MR(Self::decode_name(buffer)?),
+            ResourceType::Null => RData::Null(Self::decode_bytes(buffer, rd_length as usize)?),
+            ResourceType::WKS => {
+                let address = Self::decode_ipv4(buffer)?;
+                let protocol = buffer.read()?;
+                let bit_map = Self::decode_bytes(buffer, end.saturating_sub(buffer.pos()))?;
+                RData::WKS {
+                    address,
+                    protocol,
+                    bit_map,
+                }
+            }
+            ResourceType::PTR => RData::PTR(Self::decode_name(buffer)?),
+            ResourceType::HInfo => RData::HInfo {
+                cpu: Self::decode_character_string(buffer)?,
+                os: Self::decode_character_string(buffer)?,
+            },
+            ResourceType::MInfo => RData::MInfo {
+                r_mail_bx: Self::decode_name(buffer)?,
+                e_mail_bx: Self::decode_name(buffer)?,
+            },
+            ResourceType::MX => RData::MX {
+                preference: buffer.read_u16()?,
+                exchange: Self::decode_name(buffer)?,
+            },
+            ResourceType::Txt => {
+                let mut strings = Vec::new();
+                while buffer.pos() < end {
+                    strings.push(Self::decode_character_string(buffer)?);
                 }
+                RData::Txt(strings)
+            }
+            ResourceType::AAAA => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(buffer.get_range(buffer.pos(), 16)?);
+                buffer.step(16)?;
+                RData::AAAA(Ipv6Addr::from(octets))
+            }
+            ResourceType::SRV => RData::SRV {
+                priority: buffer.read_u16()?,
+                weight: buffer.read_u16()?,
+                port: buffer.read_u16()?,
+                target: Self::decode_name(buffer)?,
+            },
+            ResourceType::NAPTR => RData::NAPTR {
+                order: buffer.read_u16()?,
+                preference: buffer.read_u16()?,
+                flags: Self::decode_character_string(buffer)?,
+                services: Self::decode_character_string(buffer)?,
+                regexp: Self::decode_character_string(buffer)?,
+                replacement: Self::decode_name(buffer)?,
+            },
+            ResourceType::DName => RData::DName(Self::decode_name(buffer)?),
+            ResourceType::OPT => RData::OPT(Self::decode_edns_options(buffer, end)?),
+            ResourceType::SSHFP => RData::SSHFP {
+                algorithm: buffer.read()?,
+                fp_type: buffer.read()?,
+                fingerprint: Self::decode_bytes(buffer, end.saturating_sub(buffer.pos()))?,
+            },
+            ResourceType::TLSA => RData::TLSA {
+                cert_usage: buffer.read()?,
+                selector: buffer.read()?,
+                matching_type: buffer.read()?,
+                cert_data: Self::decode_bytes(buffer, end.saturating_sub(buffer.pos()))?,
+            },
+            ResourceType::SVCB => RData::SVCB {
+                priority: buffer.read_u16()?,
+                target: Self::decode_name(buffer)?,
+                params: Self::decode_svc_params(buffer, r_type, end)?,
+            },
+            ResourceType::HTTPS => RData::HTTPS {
+                priority: buffer.read_u16()?,
+                target: Self::decode_name(buffer)?,
+                params: Self::decode_svc_params(buffer, r_type, end)?,
+            },
+            ResourceType::CAA => {
+                let flags = buffer.read()?;
+                let tag = Self::decode_character_string(buffer)?;
+                let value = Self::decode_bytes(buffer, end.saturating_sub(buffer.pos()))?;
+                RData::CAA { flags, tag, value }
+            }
+        };
+
+        if buffer.pos() != end {
+            return Err(DnsError::RDataLength {
+                offset: start,
+                r_type: r_type.into(),
+                rd_length,
+                consumed: buffer.pos() - start,
+            });
+        }
+
+        Ok(r_data)
+    }
 
-                if !jumped {
-                    buffer.seek(pos + 2)?;
+    fn decode_ipv4(buffer: &mut DnsPacketBuffer) -> Result<Ipv4Addr, DnsError> {
+        Ok(Ipv4Addr::from(buffer.read_u32()?))
+    }
+
+    /// Read `len` raw octets, stepping forward by `len`
+    fn decode_bytes(buffer: &mut DnsPacketBuffer, len: usize) -> Result<Vec<u8>, DnsError> {
+        let bytes = buffer.get_range(buffer.pos(), len)?.to_vec();
+        buffer.step(len)?;
+
+        Ok(bytes)
+    }
+
+    /**
+        Read the options of an OPT record up to the end of its RDATA. An option
+        that is malformed for its code is kept as `EdnsOption::Unknown` so that
+        it still re-encodes unchanged.
+    */
+    fn decode_edns_options(
+        buffer: &mut DnsPacketBuffer,
+        end: usize,
+    ) -> Result<Vec<EdnsOption>, DnsError> {
+        let mut options = Vec::new();
+
+        while buffer.pos() < end {
+            let code = buffer.read_u16()?;
+            let len = buffer.read_u16()? as usize;
+            let data = buffer.get_range(buffer.pos(), len)?;
+            buffer.step(len)?;
+
+            let option = match code {
+                EdnsOption::NSID => Some(EdnsOption::Nsid(data.to_vec())),
+                EdnsOption::CLIENT_SUBNET if len >= 4 => {
+                    let family = ((data[0] as u16) << 8) | data[1] as u16;
+                    let address = &data[4..];
+                    match family {
+                        1 if address.len() <= 4 => {
+                            let mut octets = [0u8; 4];
+                            octets[..address.len()].copy_from_slice(address);
+                            Some(IpAddr::V4(Ipv4Addr::from(octets)))
+                        }
+                        2 if address.len() <= 16 => {
+                            let mut octets = [0u8; 16];
+                            octets[..address.len()].copy_from_slice(address);
+                            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+                        }
+                        _ => None,
+                    }
+                    .filter(|_| address.len() == (data[2] as usize).div_ceil(8))
+                    .map(|address| EdnsOption::ClientSubnet {
+                        source_prefix: data[2],
+                        scope_prefix: data[3],
+                        address,
+                    })
+                }
+                EdnsOption::COOKIE if len == 8 || (16..=40).contains(&len) => {
+                    let mut client = [0u8; 8];
+                    client.copy_from_slice(&data[..8]);
+                    Some(EdnsOption::Cookie {
+                        client,
+                        server: data[8..].to_vec(),
+                    })
                 }
+                EdnsOption::PADDING if data.iter().all(|b| *b == 0) => {
+                    Some(EdnsOption::Padding(len as u16))
+                }
+                EdnsOption::EXTENDED_ERROR if len >= 2 => String::from_utf8(data[2..].to_vec())
+                    .ok()
+                    .map(|extra_text| EdnsOption::ExtendedError {
+                        info_code: ((data[0] as u16) << 8) | data[1] as u16,
+                        extra_text,
+                    }),
+                _ => None,
+            };
 
-                let l = buffer.get(pos + 1)? as u16;
-                let h = ((len as u16) ^ 0xC0) << 8;
-                let offset = h | l;
-                pos = offset as usize;
+            options.push(option.unwrap_or_else(|| EdnsOption::Unknown {
+                code,
+                data: data.to_vec(),
+            }));
+        }
 
-                jumped = true;
-                jumps_performed += 1;
+        Ok(options)
+    }
 
-                continue;
-            } else {
-                pos += 1;
+    /// Read the SvcParams of a SVCB or HTTPS record up to the end of its RDATA
+    fn decode_svc_params(
+        buffer: &mut DnsPacketBuffer,
+        r_type: ResourceType,
+        end: usize,
+    ) -> Result<Vec<SvcParam>, DnsError> {
+        let mut params = Vec::new();
+
+        while buffer.pos() < end {
+            let offset = buffer.pos();
+            let invalid = DnsError::InvalidRData {
+                offset,
+                r_type: r_type.into(),
+            };
 
-                if len == 0 {
-                    break;
+            let key = buffer.read_u16()?;
+            let len = buffer.read_u16()? as usize;
+            let value = buffer.get_range(buffer.pos(), len)?;
+            buffer.step(len)?;
+
+            let param = match key {
+                SvcParam::MANDATORY if len.is_multiple_of(2) => SvcParam::Mandatory(
+                    value
+                        .chunks(2)
+                        .map(|c| ((c[0] as u16) << 8) | c[1] as u16)
+                        .collect(),
+                ),
+                SvcParam::ALPN => {
+                    let mut ids = Vec::new();
+                    let mut rest = value;
+                    while let Some((&id_len, tail)) = rest.split_first() {
+                        if id_len == 0 || tail.len() < id_len as usize {
+                            return Err(invalid);
+                        }
+                        let (id, tail) = tail.split_at(id_len as usize);
+                        ids.push(id.to_vec());
+                        rest = tail;
+                    }
+                    SvcParam::Alpn(ids)
+                }
+                SvcParam::NO_DEFAULT_ALPN if len == 0 => SvcParam::NoDefaultAlpn,
+                SvcParam::PORT if len == 2 => {
+                    SvcParam::Port(((value[0] as u16) << 8) | value[1] as u16)
                 }
+                SvcParam::IPV4_HINT if len > 0 && len.is_multiple_of(4) => SvcParam::Ipv4Hint(
+                    value
+                        .chunks(4)
+                        .map(|c| Ipv4Addr::new(c[0], c[1], c[2], c[3]))
+                        .collect(),
+                ),
+                SvcParam::ECH => SvcParam::Ech(value.to_vec()),
+                SvcParam::IPV6_HINT if len > 0 && len.is_multiple_of(16) => SvcParam::Ipv6Hint(
+                    value
+                        .chunks(16)
+                        .map(|c| {
+                            let mut octets = [0u8; 16];
+                            octets.copy_from_slice(c);
+                            Ipv6Addr::from(octets)
+                        })
+                        .collect(),
+                ),
+                SvcParam::MANDATORY
+                | SvcParam::NO_DEFAULT_ALPN
+                | SvcParam::PORT
+                | SvcParam::IPV4_HINT
+                | SvcParam::IPV6_HINT => return Err(invalid),
+                key => SvcParam::Unknown {
+                    key,
+                    value: value.to_vec(),
+                },
+            };
+            params.push(param);
+        }
+
+        Ok(params)
+    }
+
+    /// A <character-string> is a single length octet followed by that number of octets
+    fn decode_character_string(buffer: &mut DnsPacketBuffer) -> Result<Vec<u8>, DnsError> {
+        let len = buffer.read()?;
+        Self::decode_bytes(buffer, len as usize)
+    }
+
+    /**
+        Encode the message. The section counts of the header are derived from
+        the contents rather than taken from `header`, so they always match
+        what is written, the OPT record of `edns` included.
+    */
+    pub fn encode(&self, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        let mut header = self.header.clone();
+        header.questions = self.questions.len() as u16;
+        header.answers = self.answers.len() as u16;
+        header.authoritative_entries = self.authorities.len() as u16;
+        header.resource_entries = (self.resources.len() + self.edns.is_some() as usize) as u16;
+        Self::encode_header(&header, buffer)?;
+
+        for (index, question) in self.questions.iter().enumerate() {
+            Self::encode_question(question, buffer)
+                .map_err(|e| e.in_record(Section::Question, index))?;
+        }
+        for (index, answer) in self.answers.iter().enumerate() {
+            Self::encode_record(answer, buffer).map_err(|e| e.in_record(Section::Answer, index))?;
+        }
+        for (index, authority) in self.authorities.iter().enumerate() {
+            Self::encode_record(authority, buffer)
+                .map_err(|e| e.in_record(Section::Authority, index))?;
+        }
+        for (index, resource) in self.resources.iter().enumerate() {
+            Self::encode_record(resource, buffer)
+                .map_err(|e| e.in_record(Section::Additional, index))?;
+        }
+        if let Some(edns) = &self.edns {
+            Self::encode_record(&edns.to_record(), buffer)
+                .map_err(|e| e.in_record(Section::Additional, self.resources.len()))?;
+        }
+
+        Ok(())
+    }
 
-                domain_name.push_str(delimiter);
+    pub fn encode_header(header: &DnsHeader, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        buffer.write_range(&header.to_bytes())
+    }
 
-                let str_buffer = buffer.get_range(pos, len as usize)?;
-                domain_name.push_str(&String::from_utf8_lossy(str_buffer).to_lowercase());
+    /**
+        Writes a domain name as a sequence of length-prefixed labels
+        terminated by the null label of the root, i.e. the inverse of
+        `decode_name`: www.google.com. becomes [3]www[6]google[3]com[0].
 
-                delimiter = ".";
+        Unless the buffer has compression disabled, the longest suffix that
+        was already written to the message is replaced by a pointer to it, so
+        a following mail.google.com. becomes [4]mail[0xC0][offset of google].
+    */
+    pub fn encode_name(name: &Name, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        let compress = buffer.compression;
+        Self::write_name(name, buffer, compress)
+    }
 
-                pos += len as usize;
+    fn write_name(
+        name: &Name,
+        buffer: &mut DnsPacketWriter,
+        compress: bool,
+    ) -> Result<(), DnsError> {
+        let mut suffix = name.clone();
+        for label in name.labels() {
+            if compress {
+                if let Some(offset) = buffer.name_offset(&suffix) {
+                    buffer.write_u16(0xC000 | offset as u16)?;
+                    return Ok(());
+                }
             }
+
+            let parent = suffix.parent().unwrap_or_default();
+            buffer.add_name(suffix, buffer.pos());
+            suffix = parent;
+
+            buffer.write_u8(label.len() as u8)?;
+            buffer.write_range(label)?;
         }
 
-        if !jumped {
-            buffer.seek(pos)?;
+        buffer.write_u8(0)?;
+
+        Ok(())
+    }
+
+    pub fn encode_question(
+        question: &DnsQuestion,
+        buffer: &mut DnsPacketWriter,
+    ) -> Result<(), DnsError> {
+        Self::encode_name(&question.name, buffer)?;
+        buffer.write_u16(question.q_type.into())?;
+        buffer.write_u16(question.q_class.into())?;
+
+        Ok(())
+    }
+
+    pub fn encode_record(record: &DnsRecord, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        Self::encode_name(&record.name, buffer)?;
+        buffer.write_u16(record.r_type.into())?;
+        buffer.write_u16(record.r_class.into())?;
+        buffer.write_u32(record.ttl)?;
+
+        // RDLENGTH is patched in once the RDATA has been written
+        let len_pos = buffer.pos();
+        buffer.write_u16(0)?;
+
+        Self::encode_r_data(&record.r_data, buffer)?;
+
+        let rd_length = buffer.pos() - (len_pos + 2);
+        buffer.set_u16(len_pos, rd_length as u16)?;
+
+        Ok(())
+    }
+
+    /**
+        Writes the RDATA of a record. Names inside the RDATA of the RFC 1035
+        types are compressed like owner names, as RFC 3597 allows for these
+        well-known types only.
+    */
+    pub fn encode_r_data(r_data: &RData, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        let compress = buffer.compression;
+
+        match r_data {
+            RData::A(address) => buffer.write_range(&address.octets())?,
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CName(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => Self::write_name(name, buffer, compress)?,
+            RData::SOA {
+                m_name,
+                r_name,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                Self::write_name(m_name, buffer, compress)?;
+                Self::write_name(r_name, buffer, compress)?;
+                buffer.write_u32(*serial)?;
+                buffer.write_u32(*refresh)?;
+                buffer.write_u32(*retry)?;
+                buffer.write_u32(*expire)?;
+                buffer.write_u32(*minimum)?;
+            }
+            RData::Null(bytes) => buffer.write_range(bytes)?,
+            RData::WKS {
+                address,
+                protocol,
+                bit_map,
+            } => {
+                buffer.write_range(&address.octets())?;
+                buffer.write_u8(*protocol)?;
+                buffer.write_range(bit_map)?;
+            }
+            RData::HInfo { cpu, os } => {
+                Self::encode_character_string(cpu, buffer)?;
+                Self::encode_character_string(os, buffer)?;
+            }
+            RData::MInfo {
+                r_mail_bx,
+                e_mail_bx,
+            } => {
+                Self::write_name(r_mail_bx, buffer, compress)?;
+                Self::write_name(e_mail_bx, buffer, compress)?;
+            }
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                buffer.write_u16(*preference)?;
+                Self::write_name(exchange, buffer, compress)?;
+            }
+            RData::Txt(strings) => {
+                for string in strings {
+                    Self::encode_character_string(string, buffer)?;
+                }
+            }
+            RData::AAAA(address) => buffer.write_range(&address.octets())?,
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                buffer.write_u16(*priority)?;
+                buffer.write_u16(*weight)?;
+                buffer.write_u16(*port)?;
+                Self::write_name(target, buffer, false)?;
+            }
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                buffer.write_u16(*order)?;
+                buffer.write_u16(*preference)?;
+                Self::encode_character_string(flags, buffer)?;
+                Self::encode_character_string(services, buffer)?;
+                Self::encode_character_string(regexp, buffer)?;
+                Self::write_name(replacement, buffer, false)?;
+            }
+            RData::DName(name) => Self::write_name(name, buffer, false)?,
+            RData::OPT(options) => {
+                for option in options {
+                    Self::encode_edns_option(option, buffer)?;
+                }
+            }
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => {
+                buffer.write_u8(*algorithm)?;
+                buffer.write_u8(*fp_type)?;
+                buffer.write_range(fingerprint)?;
+            }
+            RData::TLSA {
+                cert_usage,
+                selector,
+                matching_type,
+                cert_data,
+            } => {
+                buffer.write_u8(*cert_usage)?;
+                buffer.write_u8(*selector)?;
+                buffer.write_u8(*matching_type)?;
+                buffer.write_range(cert_data)?;
+            }
+            RData::SVCB {
+                priority,
+                target,
+                params,
+            }
+            | RData::HTTPS {
+                priority,
+                target,
+                params,
+            } => {
+                buffer.write_u16(*priority)?;
+                Self::write_name(target, buffer, false)?;
+                for param in params {
+                    Self::encode_svc_param(param, buffer)?;
+                }
+            }
+            RData::CAA { flags, tag, value } => {
+                buffer.write_u8(*flags)?;
+                Self::encode_character_string(tag, buffer)?;
+                buffer.write_range(value)?;
+            }
+            RData::Unknown { data, .. } => buffer.write_range(data)?,
         }
 
-        Ok(domain_name)
+        Ok(())
     }
 
-    fn decode_question(buffer: &mut DnsPacketBuffer) -> Result<DnsQuestion, &'static str> {
-        let name = Self::decode_name(buffer)?;
-        let q_type: QuestionType = buffer.read_u16()?.try_into()?;
-        let q_class: QuestionClass = buffer.read_u16()?.try_into()?;
-        Ok(DnsQuestion {
-            name,
-            q_type,
-            q_class,
-        })
+    fn encode_edns_option(
+        option: &EdnsOption,
+        buffer: &mut DnsPacketWriter,
+    ) -> Result<(), DnsError> {
+        buffer.write_u16(option.code())?;
+
+        // OPTION-LENGTH is patched in once the data has been written
+        let len_pos = buffer.pos();
+        buffer.write_u16(0)?;
+
+        match option {
+            EdnsOption::Nsid(id) => buffer.write_range(id)?,
+            EdnsOption::ClientSubnet {
+                source_prefix,
+                scope_prefix,
+                address,
+            } => {
+                let (family, octets) = match address {
+                    IpAddr::V4(address) => (1, address.octets().to_vec()),
+                    IpAddr::V6(address) => (2, address.octets().to_vec()),
+                };
+                let len = (*source_prefix as usize).div_ceil(8).min(octets.len());
+
+                buffer.write_u16(family)?;
+                buffer.write_u8(*source_prefix)?;
+                buffer.write_u8(*scope_prefix)?;
+                buffer.write_range(&octets[..len])?;
+            }
+            EdnsOption::Cookie { client, server } => {
+                buffer.write_range(client)?;
+                buffer.write_range(server)?;
+            }
+            EdnsOption::Padding(len) => buffer.write_range(&vec![0; *len as usize])?,
+            EdnsOption::ExtendedError {
+                info_code,
+                extra_text,
+            } => {
+                buffer.write_u16(*info_code)?;
+                buffer.write_range(extra_text.as_bytes())?;
+            }
+            EdnsOption::Unknown { data, .. } => buffer.write_range(data)?,
+        }
+
+        let len = buffer.pos() - (len_pos + 2);
+        buffer.set_u16(len_pos, len as u16)?;
+
+        Ok(())
     }
 
-    fn decode_record(buffer: &mut DnsPacketBuffer) -> Result<DnsRecord, &'static str> {
-        let domain = Self::decode_name(buffer)?;
+    fn encode_svc_param(param: &SvcParam, buffer: &mut DnsPacketWriter) -> Result<(), DnsError> {
+        buffer.write_u16(param.key())?;
 
-        let resource_type: ResourceType = buffer.read_u16()?.try_into()?;
-        let resource_class: ResourceClass = buffer.read_u16()?.try_into()?;
+        // the value length is patched in once the value has been written
+        let len_pos = buffer.pos();
+        buffer.write_u16(0)?;
 
-        let ttl = buffer.read_u32()?;
-        let rd_length = buffer.read_u16()?;
+        match param {
+            SvcParam::Mandatory(keys) => {
+                for key in keys {
+                    buffer.write_u16(*key)?;
+                }
+            }
+            SvcParam::Alpn(ids) => {
+                for id in ids {
+                    Self::encode_character_string(id, buffer)?;
+                }
+            }
+            SvcParam::NoDefaultAlpn => {}
+            SvcParam::Port(port) => buffer.write_u16(*port)?,
+            SvcParam::Ipv4Hint(addresses) => {
+                for address in addresses {
+                    buffer.write_range(&address.octets())?;
+                }
+            }
+            SvcParam::Ech(config) => buffer.write_range(config)?,
+            SvcParam::Ipv6Hint(addresses) => {
+                for address in addresses {
+                    buffer.write_range(&address.octets())?;
+                }
+            }
+            SvcParam::Unknown { value, .. } => buffer.write_range(value)?,
+        }
 
-        let str_buffer = buffer.get_range(buffer.pos(), rd_length as usize)?;
-        let r_data = String::from_utf8_lossy(str_buffer).to_string();
-        buffer.step(rd_length as usize)?;
+        let len = buffer.pos() - (len_pos + 2);
+        buffer.set_u16(len_pos, len as u16)?;
 
-        Ok(DnsRecord {
-            name: domain,
-            r_type: resource_type,
-            r_class: resource_class,
-            ttl,
-            rd_length,
-            r_data,
-        })
+        Ok(())
+    }
+
+    fn encode_character_string(
+        string: &[u8],
+        buffer: &mut DnsPacketWriter,
+    ) -> Result<(), DnsError> {
+        if string.len() > 0xFF {
+            return Err(DnsError::CharacterStringTooLong { len: string.len() });
+        }
+        buffer.write_u8(string.len() as u8)?;
+        buffer.write_range(string)?;
+
+        Ok(())
+    }
+}
+
+impl Default for DnsPacket {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for DnsPacket {
+    /**
+        The message as dig shows it: the header, the EDNS information, and
+        each non-empty section in presentation format. With `{:#}` the
+        names are shown in Unicode, A-labels decoded.
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            ";; ->>HEADER<<- opcode: {}, status: {}, id: {}",
+            self.header.opcode,
+            self.extended_r_code(),
+            self.header.id
+        )?;
+        writeln!(
+            f,
+            ";; flags: {}; QUERY: {}, ANSWER: {}, AUTHORITY: {}, ADDITIONAL: {}",
+            self.header.flags(),
+            self.questions.len(),
+            self.answers.len(),
+            self.authorities.len(),
+            self.resources.len() + self.edns.is_some() as usize
+        )?;
+
+        if let Some(edns) = &self.edns {
+            writeln!(f, "\n;; OPT PSEUDOSECTION:\n{}", edns)?;
+        }
+
+        writeln!(f, "\n;; QUESTION SECTION:")?;
+        for question in &self.questions {
+            write!(f, ";")?;
+            fmt::Display::fmt(question, f)?;
+            writeln!(f)?;
+        }
+
+        for (title, records) in [
+            ("ANSWER", &self.answers),
+            ("AUTHORITY", &self.authorities),
+            ("ADDITIONAL", &self.resources),
+        ] {
+            if records.is_empty() {
+                continue;
+            }
+            writeln!(f, "\n;; {} SECTION:", title)?;
+            for record in records {
+                fmt::Display::fmt(record, f)?;
+                writeln!(f)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    /// The encoding of each of `names`, written one after the other to `buffer`
+    fn encode_names(mut buffer: DnsPacketWriter, names: &[&str]) -> Vec<Vec<u8>> {
+        let mut encoded = Vec::new();
+        for text in names {
+            let start = buffer.pos();
+            DnsPacket::encode_name(&name(text), &mut buffer).unwrap();
+            encoded.push(buffer.as_bytes()[start..].to_vec());
+        }
+        encoded
+    }
+
+    #[test]
+    fn compresses_the_longest_written_suffix() {
+        let encoded = encode_names(
+            DnsPacketWriter::new(),
+            &["www.google.com", "mail.Google.COM", "www.google.com", "org"],
+        );
+
+        assert_eq!(encoded[0], b"\x03www\x06google\x03com\x00");
+        // google.com. starts at offset 4, after [3]www
+        assert_eq!(encoded[1], b"\x04mail\xC0\x04");
+        assert_eq!(encoded[2], b"\xC0\x00");
+        assert_eq!(encoded[3], b"\x03org\x00");
+    }
+
+    #[test]
+    fn canonical_form_is_not_compressed() {
+        let encoded = encode_names(
+            DnsPacketWriter::canonical(),
+            &["www.google.com", "www.google.com"],
+        );
+
+        assert_eq!(encoded[1], b"\x03www\x06google\x03com\x00");
+    }
+
+    #[test]
+    fn compressed_names_decode() {
+        let mut packet = DnsPacket::new();
+        packet.questions.push(DnsQuestion::new(
+            name("www.google.com"),
+            QuestionType::Base(ResourceType::A),
+            QuestionClass::Base(ResourceClass::IN),
+        ));
+        packet.answers.push(DnsRecord::with_r_data(
+            name("www.google.com"),
+            300,
+            RData::CName(name("mail.google.com")),
+        ));
+
+        let bytes = packet.to_bytes().unwrap();
+        assert_eq!(bytes.len(), 12 + 20 + 2 + 10 + 7);
+
+        let decoded = DnsPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.questions, packet.questions);
+        assert_eq!(decoded.answers[0].name, name("www.google.com"));
+        assert_eq!(
+            decoded.answers[0].r_data,
+            RData::CName(name("mail.google.com"))
+        );
+    }
+
+    /// Encode a response carrying `r_data`, decode it again and compare
+    fn round_trip(r_data: RData) {
+        let mut packet = DnsPacket::new();
+        packet.answers.push(DnsRecord::with_r_data(
+            name("example.com"),
+            300,
+            r_data.clone(),
+        ));
+
+        let decoded = DnsPacket::from_bytes(&packet.to_bytes().unwrap()).unwrap();
+        let [record] = decoded.answers.as_slice() else {
+            panic!("expected one answer, got {:?}", decoded.answers);
+        };
+        assert_eq!(record.name, name("example.com"));
+        assert_eq!(record.r_type, r_data.r_type());
+        assert_eq!(record.ttl, 300);
+        assert_eq!(record.r_data, r_data);
+    }
+
+    #[test]
+    fn rfc1035_types_round_trip() {
+        let rdata = [
+            RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            RData::NS(name("ns.example.com")),
+            RData::MD(name("md.example.com")),
+            RData::MF(name("mf.example.com")),
+            RData::CName(name("www.example.com")),
+            RData::SOA {
+                m_name: name("ns.example.com"),
+                r_name: name("hostmaster.example.com"),
+                serial: 2024010101,
+                refresh: 3600,
+                retry: 600,
+                expire: 86400,
+                minimum: 60,
+            },
+            RData::MB(name("mb.example.com")),
+            RData::MG(name("mg.example.com")),
+            RData::MR(name("mr.example.com")),
+            RData::Null(vec![0, 1, 2, 255]),
+            RData::WKS {
+                address: Ipv4Addr::new(192, 0, 2, 1),
+                protocol: 6,
+                bit_map: vec![0x00, 0x00, 0x00, 0x01],
+            },
+            RData::PTR(name("host.example.com")),
+            RData::HInfo {
+                cpu: b"x86_64".to_vec(),
+                os: b"Linux".to_vec(),
+            },
+            RData::MInfo {
+                r_mail_bx: name("admin.example.com"),
+                e_mail_bx: name("errors.example.com"),
+            },
+            RData::MX {
+                preference: 10,
+                exchange: name("mail.example.com"),
+            },
+            RData::Txt(vec![b"v=spf1 -all".to_vec(), Vec::new(), vec![b'x'; 255]]),
+        ];
+        for r_data in rdata {
+            round_trip(r_data);
+        }
+    }
+
+    #[test]
+    fn unknown_types_round_trip() {
+        round_trip(RData::Unknown {
+            r_type: ResourceType::Unknown(65280),
+            data: vec![0xDE, 0xAD, 0xBE, 0xEF],
+        });
+        round_trip(RData::Unknown {
+            r_type: ResourceType::Unknown(65281),
+            data: Vec::new(),
+        });
+    }
+
+    #[test]
+    fn internet_types_stay_opaque_in_other_classes() {
+        let mut packet = DnsPacket::new();
+        packet.answers.push(DnsRecord {
+            r_class: ResourceClass::CH,
+            ..DnsRecord::with_r_data(
+                name("version.bind"),
+                0,
+                RData::Unknown {
+                    r_type: ResourceType::A,
+                    data: vec![192, 0, 2, 1],
+                },
+            )
+        });
+
+        let decoded = DnsPacket::from_bytes(&packet.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.answers[0].r_type, ResourceType::A);
+        assert_eq!(decoded.answers[0].r_class, ResourceClass::CH);
+        assert_eq!(decoded.answers[0].r_data, packet.answers[0].r_data);
+    }
+
+    #[test]
+    fn modern_types_round_trip() {
+        let params = vec![
+            SvcParam::Mandatory(vec![1]),
+            SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+            SvcParam::NoDefaultAlpn,
+            SvcParam::Port(8443),
+            SvcParam::Ipv4Hint(vec![
+                Ipv4Addr::new(192, 0, 2, 1),
+                Ipv4Addr::new(192, 0, 2, 2),
+            ]),
+            SvcParam::Ech(vec![1, 2, 3]),
+            SvcParam::Ipv6Hint(vec![Ipv6Addr::LOCALHOST]),
+        ];
+        let rdata = [
+            RData::AAAA(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            RData::SRV {
+                priority: 10,
+                weight: 60,
+                port: 5060,
+                target: name("sip.example.com"),
+            },
+            RData::NAPTR {
+                order: 100,
+                preference: 10,
+                flags: b"S".to_vec(),
+                services: b"SIP+D2U".to_vec(),
+                regexp: Vec::new(),
+                replacement: name("_sip._udp.example.com"),
+            },
+            RData::DName(name("example.net")),
+            RData::SVCB {
+                priority: 0,
+                target: name("svc.example.net"),
+                params: Vec::new(),
+            },
+            RData::HTTPS {
+                priority: 1,
+                target: Name::root(),
+                params,
+            },
+            RData::CAA {
+                flags: 128,
+                tag: b"issue".to_vec(),
+                value: b"ca.example.net".to_vec(),
+            },
+        ];
+        for r_data in rdata {
+            round_trip(r_data);
+        }
+    }
+
+    #[test]
+    fn sshfp_round_trip() {
+        round_trip(RData::SSHFP {
+            algorithm: 4,
+            fp_type: 2,
+            fingerprint: (0..32).collect(),
+        });
+    }
+
+    #[test]
+    fn tlsa_round_trip() {
+        for cert_data in [vec![], (0..32).collect()] {
+            round_trip(RData::TLSA {
+                cert_usage: 3,
+                selector: 1,
+                matching_type: 1,
+                cert_data,
+            });
+        }
     }
 }
@@ -1,35 +1,52 @@
-pub struct DnsPacketBuffer {
-    pub buf: [u8; 512],
+use crate::error::DnsError;
+
+/**
+    A read cursor over a complete DNS message. The message may be of any
+    size (classic 512 octet UDP, EDNS payloads, 65535 octet TCP messages),
+    bounds are checked against the length of the borrowed slice.
+*/
+pub struct DnsPacketBuffer<'a> {
+    pub buf: &'a [u8],
     pub pos: usize,
 }
 
-impl DnsPacketBuffer {
-    pub fn new() -> DnsPacketBuffer {
-        DnsPacketBuffer {
-            buf: [0; 512],
-            pos: 0,
-        }
+impl<'a> DnsPacketBuffer<'a> {
+    pub fn new(buf: &'a [u8]) -> DnsPacketBuffer<'a> {
+        DnsPacketBuffer { buf, pos: 0 }
     }
 
     pub fn pos(&self) -> usize {
         self.pos
     }
 
+    /// The length of the message
+    pub fn len(&self) -> usize {
+        self.buf.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.buf.is_empty()
+    }
+
     /// Step the buffer position forward a specific number of steps
-    pub fn step(&mut self, steps: usize) -> Result<(), &'static str> {
-        if self.pos + steps >= 512 {
-            return Err("End of buffer");
+    pub fn step(&mut self, steps: usize) -> Result<(), DnsError> {
+        let pos = self.pos.checked_add(steps);
+        match pos {
+            Some(pos) if pos <= self.buf.len() => self.pos = pos,
+            _ => {
+                return Err(DnsError::EndOfBuffer {
+                    offset: self.pos.saturating_add(steps),
+                })
+            }
         }
 
-        self.pos += steps;
-
         Ok(())
     }
 
-    /// Change the buffer position
-    pub fn seek(&mut self, pos: usize) -> Result<(), &'static str> {
-        if self.pos >= 512 {
-            return Err("End of buffer");
+    /// Change the buffer position. Seeking to the end of the message is allowed
+    pub fn seek(&mut self, pos: usize) -> Result<(), DnsError> {
+        if pos > self.buf.len() {
+            return Err(DnsError::EndOfBuffer { offset: pos });
         }
 
         self.pos = pos;
@@ -38,31 +55,28 @@ impl DnsPacketBuffer {
     }
 
     /// Read a single byte and move the position one step forward
-    pub fn read(&mut self) -> Result<u8, &'static str> {
-        if self.pos >= 512 {
-            return Err("End of buffer");
-        }
-        let res = self.buf[self.pos];
+    pub fn read(&mut self) -> Result<u8, DnsError> {
+        let res = self.get(self.pos)?;
         self.pos += 1;
 
         Ok(res)
     }
 
     /// Get a single byte, without changing the buffer position
-    pub fn get(&mut self, pos: usize) -> Result<u8, &'static str> {
-        if pos >= 512 {
-            return Err("End of buffer");
+    pub fn get(&self, pos: usize) -> Result<u8, DnsError> {
+        match self.buf.get(pos) {
+            Some(res) => Ok(*res),
+            None => Err(DnsError::EndOfBuffer { offset: pos }),
         }
-        Ok(self.buf[pos])
     }
 
     /// Get a range of bytes
-    pub fn get_range(&mut self, start: usize, len: usize) -> Result<&[u8], &'static str> {
+    pub fn get_range(&self, start: usize, len: usize) -> Result<&'a [u8], DnsError> {
         let end = match start.checked_add(len) {
-            None => return Err("End of buffer"),
+            None => return Err(DnsError::EndOfBuffer { offset: start }),
             Some(result) => {
-                if start + len >= 512 {
-                    return Err("End of buffer");
+                if result > self.buf.len() {
+                    return Err(DnsError::EndOfBuffer { offset: result });
                 }
                 result
             }
@@ -71,18 +85,18 @@ impl DnsPacketBuffer {
     }
 
     /// Read two bytes, stepping two steps forward
-    pub fn read_u16(&mut self) -> Result<u16, &'static str> {
+    pub fn read_u16(&mut self) -> Result<u16, DnsError> {
         let res = ((self.read()? as u16) << 8) | (self.read()? as u16);
 
         Ok(res)
     }
 
     /// Read four bytes, stepping four steps forward
-    pub fn read_u32(&mut self) -> Result<u32, &'static str> {
+    pub fn read_u32(&mut self) -> Result<u32, DnsError> {
         let res = ((self.read()? as u32) << 24)
             | ((self.read()? as u32) << 16)
             | ((self.read()? as u32) << 8)
-            | ((self.read()? as u32) << 0);
+            | (self.read()? as u32);
 
         Ok(res)
     }
@@ -0,0 +1,333 @@
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::protocol::dns_edns::Edns;
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_question::DnsQuestion;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::edns_option_enum::EdnsOption;
+use crate::protocol::opcode_enum::OpCode;
+use crate::protocol::question_class_enum::QuestionClass;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::rcode_enum::RCode;
+use crate::protocol::resource_class_enum::ResourceClass;
+
+/**
+    A message ID that is hard to guess, as RFC 5452 asks of resolvers. Every
+    `RandomState` is keyed from the operating system's randomness, so the
+    hash of the current time under a fresh one serves without pulling in a
+    random number generator.
+*/
+pub fn random_id() -> u16 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(nanos);
+    hasher.finish() as u16
+}
+
+/**
+    Builds a query message. It starts as a standard query with a random ID
+    and recursion desired, without EDNS until an EDNS setting is made.
+*/
+pub struct QueryBuilder {
+    packet: DnsPacket,
+}
+
+impl QueryBuilder {
+    pub fn new() -> QueryBuilder {
+        let mut packet = DnsPacket::new();
+        packet.header.id = random_id();
+        packet.header.opcode = OpCode::Query;
+        packet.header.recursion_desired = true;
+
+        QueryBuilder { packet }
+    }
+
+    pub fn id(mut self, id: u16) -> QueryBuilder {
+        self.packet.header.id = id;
+        self
+    }
+
+    pub fn opcode(mut self, opcode: OpCode) -> QueryBuilder {
+        self.packet.header.opcode = opcode;
+        self
+    }
+
+    /// Ask for `q_type` records of `name` in the Internet class
+    pub fn question(self, name: &Name, q_type: QuestionType) -> QueryBuilder {
+        let q_class = QuestionClass::Base(ResourceClass::IN);
+        self.add_question(DnsQuestion::new(name.clone(), q_type, q_class))
+    }
+
+    pub fn add_question(mut self, question: DnsQuestion) -> QueryBuilder {
+        self.packet.questions.push(question);
+        self
+    }
+
+    pub fn recursion_desired(mut self, value: bool) -> QueryBuilder {
+        self.packet.header.recursion_desired = value;
+        self
+    }
+
+    pub fn checking_disabled(mut self, value: bool) -> QueryBuilder {
+        self.packet.header.checking_disabled = value;
+        self
+    }
+
+    /// Ask for authenticated data to be indicated in the response (RFC 6840)
+    pub fn authed_data(mut self, value: bool) -> QueryBuilder {
+        self.packet.header.authed_data = value;
+        self
+    }
+
+    /// Set the DO bit, which needs EDNS
+    pub fn dnssec_ok(mut self, value: bool) -> QueryBuilder {
+        self.edns().dnssec_ok = value;
+        self
+    }
+
+    /// Advertise the UDP payload size the response may use, which needs EDNS
+    pub fn edns_payload_size(mut self, size: u16) -> QueryBuilder {
+        self.edns().udp_payload_size = size;
+        self
+    }
+
+    pub fn edns_option(mut self, option: EdnsOption) -> QueryBuilder {
+        self.edns().options.push(option);
+        self
+    }
+
+    /// Add a record to the additional section
+    pub fn additional(mut self, record: DnsRecord) -> QueryBuilder {
+        self.packet.resources.push(record);
+        self
+    }
+
+    /// The message. Its header counts are derived when it is encoded
+    pub fn build(self) -> DnsPacket {
+        self.packet
+    }
+
+    fn edns(&mut self) -> &mut Edns {
+        self.packet.edns.get_or_insert_with(Edns::new)
+    }
+}
+
+impl Default for QueryBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/**
+    Builds the response to a query. The ID, OPCODE, RD and CD bits and the
+    questions are copied from the query, and EDNS is answered with EDNS
+    when the query used it (RFC 6891 section 7).
+*/
+pub struct ResponseBuilder {
+    packet: DnsPacket,
+    r_code: RCode,
+}
+
+impl ResponseBuilder {
+    pub fn new(query: &DnsPacket) -> ResponseBuilder {
+        let mut packet = DnsPacket::new();
+        packet.header.id = query.header.id;
+        packet.header.response = true;
+        packet.header.opcode = query.header.opcode;
+        packet.header.recursion_desired = query.header.recursion_desired;
+        packet.header.checking_disabled = query.header.checking_disabled;
+        packet.questions = query.questions.clone();
+        packet.edns = query.edns.as_ref().map(|edns| Edns {
+            dnssec_ok: edns.dnssec_ok,
+            ..Edns::new()
+        });
+
+        ResponseBuilder {
+            packet,
+            r_code: RCode::NoError,
+        }
+    }
+
+    pub fn authoritative(mut self, value: bool) -> ResponseBuilder {
+        self.packet.header.authoritative_answer = value;
+        self
+    }
+
+    pub fn recursion_available(mut self, value: bool) -> ResponseBuilder {
+        self.packet.header.recursion_available = value;
+        self
+    }
+
+    pub fn authed_data(mut self, value: bool) -> ResponseBuilder {
+        self.packet.header.authed_data = value;
+        self
+    }
+
+    pub fn truncated(mut self, value: bool) -> ResponseBuilder {
+        self.packet.header.truncated_message = value;
+        self
+    }
+
+    /// Set the RCODE, which may be an extended one carried by EDNS
+    pub fn r_code(mut self, r_code: RCode) -> ResponseBuilder {
+        self.r_code = r_code;
+        self
+    }
+
+    pub fn answer(mut self, record: DnsRecord) -> ResponseBuilder {
+        self.packet.answers.push(record);
+        self
+    }
+
+    pub fn authority(mut self, record: DnsRecord) -> ResponseBuilder {
+        self.packet.authorities.push(record);
+        self
+    }
+
+    pub fn additional(mut self, record: DnsRecord) -> ResponseBuilder {
+        self.packet.resources.push(record);
+        self
+    }
+
+    /// Replace the EDNS information of the response, or drop it with `None`
+    pub fn edns(mut self, edns: Option<Edns>) -> ResponseBuilder {
+        self.packet.edns = edns;
+        self
+    }
+
+    /// The message. Its header counts are derived when it is encoded
+    pub fn build(mut self) -> DnsPacket {
+        self.packet.set_extended_r_code(self.r_code);
+        self.packet
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+    use crate::protocol::dns_header::DnsHeader;
+    use crate::protocol::resource_data_enum::RData;
+    use crate::protocol::resource_type_enum::ResourceType;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn a(last_octet: u8) -> DnsRecord {
+        DnsRecord::with_r_data(
+            name("www.example.com"),
+            300,
+            RData::A(Ipv4Addr::new(192, 0, 2, last_octet)),
+        )
+    }
+
+    /// The header of `packet` as it is encoded
+    fn encoded_header(packet: &DnsPacket) -> DnsHeader {
+        DnsHeader::from_bytes(&packet.to_bytes().unwrap()[..12]).unwrap()
+    }
+
+    fn a_query() -> QueryBuilder {
+        QueryBuilder::new().question(
+            &name("www.example.com"),
+            QuestionType::Base(ResourceType::A),
+        )
+    }
+
+    #[test]
+    fn derives_header_counts_from_the_sections() {
+        let query = a_query().additional(a(9)).dnssec_ok(true).build();
+        let header = encoded_header(&query);
+        assert_eq!(
+            (
+                header.questions,
+                header.answers,
+                header.authoritative_entries,
+                header.resource_entries
+            ),
+            (1, 0, 0, 2)
+        );
+
+        let response = DnsPacket::response_to(&query)
+            .answer(a(1))
+            .answer(a(2))
+            .authority(a(3))
+            .build();
+        let header = encoded_header(&response);
+        assert_eq!(
+            (
+                header.questions,
+                header.answers,
+                header.authoritative_entries,
+                header.resource_entries
+            ),
+            (1, 2, 1, 1)
+        );
+    }
+
+    #[test]
+    fn responses_copy_the_query() {
+        let query = a_query()
+            .id(0x1234)
+            .opcode(OpCode::Notify)
+            .checking_disabled(true)
+            .build();
+        let response = DnsPacket::response_to(&query).build();
+
+        assert!(response.header.response);
+        assert_eq!(response.header.id, 0x1234);
+        assert_eq!(response.header.opcode, OpCode::Notify);
+        assert!(response.header.recursion_desired);
+        assert!(response.header.checking_disabled);
+        assert_eq!(response.questions, query.questions);
+        assert!(response.answers.is_empty());
+
+        let query = a_query().recursion_desired(false).build();
+        assert!(
+            !DnsPacket::response_to(&query)
+                .build()
+                .header
+                .recursion_desired
+        );
+    }
+
+    #[test]
+    fn answers_edns_with_edns() {
+        let plain = a_query().build();
+        assert!(DnsPacket::response_to(&plain).build().edns.is_none());
+
+        let query = a_query().dnssec_ok(true).build();
+        let edns = DnsPacket::response_to(&query).build().edns.unwrap();
+        assert!(edns.dnssec_ok);
+
+        // an extended RCODE needs EDNS even when the query had none
+        let response = DnsPacket::response_to(&plain)
+            .r_code(RCode::BadCookie)
+            .build();
+        assert_eq!(response.extended_r_code(), RCode::BadCookie);
+    }
+
+    #[test]
+    fn edns_settings_add_one_opt() {
+        let query = a_query()
+            .edns_payload_size(4096)
+            .dnssec_ok(true)
+            .edns_option(EdnsOption::Nsid(Vec::new()))
+            .build();
+        assert!(query.resources.is_empty());
+
+        let decoded = DnsPacket::from_bytes(&query.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.header.resource_entries, 1);
+        let edns = decoded.edns.unwrap();
+        assert_eq!(edns.udp_payload_size, 4096);
+        assert!(edns.dnssec_ok);
+        assert_eq!(edns.options, [EdnsOption::Nsid(Vec::new())]);
+    }
+}
@@ -0,0 +1,613 @@
+use std::fmt;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_buffer::DnsPacketBuffer;
+use crate::error::{DnsError, Section};
+use crate::protocol::dns_header::DnsHeader;
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::question_class_enum::QuestionClass;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_type_enum::ResourceType;
+
+/**
+    The number of compression pointers a single name may follow. Pointers
+    must lead backward, so following them always ends; this only bounds
+    the work, at the 127 labels a name of 255 octets can have at most.
+*/
+const MAX_JUMPS: usize = 127;
+
+/**
+    A lazily parsed, borrowed view of a DNS message.
+
+    Nothing is decoded up front: header fields are read from the fixed
+    12 octet header on access, and every section is walked on demand by
+    iterators handing out views into the original bytes. Names are never
+    copied, their labels are iterated in place following compression
+    pointers.
+*/
+#[derive(Copy, Clone, Debug)]
+pub struct DnsPacketRef<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> DnsPacketRef<'a> {
+    /// Wrap a message, only checking that it is long enough to hold a header
+    pub fn new(buf: &'a [u8]) -> Result<DnsPacketRef<'a>, DnsError> {
+        if buf.len() < 12 {
+            return Err(DnsError::EndOfBuffer { offset: buf.len() });
+        }
+
+        Ok(DnsPacketRef { buf })
+    }
+
+    /// The message this view borrows
+    pub fn as_bytes(&self) -> &'a [u8] {
+        self.buf
+    }
+
+    /// Decode the header, which does not allocate
+    pub fn header(&self) -> Result<DnsHeader, DnsError> {
+        DnsPacket::decode_header(&mut DnsPacketBuffer::new(self.buf))
+    }
+
+    pub fn id(&self) -> u16 {
+        self.u16_at(0)
+    }
+
+    /// The raw 16 bit word holding QR, OPCODE, AA, TC, RD, RA, Z, AD, CD and RCODE
+    pub fn flags(&self) -> u16 {
+        self.u16_at(2)
+    }
+
+    pub fn question_count(&self) -> u16 {
+        self.u16_at(4)
+    }
+
+    pub fn answer_count(&self) -> u16 {
+        self.u16_at(6)
+    }
+
+    pub fn authority_count(&self) -> u16 {
+        self.u16_at(8)
+    }
+
+    pub fn additional_count(&self) -> u16 {
+        self.u16_at(10)
+    }
+
+    pub fn questions(&self) -> Questions<'a> {
+        Questions {
+            buf: self.buf,
+            pos: 12,
+            index: 0,
+            count: self.question_count() as usize,
+        }
+    }
+
+    pub fn answers(&self) -> Records<'a> {
+        self.records(Section::Answer, self.answer_count())
+    }
+
+    pub fn authorities(&self) -> Records<'a> {
+        self.records(Section::Authority, self.authority_count())
+    }
+
+    pub fn additionals(&self) -> Records<'a> {
+        self.records(Section::Additional, self.additional_count())
+    }
+
+    fn u16_at(&self, pos: usize) -> u16 {
+        ((self.buf[pos] as u16) << 8) | (self.buf[pos + 1] as u16)
+    }
+
+    /// Walk the sections preceding `section` to find where it starts
+    fn records(&self, section: Section, count: u16) -> Records<'a> {
+        let mut records = Records {
+            buf: self.buf,
+            pos: 12,
+            section,
+            index: 0,
+            count: count as usize,
+            error: None,
+        };
+
+        let mut start = skip_section(self.buf, 12, self.question_count(), Section::Question);
+        if section != Section::Answer {
+            start = start
+                .and_then(|pos| skip_section(self.buf, pos, self.answer_count(), Section::Answer));
+        }
+        if section == Section::Additional {
+            start = start.and_then(|pos| {
+                skip_section(self.buf, pos, self.authority_count(), Section::Authority)
+            });
+        }
+
+        match start {
+            Ok(pos) => records.pos = pos,
+            Err(e) => records.error = Some(e),
+        }
+
+        records
+    }
+}
+
+/// The position following the `count` entries of `section` starting at `pos`
+fn skip_section(buf: &[u8], pos: usize, count: u16, section: Section) -> Result<usize, DnsError> {
+    let mut pos = pos;
+    for index in 0..count as usize {
+        let end = match section {
+            Section::Question => QuestionRef::parse(buf, pos).map(|(_, end)| end),
+            _ => RecordRef::parse(buf, pos).map(|(_, end)| end),
+        };
+        pos = end.map_err(|e| e.in_record(section, index))?;
+    }
+
+    Ok(pos)
+}
+
+/// A question borrowed from a message
+#[derive(Copy, Clone, Debug)]
+pub struct QuestionRef<'a> {
+    pub name: NameRef<'a>,
+    pub q_type: QuestionType,
+    pub q_class: QuestionClass,
+}
+
+impl<'a> QuestionRef<'a> {
+    /// Parse the question at `pos`, returning it with the position following it
+    fn parse(buf: &'a [u8], pos: usize) -> Result<(QuestionRef<'a>, usize), DnsError> {
+        let name = NameRef::new(buf, pos);
+
+        let mut buffer = DnsPacketBuffer::new(buf);
+        buffer.seek(name.end()?)?;
+        let q_type = buffer.read_u16()?.into();
+        let q_class = buffer.read_u16()?.into();
+
+        Ok((
+            QuestionRef {
+                name,
+                q_type,
+                q_class,
+            },
+            buffer.pos(),
+        ))
+    }
+}
+
+/// A resource record borrowed from a message, its RDATA left undecoded
+#[derive(Copy, Clone)]
+pub struct RecordRef<'a> {
+    pub name: NameRef<'a>,
+    pub r_type: ResourceType,
+    pub r_class: ResourceClass,
+    pub ttl: u32,
+    /// The raw RDATA. Names inside it may point elsewhere in the message
+    pub r_data: &'a [u8],
+    /// The offset of the RDATA within the message
+    pub r_data_offset: usize,
+    buf: &'a [u8],
+}
+
+impl<'a> RecordRef<'a> {
+    /// Parse the record at `pos`, returning it with the position following it
+    fn parse(buf: &'a [u8], pos: usize) -> Result<(RecordRef<'a>, usize), DnsError> {
+        let name = NameRef::new(buf, pos);
+
+        let mut buffer = DnsPacketBuffer::new(buf);
+        buffer.seek(name.end()?)?;
+        let r_type = buffer.read_u16()?.into();
+        let r_class = buffer.read_u16()?.into();
+        let ttl = buffer.read_u32()?;
+        let rd_length = buffer.read_u16()?;
+
+        let r_data_offset = buffer.pos();
+        let r_data = buffer.get_range(r_data_offset, rd_length as usize)?;
+        buffer.step(rd_length as usize)?;
+
+        Ok((
+            RecordRef {
+                name,
+                r_type,
+                r_class,
+                ttl,
+                r_data,
+                r_data_offset,
+                buf,
+            },
+            buffer.pos(),
+        ))
+    }
+
+    /// Decode into an owned record with typed RDATA
+    pub fn to_record(&self) -> Result<DnsRecord, DnsError> {
+        let mut buffer = DnsPacketBuffer::new(self.buf);
+        buffer.seek(self.name.offset)?;
+        DnsPacket::decode_record(&mut buffer)
+    }
+}
+
+impl fmt::Debug for RecordRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RecordRef")
+            .field("name", &self.name)
+            .field("r_type", &self.r_type)
+            .field("r_class", &self.r_class)
+            .field("ttl", &self.ttl)
+            .field("r_data", &self.r_data)
+            .field("r_data_offset", &self.r_data_offset)
+            .finish()
+    }
+}
+
+/// Iterates the question section of a message
+pub struct Questions<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    index: usize,
+    count: usize,
+}
+
+impl<'a> Iterator for Questions<'a> {
+    type Item = Result<QuestionRef<'a>, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.count {
+            return None;
+        }
+
+        let index = self.index;
+        self.index += 1;
+
+        match QuestionRef::parse(self.buf, self.pos) {
+            Ok((question, end)) => {
+                self.pos = end;
+                Some(Ok(question))
+            }
+            Err(e) => {
+                self.index = self.count;
+                Some(Err(e.in_record(Section::Question, index)))
+            }
+        }
+    }
+}
+
+/// Iterates the resource records of one section of a message
+pub struct Records<'a> {
+    buf: &'a [u8],
+    pos: usize,
+    section: Section,
+    index: usize,
+    count: usize,
+    /// A failure to locate the start of the section, reported on first use
+    error: Option<DnsError>,
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<RecordRef<'a>, DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(e) = self.error.take() {
+            self.index = self.count;
+            return Some(Err(e));
+        }
+        if self.index >= self.count {
+            return None;
+        }
+
+        let index = self.index;
+        self.index += 1;
+
+        match RecordRef::parse(self.buf, self.pos) {
+            Ok((record, end)) => {
+                self.pos = end;
+                Some(Ok(record))
+            }
+            Err(e) => {
+                self.index = self.count;
+                Some(Err(e.in_record(self.section, index)))
+            }
+        }
+    }
+}
+
+/// A possibly compressed domain name inside a message
+#[derive(Copy, Clone)]
+pub struct NameRef<'a> {
+    buf: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> NameRef<'a> {
+    pub fn new(buf: &'a [u8], offset: usize) -> NameRef<'a> {
+        NameRef { buf, offset }
+    }
+
+    /// The offset of the name within the message
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// The labels of the name, without the null label of the root
+    pub fn labels(&self) -> Labels<'a> {
+        Labels::new(self.buf, self.offset)
+    }
+
+    /// The position following the name where it appears, i.e. after its null
+    /// label or after the first compression pointer
+    pub fn end(&self) -> Result<usize, DnsError> {
+        let mut labels = self.labels();
+        for label in &mut labels {
+            label?;
+        }
+
+        Ok(labels.end().unwrap_or(self.offset))
+    }
+
+    /// The name decompressed
+    pub fn to_name(&self) -> Result<Name, DnsError> {
+        Name::from_labels(self.labels().collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Compare to a name, ignoring ASCII case
+    pub fn eq_ignore_case(&self, name: &Name) -> bool {
+        let mut expected = name.labels();
+
+        for label in self.labels() {
+            match (label, expected.next()) {
+                (Ok(label), Some(other)) if label.eq_ignore_ascii_case(other) => {}
+                _ => return false,
+            }
+        }
+
+        expected.next().is_none()
+    }
+}
+
+impl fmt::Display for NameRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.to_name().map_err(|_| fmt::Error)?;
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Debug for NameRef<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_name() {
+            Ok(name) => write!(f, "NameRef({:?} @ {})", name.to_string(), self.offset),
+            Err(_) => write!(f, "NameRef(<malformed> @ {})", self.offset),
+        }
+    }
+}
+
+/**
+    Iterates the labels of a name in place, following compression pointers.
+    This is the name reading logic of the whole crate: `decode_name` builds
+    its names from these labels.
+*/
+pub struct Labels<'a> {
+    buf: &'a [u8],
+    start: usize,
+    pos: usize,
+    end: Option<usize>,
+    jumps_performed: usize,
+    done: bool,
+}
+
+impl<'a> Labels<'a> {
+    pub fn new(buf: &'a [u8], pos: usize) -> Labels<'a> {
+        Labels {
+            buf,
+            start: pos,
+            pos,
+            end: None,
+            jumps_performed: 0,
+            done: false,
+        }
+    }
+
+    /// The position following the name, known once the labels were iterated
+    /// up to the null label or the first pointer was followed
+    pub fn end(&self) -> Option<usize> {
+        self.end
+    }
+
+    fn get(&self, pos: usize) -> Result<u8, DnsError> {
+        match self.buf.get(pos) {
+            Some(res) => Ok(*res),
+            None => Err(DnsError::EndOfBuffer { offset: pos }),
+        }
+    }
+
+    fn next_label(&mut self) -> Result<Option<&'a [u8]>, DnsError> {
+        loop {
+            let len = self.get(self.pos)?;
+
+            /*
+            The pointer takes the form of a two octet sequence:
+                +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+                | 1  1|                OFFSET                   |
+                +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+             */
+            if (len & 0xC0) == 0xC0 {
+                if self.jumps_performed >= MAX_JUMPS {
+                    return Err(DnsError::TooManyJumps {
+                        offset: self.start,
+                        limit: MAX_JUMPS,
+                    });
+                }
+
+                let l = self.get(self.pos + 1)? as u16;
+                let h = ((len as u16) ^ 0xC0) << 8;
+                let offset = (h | l) as usize;
+
+                // a pointer may only refer to a prior occurrence
+                if offset >= self.pos {
+                    return Err(DnsError::PointerLoop {
+                        offset: self.pos,
+                        target: offset,
+                    });
+                }
+
+                if self.end.is_none() {
+                    self.end = Some(self.pos + 2);
+                }
+                self.pos = offset;
+                self.jumps_performed += 1;
+            } else if (len & 0xC0) != 0 {
+                return Err(DnsError::InvalidLabelType {
+                    offset: self.pos,
+                    octet: len,
+                });
+            } else {
+                let start = self.pos + 1;
+
+                if len == 0 {
+                    if self.end.is_none() {
+                        self.end = Some(start);
+                    }
+                    return Ok(None);
+                }
+
+                let end = start + len as usize;
+                if end > self.buf.len() {
+                    return Err(DnsError::EndOfBuffer { offset: end });
+                }
+                self.pos = end;
+
+                return Ok(Some(&self.buf[start..end]));
+            }
+        }
+    }
+}
+
+impl<'a> Iterator for Labels<'a> {
+    type Item = Result<&'a [u8], DnsError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+
+        match self.next_label() {
+            Ok(Some(label)) => Some(Ok(label)),
+            Ok(None) => {
+                self.done = true;
+                None
+            }
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn labels(buf: &[u8], pos: usize) -> Result<Vec<&[u8]>, DnsError> {
+        Labels::new(buf, pos).collect()
+    }
+
+    /**
+        A root label followed by `count` names, each of `label` and a pointer
+        to the name before. The last of them follows `count` pointers.
+    */
+    fn pointer_chain(count: usize, label: &[u8]) -> Vec<u8> {
+        let mut buf = vec![0];
+        let mut target = 0;
+        for _ in 0..count {
+            let pos = buf.len();
+            if !label.is_empty() {
+                buf.push(label.len() as u8);
+                buf.extend_from_slice(label);
+            }
+            buf.extend_from_slice(&(0xC000 | target as u16).to_be_bytes());
+            target = pos;
+        }
+        buf
+    }
+
+    #[test]
+    fn follows_pointers_to_prior_labels() {
+        let buf = b"\x03www\x06google\x03com\x00\x04mail\xC0\x04";
+
+        let labels = labels(buf, 16).unwrap();
+        assert_eq!(labels, [&b"mail"[..], b"google", b"com"]);
+        assert_eq!(NameRef::new(buf, 16).end().unwrap(), buf.len());
+    }
+
+    #[test]
+    fn rejects_pointers_to_themselves() {
+        assert_eq!(
+            labels(b"\x03www\xC0\x04", 0),
+            Err(DnsError::PointerLoop {
+                offset: 4,
+                target: 4
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_forward_pointers() {
+        // two names pointing at each other
+        let buf = b"\xC0\x02\xC0\x00";
+
+        assert_eq!(
+            labels(buf, 0),
+            Err(DnsError::PointerLoop {
+                offset: 0,
+                target: 2
+            })
+        );
+        assert!(labels(buf, 2).is_err());
+    }
+
+    #[test]
+    fn follows_chains_as_long_as_a_name_can_need() {
+        // a name of 127 one-octet labels, each compressed against the next
+        let buf = pointer_chain(MAX_JUMPS, b"a");
+        let start = buf.len() - 4;
+
+        assert_eq!(labels(&buf, start).unwrap().len(), MAX_JUMPS);
+        assert_eq!(NameRef::new(&buf, start).to_name().unwrap().wire_len(), 255);
+    }
+
+    #[test]
+    fn limits_the_pointers_followed() {
+        let buf = pointer_chain(MAX_JUMPS + 1, b"");
+        assert_eq!(
+            labels(&buf, buf.len() - 2),
+            Err(DnsError::TooManyJumps {
+                offset: buf.len() - 2,
+                limit: MAX_JUMPS,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_messages_with_pointer_loops() {
+        let mut message = vec![0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 0, 0];
+        message.extend_from_slice(b"\xC0\x0C\x00\x01\x00\x01");
+
+        let question = DnsPacketRef::new(&message)
+            .unwrap()
+            .questions()
+            .next()
+            .unwrap();
+        assert_eq!(
+            question.err(),
+            Some(
+                DnsError::PointerLoop {
+                    offset: 12,
+                    target: 12
+                }
+                .in_record(Section::Question, 0)
+            )
+        );
+        assert!(DnsPacket::from_bytes(&message).is_err());
+    }
+}
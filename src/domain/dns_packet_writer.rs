@@ -0,0 +1,131 @@
+use std::collections::HashMap;
+
+use crate::error::DnsError;
+use crate::protocol::dns_name::Name;
+
+/// The largest message that can be carried, bounded by the TCP length prefix
+pub const MAX_MESSAGE_SIZE: usize = 65535;
+
+/**
+    A growable buffer a DNS message is encoded into. It grows as needed up
+    to `MAX_MESSAGE_SIZE`; whether the result fits the transport (512 octets
+    for classic UDP, the advertised EDNS payload size) is for the caller to
+    decide.
+*/
+pub struct DnsPacketWriter {
+    pub buf: Vec<u8>,
+    /// Whether names written to the buffer may be replaced by pointers to a
+    /// prior occurrence. Disable it for canonical (DNSSEC) form.
+    pub compression: bool,
+    /// Offsets of the name suffixes written so far, which match ignoring
+    /// case (e.g. google.com. and com. for www.google.com.)
+    names: HashMap<Name, usize>,
+}
+
+impl DnsPacketWriter {
+    pub fn new() -> DnsPacketWriter {
+        DnsPacketWriter {
+            buf: Vec::with_capacity(512),
+            compression: true,
+            names: HashMap::new(),
+        }
+    }
+
+    /// A buffer that never compresses names, as required for canonical form
+    pub fn canonical() -> DnsPacketWriter {
+        DnsPacketWriter {
+            compression: false,
+            ..DnsPacketWriter::new()
+        }
+    }
+
+    /// The position the next byte is written to
+    pub fn pos(&self) -> usize {
+        self.buf.len()
+    }
+
+    /// Write a single byte and move the position one step forward
+    pub fn write(&mut self, val: u8) -> Result<(), DnsError> {
+        if self.buf.len() >= MAX_MESSAGE_SIZE {
+            return Err(DnsError::EndOfBuffer {
+                offset: self.buf.len(),
+            });
+        }
+        self.buf.push(val);
+
+        Ok(())
+    }
+
+    /// Write one byte, stepping one step forward
+    pub fn write_u8(&mut self, val: u8) -> Result<(), DnsError> {
+        self.write(val)
+    }
+
+    /// Write two bytes, stepping two steps forward
+    pub fn write_u16(&mut self, val: u16) -> Result<(), DnsError> {
+        self.write_range(&val.to_be_bytes())
+    }
+
+    /// Write four bytes, stepping four steps forward
+    pub fn write_u32(&mut self, val: u32) -> Result<(), DnsError> {
+        self.write_range(&val.to_be_bytes())
+    }
+
+    /// Write a range of bytes, stepping forward by its length
+    pub fn write_range(&mut self, bytes: &[u8]) -> Result<(), DnsError> {
+        if self.buf.len() + bytes.len() > MAX_MESSAGE_SIZE {
+            return Err(DnsError::EndOfBuffer {
+                offset: self.buf.len() + bytes.len(),
+            });
+        }
+        self.buf.extend_from_slice(bytes);
+
+        Ok(())
+    }
+
+    /// Set a single byte that was already written, without changing the position
+    pub fn set(&mut self, pos: usize, val: u8) -> Result<(), DnsError> {
+        match self.buf.get_mut(pos) {
+            Some(byte) => *byte = val,
+            None => return Err(DnsError::EndOfBuffer { offset: pos }),
+        }
+
+        Ok(())
+    }
+
+    /// Set two bytes that were already written, without changing the position
+    pub fn set_u16(&mut self, pos: usize, val: u16) -> Result<(), DnsError> {
+        self.set(pos, (val >> 8) as u8)?;
+        self.set(pos + 1, (val & 0xFF) as u8)?;
+
+        Ok(())
+    }
+
+    /// Look up the offset of a previously written name suffix
+    pub fn name_offset(&self, suffix: &Name) -> Option<usize> {
+        self.names.get(suffix).copied()
+    }
+
+    /// Remember where a name suffix was written so that later names can point
+    /// to it. Only offsets addressable by the 14 bit pointer are kept.
+    pub fn add_name(&mut self, suffix: Name, pos: usize) {
+        if pos <= 0x3FFF {
+            self.names.entry(suffix).or_insert(pos);
+        }
+    }
+
+    /// The bytes written so far
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.buf
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.buf
+    }
+}
+
+impl Default for DnsPacketWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -1,2 +1,5 @@
 pub mod dns_packet;
 pub mod dns_packet_buffer;
+pub mod dns_packet_builder;
+pub mod dns_packet_ref;
+pub mod dns_packet_writer;
@@ -0,0 +1,323 @@
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+/// The part of a message a question or resource record was read from or written to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Section {
+    Question,
+    Answer,
+    Authority,
+    Additional,
+}
+
+impl fmt::Display for Section {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Section::Question => write!(f, "question"),
+            Section::Answer => write!(f, "answer"),
+            Section::Authority => write!(f, "authority"),
+            Section::Additional => write!(f, "additional"),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DnsError {
+    /// An access at `offset` fell outside the buffer
+    EndOfBuffer { offset: usize },
+    /// A name at `offset` followed more compression pointers than allowed
+    TooManyJumps { offset: usize, limit: usize },
+    /**
+        The compression pointer at `offset` does not point to a prior
+        occurrence, which would let a crafted message loop forever.
+    */
+    PointerLoop { offset: usize, target: usize },
+    /// A label length octet at `offset` uses the reserved 01 or 10 high bits
+    InvalidLabelType { offset: usize, octet: u8 },
+    /// A label of the name is empty, e.g. "www..com"
+    EmptyLabel { name: String },
+    /// A label of the name is longer than 63 octets
+    LabelTooLong { name: String, len: usize },
+    /// The name is longer than 255 octets in wire format
+    NameTooLong { name: String },
+    /// A backslash in the text of a name escapes nothing, or `\DDD` exceeds 255
+    InvalidEscape { name: String },
+    /// Unicode text that UTS #46 processing cannot make a name of, e.g. with a disallowed character
+    Idna { name: String },
+    /// A <character-string> is longer than 255 octets
+    CharacterStringTooLong { len: usize },
+    /// The RDATA starting at `offset` did not take exactly RDLENGTH octets
+    RDataLength {
+        offset: usize,
+        r_type: u16,
+        rd_length: u16,
+        consumed: usize,
+    },
+    /// The RDATA field at `offset` is malformed for its TYPE
+    InvalidRData { offset: usize, r_type: u16 },
+    /// An OPT pseudo-record appears outside the additional section (RFC 6891 section 6.1.1)
+    MisplacedOpt,
+    /// The additional section holds more than one OPT pseudo-record (RFC 6891 section 6.1.1)
+    DuplicateOpt,
+    /**
+        Reading or writing the `index`th entry of `section` failed. The
+        message includes that of `source`, which is therefore not reported
+        as the source of the error as well.
+    */
+    Record {
+        section: Section,
+        index: usize,
+        source: Box<DnsError>,
+    },
+}
+
+impl DnsError {
+    /// Attach the section and index of the entry being processed
+    pub fn in_record(self, section: Section, index: usize) -> DnsError {
+        DnsError::Record {
+            section,
+            index,
+            source: Box::new(self),
+        }
+    }
+}
+
+impl fmt::Display for DnsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DnsError::EndOfBuffer { offset } => write!(f, "end of buffer at offset {}", offset),
+            DnsError::TooManyJumps { offset, limit } => write!(
+                f,
+                "limit of {} compression jumps exceeded in name at offset {}",
+                limit, offset
+            ),
+            DnsError::PointerLoop { offset, target } => write!(
+                f,
+                "compression pointer at offset {} does not point backward, to offset {}",
+                offset, target
+            ),
+            DnsError::InvalidLabelType { offset, octet } => {
+                write!(f, "invalid label type {:#04x} at offset {}", octet, offset)
+            }
+            DnsError::EmptyLabel { name } => write!(f, "empty label in name {:?}", name),
+            DnsError::LabelTooLong { name, len } => write!(
+                f,
+                "label of {} octets exceeds 63 octets in name {:?}",
+                len, name
+            ),
+            DnsError::NameTooLong { name } => {
+                write!(f, "name {:?} exceeds 255 octets of length", name)
+            }
+            DnsError::InvalidEscape { name } => write!(f, "invalid escape in name {:?}", name),
+            DnsError::Idna { name } => {
+                write!(f, "{:?} is not a valid internationalized domain name", name)
+            }
+            DnsError::CharacterStringTooLong { len } => {
+                write!(f, "character string of {} octets exceeds 255 octets", len)
+            }
+            DnsError::RDataLength {
+                offset,
+                r_type,
+                rd_length,
+                consumed,
+            } => write!(
+                f,
+                "RDATA of TYPE{} at offset {} has RDLENGTH {} but takes {} octets",
+                r_type, offset, rd_length, consumed
+            ),
+            DnsError::InvalidRData { offset, r_type } => {
+                write!(f, "malformed RDATA of TYPE{} at offset {}", r_type, offset)
+            }
+            DnsError::MisplacedOpt => write!(f, "OPT pseudo-record outside the additional section"),
+            DnsError::DuplicateOpt => write!(f, "more than one OPT pseudo-record"),
+            DnsError::Record {
+                section,
+                index,
+                source,
+            } => write!(f, "{} section entry {}: {}", section, index, source),
+        }
+    }
+}
+
+impl Error for DnsError {}
+
+/// Failure to get an answer from a name server
+#[derive(Debug)]
+pub enum ResolveError {
+    /// The socket failed, other than by timing out
+    Io(io::Error),
+    /// The message to send could not be encoded, or the response decoded
+    Dns(DnsError),
+    /// No matching response arrived within the timeout of any attempt
+    Timeout { attempts: usize },
+    /// None of the name servers of a zone could be reached or gave a usable response
+    NoNameservers { zone: String },
+    /// Resolving one query took more messages than allowed
+    WorkLimit { queries: usize },
+    /// A chain of aliases loops or grows too long
+    AliasLoop { name: String },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResolveError::Io(e) => write!(f, "i/o error: {}", e),
+            ResolveError::Dns(e) => write!(f, "malformed message: {}", e),
+            ResolveError::Timeout { attempts: 1 } => write!(f, "timed out"),
+            ResolveError::Timeout { attempts } => {
+                write!(f, "no response after {} attempts", attempts)
+            }
+            ResolveError::NoNameservers { zone } => {
+                write!(f, "no usable name server for zone {:?}", zone)
+            }
+            ResolveError::WorkLimit { queries } => {
+                write!(f, "gave up after {} queries", queries)
+            }
+            ResolveError::AliasLoop { name } => {
+                write!(f, "alias chain loops or is too long at {:?}", name)
+            }
+        }
+    }
+}
+
+impl Error for ResolveError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            ResolveError::Io(e) => Some(e),
+            ResolveError::Dns(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for ResolveError {
+    fn from(e: io::Error) -> ResolveError {
+        ResolveError::Io(e)
+    }
+}
+
+impl From<DnsError> for ResolveError {
+    fn from(e: DnsError) -> ResolveError {
+        ResolveError::Dns(e)
+    }
+}
+
+/// What is wrong with an entry of a master file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ZoneFileErrorKind {
+    /// The text cannot be split into entries, e.g. an unterminated quote
+    Syntax(String),
+    UnknownDirective(String),
+    /// A record leaves out its owner before any record has named one
+    MissingOwner,
+    /// A record leaves out its TTL with neither `$TTL` nor an earlier TTL to inherit
+    MissingTtl,
+    /// A relative name appears while no origin is known
+    MissingOrigin {
+        name: String,
+    },
+    UnknownType(String),
+    InvalidTtl(String),
+    /// A field of the entry does not parse as what it should be
+    InvalidField {
+        field: &'static str,
+        value: String,
+    },
+    MissingField(&'static str),
+    /// Tokens are left over after the last field of the entry
+    TrailingData(String),
+    /// A name is not a valid domain name
+    Name(DnsError),
+    /// RDATA in the RFC 3597 generic form does not decode as its TYPE
+    RData(DnsError),
+    /// A `$INCLUDE` file cannot be read
+    Include {
+        path: PathBuf,
+        message: String,
+    },
+    /// `$INCLUDE` files nest deeper than allowed, probably in a cycle
+    IncludeDepth {
+        limit: usize,
+    },
+    /// A `$GENERATE` directive is malformed
+    Generate(String),
+}
+
+impl fmt::Display for ZoneFileErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ZoneFileErrorKind::Syntax(message) => write!(f, "syntax error: {}", message),
+            ZoneFileErrorKind::UnknownDirective(directive) => {
+                write!(f, "unknown directive {}", directive)
+            }
+            ZoneFileErrorKind::MissingOwner => write!(f, "no owner name to inherit"),
+            ZoneFileErrorKind::MissingTtl => write!(f, "no TTL given and no $TTL to inherit"),
+            ZoneFileErrorKind::MissingOrigin { name } => {
+                write!(f, "relative name {:?} with no origin", name)
+            }
+            ZoneFileErrorKind::UnknownType(r_type) => write!(f, "unknown type {}", r_type),
+            ZoneFileErrorKind::InvalidTtl(ttl) => write!(f, "invalid TTL {:?}", ttl),
+            ZoneFileErrorKind::InvalidField { field, value } => {
+                write!(f, "invalid {} {:?}", field, value)
+            }
+            ZoneFileErrorKind::MissingField(field) => write!(f, "missing {}", field),
+            ZoneFileErrorKind::TrailingData(token) => {
+                write!(f, "unexpected {:?} after the last field", token)
+            }
+            ZoneFileErrorKind::Name(e) => write!(f, "invalid name: {}", e),
+            ZoneFileErrorKind::RData(e) => write!(f, "invalid generic RDATA: {}", e),
+            ZoneFileErrorKind::Include { path, message } => {
+                write!(f, "cannot include {}: {}", path.display(), message)
+            }
+            ZoneFileErrorKind::IncludeDepth { limit } => {
+                write!(f, "$INCLUDE nested more than {} deep", limit)
+            }
+            ZoneFileErrorKind::Generate(message) => write!(f, "invalid $GENERATE: {}", message),
+        }
+    }
+}
+
+/// An error in a master file, at the line its entry starts on
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ZoneFileError {
+    /// The file the entry is in, `None` for text parsed from memory
+    pub path: Option<PathBuf>,
+    pub line: usize,
+    pub kind: ZoneFileErrorKind,
+}
+
+impl fmt::Display for ZoneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.path {
+            Some(path) => write!(f, "{}:{}: {}", path.display(), self.line, self.kind),
+            None => write!(f, "line {}: {}", self.line, self.kind),
+        }
+    }
+}
+
+impl Error for ZoneFileError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match &self.kind {
+            ZoneFileErrorKind::Name(e) | ZoneFileErrorKind::RData(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn record_errors_report_their_cause_once() {
+        let error = DnsError::EndOfBuffer { offset: 40 }.in_record(Section::Answer, 1);
+
+        assert_eq!(
+            error.to_string(),
+            "answer section entry 1: end of buffer at offset 40"
+        );
+        assert!(error.source().is_none());
+    }
+}
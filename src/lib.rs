@@ -0,0 +1,8 @@
+pub mod domain;
+pub mod encoding;
+pub mod error;
+pub mod protocol;
+pub mod resolver;
+pub mod server;
+pub mod transport;
+pub mod zone_file;
@@ -1,53 +1,856 @@
-use std::ops::Index;
-use crate::domain::dns_packet::DnsPacket;
-use crate::domain::dns_packet_buffer::DnsPacketBuffer;
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Write as _};
+use std::fs;
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::process;
+use std::time::{Duration, Instant};
 
-mod domain;
-mod protocol;
+use dns::domain::dns_packet::DnsPacket;
+use dns::domain::dns_packet_ref::{DnsPacketRef, NameRef, RecordRef};
+use dns::domain::dns_packet_writer::MAX_MESSAGE_SIZE;
+use dns::encoding::{base64_decode, base64url_decode, hex_decode, hex_encode};
+use dns::error::DnsError;
+use dns::protocol::dns_name::Name;
+use dns::protocol::dns_question::DnsQuestion;
+use dns::protocol::dns_resource_record::DnsRecord;
+use dns::protocol::question_class_enum::QuestionClass;
+use dns::protocol::question_type_enum::QuestionType;
+use dns::protocol::resource_class_enum::ResourceClass;
+use dns::protocol::resource_data_enum::RData;
+use dns::protocol::resource_type_enum::ResourceType;
+use dns::resolver::recursive_resolver::RecursiveResolver;
+use dns::server::authority::Authority;
+use dns::server::dns_server::DnsServer;
+use dns::server::forwarder::{Forwarder, Strategy};
+use dns::server::zone::Zone;
+use dns::transport::tcp_framing::{is_timeout, TcpConnection};
+use dns::zone_file::parser::ZoneParser;
 
-fn hex_str_to_bits(hex_str: &str) -> Result<Vec<u8>, &'static str> {
-    if hex_str.len() % 2 != 0 {
-        return Err("Hex String Formatter Error")
+const DEFAULT_LISTEN: &str = "127.0.0.1:5353";
+const DEFAULT_PORT: u16 = 53;
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+/// The octets shown on each line of a hex dump
+const DUMP_WIDTH: usize = 8;
+
+const USAGE: &str = "\
+usage: dns query [NAME] [TYPE] [CLASS] [@SERVER] [-p PORT] [+[no]OPTION]...
+           options: tcp, dnssec, recurse, edns, idnout
+       dns decode [--hex | --base64 | --raw] [INPUT | -] [--dig | --json | --dump]
+       dns serve [ADDR] [--zone FILE]...
+       dns recurse [ADDR]
+       dns forward [ADDR] --upstream ADDR... [--strategy NAME]";
+
+/// A small zone exercising answers, aliases, wildcards and a delegation
+fn example_zone() -> Zone {
+    let name = |text: &str| -> Name { text.parse().expect("a valid name") };
+    let record = |owner: &str, r_data| DnsRecord::with_r_data(name(owner), 300, r_data);
+
+    let mut zone = Zone::new(name("example.com"));
+    for record in [
+        record(
+            "example.com",
+            RData::SOA {
+                m_name: name("ns1.example.com"),
+                r_name: name("hostmaster.example.com"),
+                serial: 1,
+                refresh: 3600,
+                retry: 600,
+                expire: 86400,
+                minimum: 60,
+            },
+        ),
+        record("example.com", RData::NS(name("ns1.example.com"))),
+        record(
+            "example.com",
+            RData::MX {
+                preference: 10,
+                exchange: name("mail.example.com"),
+            },
+        ),
+        record("example.com", RData::A([192, 0, 2, 1].into())),
+        record("ns1.example.com", RData::A([192, 0, 2, 53].into())),
+        record("mail.example.com", RData::A([192, 0, 2, 25].into())),
+        record("www.example.com", RData::CName(name("example.com"))),
+        record("*.apps.example.com", RData::A([192, 0, 2, 80].into())),
+        record("sub.example.com", RData::NS(name("ns.sub.example.com"))),
+        record("ns.sub.example.com", RData::A([192, 0, 2, 54].into())),
+    ] {
+        zone.insert(record);
+    }
+    zone
+}
+
+/**
+    `serve [ADDR] [--zone FILE]...`: answer over UDP and TCP for the zones
+    of the master files, or for the example zone if none are given
+*/
+fn serve(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut addr = DEFAULT_LISTEN.to_string();
+    let mut authority = Authority::new();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg != "--zone" {
+            addr = arg.clone();
+            continue;
+        }
+
+        let path = args.next().ok_or("--zone needs a file")?;
+        let records = ZoneParser::new().parse_file(path)?;
+        let zone = Zone::from_records(records).ok_or(format!("{} has no SOA record", path))?;
+        authority.add_zone(zone);
+    }
+    if authority.zones().is_empty() {
+        authority.add_zone(example_zone());
+    }
+
+    let server = DnsServer::bind(addr.parse::<SocketAddr>()?, authority)?.on_error(log_udp_error);
+    for zone in server.handler().zones() {
+        println!("serving {} on {}", zone.origin(), server.local_addr()?);
+    }
+    server.run()?;
+
+    Ok(())
+}
+
+/// Print the datagrams a server skipped
+fn log_udp_error(e: &io::Error) {
+    eprintln!("udp: {}", e);
+}
+
+/**
+    `query [NAME] [TYPE] [CLASS] [@SERVER] [-p PORT] [+[no]OPTION]...`: ask
+    a server as dig does and print its response with the query time, the
+    server and the size of the message.
+
+    The query goes over UDP with EDNS and recursion desired, and over TCP
+    when the response is truncated. `+tcp`, `+dnssec`, `+norecurse` and
+    `+noedns` change that, and `+idnout` shows names in Unicode. Without a
+    `@SERVER`, the first name server of `/etc/resolv.conf` is asked.
+*/
+fn query(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let QueryArgs {
+        request,
+        server,
+        port,
+        mut tcp,
+        idnout,
+    } = query_args(args)?;
+    let server = SocketAddr::new(server.unwrap_or_else(system_name_server), port);
+
+    println!("; <<>> dns <<>> {}", args.join(" "));
+
+    let mut start = Instant::now();
+    let mut message = match tcp {
+        true => exchange_tcp(&request, server)?,
+        false => exchange_udp(&request, server)?,
+    };
+    let mut response = DnsPacket::from_bytes(&message)?;
+    if !tcp && response.header.truncated_message {
+        println!(";; Truncated, retrying in TCP mode.");
+        tcp = true;
+        start = Instant::now();
+        message = exchange_tcp(&request, server)?;
+        response = DnsPacket::from_bytes(&message)?;
+    }
+    let elapsed = start.elapsed();
+
+    println!(";; Got answer:");
+    match idnout {
+        true => print!("{:#}", response),
+        false => print!("{}", response),
+    }
+    println!();
+    println!(";; Query time: {} msec", elapsed.as_millis());
+    println!(
+        ";; SERVER: {}#{}({}) ({})",
+        server.ip(),
+        server.port(),
+        server.ip(),
+        if tcp { "TCP" } else { "UDP" }
+    );
+    println!(";; MSG SIZE  rcvd: {}", message.len());
+
+    Ok(())
+}
+
+/// What the arguments of `query` ask for
+struct QueryArgs {
+    request: DnsPacket,
+    server: Option<IpAddr>,
+    port: u16,
+    tcp: bool,
+    idnout: bool,
+}
+
+/**
+    Parse the arguments of `query` into the request to send. Types and
+    classes may come before or after the name, as in dig, and without a
+    name the root name servers are asked for.
+*/
+fn query_args(args: &[String]) -> Result<QueryArgs, Box<dyn Error>> {
+    let mut name = None;
+    let mut q_type = None;
+    let mut q_class = None;
+    let mut server = None;
+    let mut port = DEFAULT_PORT;
+    let mut tcp = false;
+    let mut dnssec = false;
+    let mut recurse = true;
+    let mut edns = true;
+    let mut idnout = false;
+
+    let mut rest = args.iter();
+    while let Some(arg) = rest.next() {
+        if let Some(addr) = arg.strip_prefix('@') {
+            server = Some(
+                addr.parse::<IpAddr>()
+                    .map_err(|_| format!("invalid server {}", addr))?,
+            );
+        } else if arg == "-p" {
+            let value = rest.next().ok_or("-p needs a port")?;
+            port = value
+                .parse()
+                .map_err(|_| format!("invalid port {}", value))?;
+        } else if let Some(option) = arg.strip_prefix('+') {
+            let (value, option) = match option.strip_prefix("no") {
+                Some(option) => (false, option),
+                None => (true, option),
+            };
+            match option {
+                "tcp" => tcp = value,
+                "dnssec" => dnssec = value,
+                "recurse" => recurse = value,
+                "edns" => edns = value,
+                "idnout" => idnout = value,
+                _ => return Err(format!("unknown option {}", arg).into()),
+            }
+        } else if let Some(value) = QuestionType::from_mnemonic(arg).filter(|_| q_type.is_none()) {
+            q_type = Some(value);
+        } else if let Some(value) = QuestionClass::from_mnemonic(arg).filter(|_| q_class.is_none())
+        {
+            q_class = Some(value);
+        } else if name.is_none() {
+            name = Some(match arg.is_ascii() {
+                true => arg.parse::<Name>()?,
+                false => Name::from_unicode(arg)?,
+            });
+        } else {
+            return Err(format!("unexpected argument {}", arg).into());
+        }
+    }
+
+    // like dig, ask for the root name servers when no name is given
+    let q_type = q_type.unwrap_or(QuestionType::Base(match name {
+        Some(_) => ResourceType::A,
+        None => ResourceType::NS,
+    }));
+    let question = DnsQuestion::new(
+        name.unwrap_or_else(Name::root),
+        q_type,
+        q_class.unwrap_or(QuestionClass::Base(ResourceClass::IN)),
+    );
+    let mut builder = DnsPacket::query()
+        .add_question(question)
+        .recursion_desired(recurse);
+    if edns || dnssec {
+        builder = builder.dnssec_ok(dnssec);
+    }
+    Ok(QueryArgs {
+        request: builder.build(),
+        server,
+        port,
+        tcp,
+        idnout,
+    })
+}
+
+/// The first name server of `/etc/resolv.conf`, or the loopback address if there is none
+fn system_name_server() -> IpAddr {
+    fs::read_to_string("/etc/resolv.conf")
+        .ok()
+        .and_then(|conf| {
+            conf.lines().find_map(|line| {
+                let mut words = line.split_whitespace();
+                (words.next() == Some("nameserver")).then(|| words.next()?.parse().ok())?
+            })
+        })
+        .unwrap_or(IpAddr::V4(Ipv4Addr::LOCALHOST))
+}
+
+/**
+    Send `request` over UDP and return the first datagram answering it,
+    ignoring any that do not decode or carry another ID or question.
+*/
+fn exchange_udp(request: &DnsPacket, server: SocketAddr) -> Result<Vec<u8>, Box<dyn Error>> {
+    let local: SocketAddr = match server {
+        SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(local)?;
+    socket.connect(server)?;
+    socket.set_read_timeout(Some(QUERY_TIMEOUT))?;
+    socket.send(&request.to_bytes()?)?;
+
+    let mut buf = vec![0; MAX_MESSAGE_SIZE];
+    loop {
+        let len = match socket.recv(&mut buf) {
+            Ok(len) => len,
+            Err(e) if is_timeout(&e) => return Err(timed_out(server)),
+            Err(e) => return Err(e.into()),
+        };
+        if let Ok(response) = DnsPacket::from_bytes(&buf[..len]) {
+            if response.is_response_to(request) {
+                return Ok(buf[..len].to_vec());
+            }
+        }
+    }
+}
+
+/// Send `request` over a new TCP connection and return the response
+fn exchange_tcp(request: &DnsPacket, server: SocketAddr) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut connection = TcpConnection::connect(server, QUERY_TIMEOUT)?;
+    connection.write_message(&request.to_bytes()?)?;
+    match connection.read_message() {
+        Ok(Some(message)) => Ok(message),
+        Ok(None) => Err(format!("{} closed the connection without responding", server).into()),
+        Err(e) if is_timeout(&e) => Err(timed_out(server)),
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn timed_out(server: SocketAddr) -> Box<dyn Error> {
+    Box::new(io::Error::new(
+        io::ErrorKind::TimedOut,
+        format!("no response from {} within {:?}", server, QUERY_TIMEOUT),
+    ))
+}
+
+/**
+    `decode [--hex | --base64 | --raw] [INPUT | -] [--dig | --json | --dump]`:
+    print a message as dig does, as JSON or as a hex dump with the offset
+    and meaning of every field.
+
+    The message is hexadecimal by default, whitespace ignored, or base64
+    with `--base64`, where a DNS over HTTPS URL or `dns=` parameter may be
+    given as is. INPUT is the text itself, or with `--raw` a file holding
+    the message in wire format; without it, or as `-`, stdin is read.
+*/
+fn decode(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut encoding = "--hex";
+    let mut format = "--dig";
+    let mut input = None;
+    for arg in args {
+        match arg.as_str() {
+            "--hex" | "--base64" | "--raw" => encoding = arg,
+            "--dig" | "--json" | "--dump" => format = arg,
+            _ if input.is_none() => input = Some(arg.as_str()),
+            _ => return Err(format!("unexpected argument {}", arg).into()),
+        }
+    }
+    let input = input.filter(|&input| input != "-");
+
+    let bytes = match encoding {
+        "--raw" => match input {
+            Some(path) => fs::read(path)?,
+            None => {
+                let mut bytes = Vec::new();
+                io::stdin().read_to_end(&mut bytes)?;
+                bytes
+            }
+        },
+        _ => {
+            let text = match input {
+                Some(text) => text.to_string(),
+                None => io::read_to_string(io::stdin())?,
+            };
+            match encoding {
+                "--base64" => decode_base64(&text).ok_or("invalid base64")?,
+                _ => decode_hex(&text).ok_or("invalid hexadecimal")?,
+            }
+        }
+    };
+
+    match format {
+        "--json" => println!("{}", to_json(DnsPacketRef::new(&bytes)?)?),
+        "--dump" => print!("{}", hex_dump(&bytes)),
+        _ => print!("{}", DnsPacket::from_bytes(&bytes)?),
+    }
+
+    Ok(())
+}
+
+/// Hexadecimal with any whitespace between the digits and an optional `0x` prefix
+fn decode_hex(text: &str) -> Option<Vec<u8>> {
+    let text: String = text.split_whitespace().collect();
+    hex_decode(text.strip_prefix("0x").unwrap_or(&text))
+}
+
+/**
+    Base64url, or standard base64, alone or as the `dns` parameter of a DNS
+    over HTTPS GET request, e.g. `https://doh.example/dns-query?dns=AAAB...`
+*/
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let text = text.trim();
+    let text = match text.split_once("dns=") {
+        Some((_, parameter)) => parameter.split('&').next()?,
+        None => text,
+    };
+    base64url_decode(text).or_else(|| base64_decode(text))
+}
+
+/**
+    The message as JSON, with the member names of RFC 8427: the header
+    fields, then the questions and records of each section. Records carry
+    their RDATA as hexadecimal and in presentation format, the latter under
+    `rdata` followed by the type, e.g. `rdataMX`. The RCODE is the full 12
+    bit one, extended by the upper 8 bits of the OPT record if there is one.
+*/
+fn to_json(message: DnsPacketRef) -> Result<String, DnsError> {
+    let header = message.header()?;
+    let mut json = String::from("{\n");
+    let mut member = |name: &str, value: &dyn fmt::Display| {
+        writeln!(json, "  \"{}\": {},", name, value).unwrap();
+    };
+    member("ID", &header.id);
+    member("QR", &(header.response as u8));
+    member("Opcode", &u8::from(header.opcode));
+    member("AA", &(header.authoritative_answer as u8));
+    member("TC", &(header.truncated_message as u8));
+    member("RD", &(header.recursion_desired as u8));
+    member("RA", &(header.recursion_available as u8));
+    member("AD", &(header.authed_data as u8));
+    member("CD", &(header.checking_disabled as u8));
+    let mut r_code = u16::from(header.r_code) & 0x0F;
+    for record in message.additionals() {
+        let record = record?;
+        // the OPT record carries the upper 8 bits of the RCODE (RFC 6891 section 6.1.3)
+        if record.r_type == ResourceType::OPT {
+            r_code |= ((record.ttl >> 24) as u16) << 4;
+        }
+    }
+    member("RCODE", &r_code);
+    member("QDCOUNT", &header.questions);
+    member("ANCOUNT", &header.answers);
+    member("NSCOUNT", &header.authoritative_entries);
+    member("ARCOUNT", &header.resource_entries);
+
+    json.push_str("  \"questionRRs\": [");
+    let mut delimiter = "\n";
+    for question in message.questions() {
+        let question = question?;
+        write!(
+            json,
+            "{}    {{ \"NAME\": {}, \"TYPE\": {}, \"TYPEname\": {}, \"CLASS\": {}, \"CLASSname\": {} }}",
+            delimiter,
+            json_string(&question.name.to_name()?.to_string()),
+            u16::from(question.q_type),
+            json_string(&question.q_type.to_string()),
+            u16::from(question.q_class),
+            json_string(&question.q_class.to_string())
+        )
+        .unwrap();
+        delimiter = ",\n";
     }
-    let mut result = vec![];
-    for i in 0..hex_str.len() / 2 {
-        let pos = i * 2;
-        let h = u8::from_str_radix(&hex_str[pos..(pos+1)], 16).unwrap();
-        let l = u8::from_str_radix(&hex_str[(pos+1)..(pos+2)], 16).unwrap();
-        let bit = (h << 4) | l;
-        result.push(bit);
+    json.push_str(if delimiter == "\n" { "]" } else { "\n  ]" });
+
+    for (title, records) in [
+        ("answerRRs", message.answers()),
+        ("authorityRRs", message.authorities()),
+        ("additionalRRs", message.additionals()),
+    ] {
+        write!(json, ",\n  \"{}\": [", title).unwrap();
+        let mut delimiter = "\n";
+        for record in records {
+            let record = record?;
+            write!(
+                json,
+                "{}    {{ \"NAME\": {}, \"TYPE\": {}, \"TYPEname\": {}, \"CLASS\": {}, \"CLASSname\": {}, \"TTL\": {}, \"RDLENGTH\": {}, \"RDATAHEX\": \"{}\", \"rdata{}\": {} }}",
+                delimiter,
+                json_string(&record.name.to_name()?.to_string()),
+                u16::from(record.r_type),
+                json_string(&record.r_type.to_string()),
+                u16::from(record.r_class),
+                json_string(&record.r_class.to_string()),
+                record.ttl,
+                record.r_data.len(),
+                hex_encode(record.r_data),
+                record.r_type,
+                json_string(&record.to_record()?.r_data.to_string())
+            )
+            .unwrap();
+            delimiter = ",\n";
+        }
+        json.push_str(if delimiter == "\n" { "]" } else { "\n  ]" });
     }
-    Ok(result)
+    json.push_str("\n}");
+
+    Ok(json)
 }
 
-fn main() -> Result<(), &'static str> {
+/// `text` as a JSON string, quoted and escaped
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if c.is_control() => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
 
-    let dns_response = "862a8180000100010000000006676f6f676c6503636f6d0000010001c00c00010001000001250004d83ad38e".to_string();
-    let bits = hex_str_to_bits(&dns_response)?;
-    let mut buf: [u8; 512] = [0; 512];
+/**
+    A hex dump of a message with every field on a line of its own: its
+    offset, its octets and what they mean. Names note where a compression
+    pointer leads. Whatever cannot be decoded is dumped as it is, after the
+    reason, so malformed messages can be looked into too.
+*/
+fn hex_dump(bytes: &[u8]) -> String {
+    let mut dump = HexDump {
+        bytes,
+        text: String::new(),
+        end: 0,
+    };
+    writeln!(dump.text, ";; {} octets", bytes.len()).unwrap();
 
-    for i in 0..bits.len() {
-        buf[i] = bits[i];
+    let rest = match dump_message(&mut dump) {
+        Ok(()) => "trailing octets",
+        Err(e) => {
+            dump.note(format!(";; {}", e));
+            "not decoded"
+        }
+    };
+    if dump.end < bytes.len() {
+        dump.field(dump.end, bytes.len() - dump.end, rest);
     }
 
-    let mut buffer = DnsPacketBuffer { pos: 0, buf };
+    dump.text
+}
 
-    let packet = DnsPacket::decode(&mut buffer)?;
+fn dump_message(dump: &mut HexDump) -> Result<(), DnsError> {
+    let message = DnsPacketRef::new(dump.bytes)?;
+    let header = message.header()?;
+    dump.field(0, 2, format!("ID: {}", header.id));
+    dump.field(
+        2,
+        2,
+        format!(
+            "flags: {}; opcode: {}; rcode: {}",
+            header.flags(),
+            header.opcode,
+            header.r_code
+        ),
+    );
+    dump.field(4, 2, format!("QDCOUNT: {}", header.questions));
+    dump.field(6, 2, format!("ANCOUNT: {}", header.answers));
+    dump.field(8, 2, format!("NSCOUNT: {}", header.authoritative_entries));
+    dump.field(10, 2, format!("ARCOUNT: {}", header.resource_entries));
 
-    println!("{:#?}", packet.header);
+    if header.questions > 0 {
+        dump.note(";; QUESTION SECTION");
+    }
+    for question in message.questions() {
+        let question = question?;
+        let end = dump_name(dump, question.name)?;
+        dump.field(end, 2, format!("TYPE: {}", question.q_type));
+        dump.field(end + 2, 2, format!("CLASS: {}", question.q_class));
+    }
 
-    for q in packet.questions {
-        println!("{:#?}", q);
+    for (title, count, records) in [
+        ("ANSWER", header.answers, message.answers()),
+        (
+            "AUTHORITY",
+            header.authoritative_entries,
+            message.authorities(),
+        ),
+        ("ADDITIONAL", header.resource_entries, message.additionals()),
+    ] {
+        if count > 0 {
+            dump.note(format!(";; {} SECTION", title));
+        }
+        for record in records {
+            dump_record(dump, record?)?;
+        }
     }
-    for rec in packet.answers {
-        println!("{:#?}", rec);
+
+    Ok(())
+}
+
+fn dump_record(dump: &mut HexDump, record: RecordRef) -> Result<(), DnsError> {
+    let end = dump_name(dump, record.name)?;
+    dump.field(end, 2, format!("TYPE: {}", record.r_type));
+    dump.field(end + 2, 2, format!("CLASS: {}", record.r_class));
+    dump.field(end + 4, 4, format!("TTL: {}", record.ttl));
+    dump.field(end + 8, 2, format!("RDLENGTH: {}", record.r_data.len()));
+
+    let r_data = record.to_record()?.r_data;
+    dump.field(
+        record.r_data_offset,
+        record.r_data.len(),
+        format!("RDATA: {}", r_data),
+    );
+
+    Ok(())
+}
+
+/// Dump the octets of a name in place, returning the position following them
+fn dump_name(dump: &mut HexDump, name: NameRef) -> Result<usize, DnsError> {
+    let start = name.offset();
+    let end = name.end()?;
+    let mut note = format!("NAME: {}", name.to_name()?);
+
+    // the labels in place end in the root label or a compression pointer
+    let mut pos = start;
+    while pos < end {
+        match dump.bytes[pos] {
+            len if len & 0xC0 == 0xC0 => {
+                let pointer = u16::from_be_bytes([len, dump.bytes[pos + 1]]) & 0x3FFF;
+                write!(note, " (pointer to {:04x})", pointer).unwrap();
+                break;
+            }
+            len => pos += len as usize + 1,
+        }
     }
-    for rec in packet.authorities {
-        println!("{:#?}", rec);
+    dump.field(start, end - start, note);
+
+    Ok(end)
+}
+
+/// The text of a hex dump being written, see `hex_dump`
+struct HexDump<'a> {
+    bytes: &'a [u8],
+    text: String,
+    /// The position following the last field dumped
+    end: usize,
+}
+
+impl HexDump<'_> {
+    /// Write the octets of a field, `DUMP_WIDTH` to a line, the first line followed by `note`
+    fn field(&mut self, start: usize, len: usize, note: impl fmt::Display) {
+        let octets = &self.bytes[start..start + len];
+        let mut note = Some(note);
+        let mut offset = start;
+        // an empty field, like empty RDATA, still gets its line
+        for line in octets
+            .chunks(DUMP_WIDTH)
+            .chain(octets.is_empty().then_some(octets))
+        {
+            let hex: Vec<String> = line.iter().map(|octet| format!("{:02x}", octet)).collect();
+            let mut text = format!(
+                "{:04x}  {:<width$}",
+                offset,
+                hex.join(" "),
+                width = DUMP_WIDTH * 3
+            );
+            if let Some(note) = note.take() {
+                write!(text, "  {}", note).unwrap();
+            }
+            writeln!(self.text, "{}", text.trim_end()).unwrap();
+            offset += line.len();
+        }
+        self.end = self.end.max(start + len);
     }
-    for rec in packet.resources {
-        println!("{:#?}", rec);
+
+    /// Write a line without octets
+    fn note(&mut self, note: impl fmt::Display) {
+        writeln!(
+            self.text,
+            "{:width$}{}",
+            "",
+            note,
+            width = DUMP_WIDTH * 3 + 8
+        )
+        .unwrap();
     }
+}
+
+/// `recurse [ADDR]`: answer queries by resolving them from the root servers
+fn recurse(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let addr = args.first().map_or(DEFAULT_LISTEN, String::as_str);
+
+    let server = DnsServer::bind(addr.parse::<SocketAddr>()?, RecursiveResolver::new())?
+        .on_error(log_udp_error);
+    println!("resolving on {}", server.local_addr()?);
+    server.run()?;
 
     Ok(())
 }
+
+/**
+    `forward [ADDR] --upstream ADDR... [--strategy NAME]`: relay queries to
+    the upstreams, with failover, round-robin, fastest-rtt or race
+*/
+fn forward(args: &[String]) -> Result<(), Box<dyn Error>> {
+    let mut addr = DEFAULT_LISTEN.to_string();
+    let mut upstreams = Vec::new();
+    let mut strategy = Strategy::Failover;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--upstream" => {
+                let upstream = args.next().ok_or("--upstream needs an address")?;
+                upstreams.push(upstream.parse::<SocketAddr>()?);
+            }
+            "--strategy" => {
+                let name = args.next().ok_or("--strategy needs a name")?;
+                strategy = Strategy::from_name(name).ok_or(format!("unknown strategy {}", name))?;
+            }
+            _ => addr = arg.clone(),
+        }
+    }
+    if upstreams.is_empty() {
+        return Err("forward needs at least one --upstream".into());
+    }
+
+    let forwarder = Forwarder::new(upstreams).strategy(strategy);
+    let server = DnsServer::bind(addr.parse::<SocketAddr>()?, forwarder)?.on_error(log_udp_error);
+    println!(
+        "forwarding on {} to {:?} ({})",
+        server.local_addr()?,
+        server.handler().upstreams(),
+        strategy
+    );
+    server.run()?;
+
+    Ok(())
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("query") => query(&args[1..]),
+        Some("decode") => decode(&args[1..]),
+        Some("serve") => serve(&args[1..]),
+        Some("recurse") => recurse(&args[1..]),
+        Some("forward") => forward(&args[1..]),
+        _ => {
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use dns::protocol::rcode_enum::RCode;
+
+    use super::*;
+
+    #[test]
+    fn json_rcode_includes_the_extended_bits() {
+        let mut packet = DnsPacket::new();
+        packet.set_extended_r_code(RCode::BadVers);
+        let bytes = packet.to_bytes().unwrap();
+
+        let json = to_json(DnsPacketRef::new(&bytes).unwrap()).unwrap();
+        assert!(json.contains("\"RCODE\": 16,"), "{}", json);
+    }
+
+    fn args(text: &str) -> Vec<String> {
+        text.split_whitespace().map(String::from).collect()
+    }
+
+    fn question(args: &QueryArgs) -> &DnsQuestion {
+        &args.request.questions[0]
+    }
+
+    #[test]
+    fn query_takes_dig_arguments_in_any_order() {
+        let parsed =
+            query_args(&args("example.com MX CH @192.0.2.1 -p 5353 +tcp +noedns")).unwrap();
+        assert_eq!(question(&parsed).name, "example.com".parse().unwrap());
+        assert_eq!(
+            question(&parsed).q_type,
+            QuestionType::Base(ResourceType::MX)
+        );
+        assert_eq!(
+            question(&parsed).q_class,
+            QuestionClass::Base(ResourceClass::CH)
+        );
+        assert_eq!(parsed.server, Some([192, 0, 2, 1].into()));
+        assert_eq!(parsed.port, 5353);
+        assert!(parsed.tcp);
+        assert!(parsed.request.edns.is_none());
+
+        // the type and class may come first
+        let parsed = query_args(&args("@::1 CH TXT version.bind")).unwrap();
+        assert_eq!(question(&parsed).name, "version.bind".parse().unwrap());
+        assert_eq!(
+            question(&parsed).q_type,
+            QuestionType::Base(ResourceType::Txt)
+        );
+        assert_eq!(
+            question(&parsed).q_class,
+            QuestionClass::Base(ResourceClass::CH)
+        );
+        assert_eq!(parsed.server, Some("::1".parse().unwrap()));
+        assert_eq!(parsed.port, DEFAULT_PORT);
+        assert!(parsed.request.header.recursion_desired);
+        assert!(parsed.request.edns.is_some());
+    }
+
+    #[test]
+    fn query_asks_for_the_root_servers_without_a_name() {
+        let parsed = query_args(&[]).unwrap();
+        assert_eq!(question(&parsed).name, Name::root());
+        assert_eq!(
+            question(&parsed).q_type,
+            QuestionType::Base(ResourceType::NS)
+        );
+        assert_eq!(
+            question(&parsed).q_class,
+            QuestionClass::Base(ResourceClass::IN)
+        );
+        assert_eq!(parsed.server, None);
+    }
+
+    #[test]
+    fn query_rejects_bad_arguments() {
+        for text in ["a.example b.example", "@ns.example", "-p", "-p x", "+bogus"] {
+            assert!(query_args(&args(text)).is_err(), "{}", text);
+        }
+    }
+
+    #[test]
+    fn udp_exchange_waits_for_the_response_to_the_query() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = server.local_addr().unwrap();
+        let request = query_args(&args("www.example.com")).unwrap().request;
+
+        let answered = request.clone();
+        std::thread::spawn(move || {
+            let mut buf = vec![0; MAX_MESSAGE_SIZE];
+            let (_, client) = server.recv_from(&mut buf).unwrap();
+
+            // the right ID with another question, and a runt with the right ID
+            let mut spoofed = answered.clone();
+            spoofed.questions[0].name = "evil.example".parse().unwrap();
+            let spoofed = DnsPacket::response_to(&spoofed).build();
+            server
+                .send_to(&spoofed.to_bytes().unwrap(), client)
+                .unwrap();
+            server
+                .send_to(&answered.header.id.to_be_bytes(), client)
+                .unwrap();
+
+            let response = DnsPacket::response_to(&answered)
+                .r_code(RCode::NameErr)
+                .build();
+            server
+                .send_to(&response.to_bytes().unwrap(), client)
+                .unwrap();
+        });
+
+        let message = exchange_udp(&request, addr).unwrap();
+        let response = DnsPacket::from_bytes(&message).unwrap();
+        assert!(response.is_response_to(&request));
+        assert_eq!(response.header.r_code, RCode::NameErr);
+    }
+}
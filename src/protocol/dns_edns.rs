@@ -0,0 +1,212 @@
+use std::fmt;
+
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::edns_option_enum::EdnsOption;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+
+/**
+    The EDNS(0) information of a message (RFC 6891), carried on the wire by
+    an OPT pseudo-record in the additional section whose fixed fields are
+    repurposed:
+
+    ```text
+    +------------+--------------+------------------------------+
+    | Field Name | Field Type   | Description                  |
+    +------------+--------------+------------------------------+
+    | NAME       | domain name  | MUST be 0 (root domain)      |
+    | TYPE       | u_int16_t    | OPT (41)                     |
+    | CLASS      | u_int16_t    | requestor's UDP payload size |
+    | TTL        | u_int32_t    | extended RCODE and flags     |
+    | RDLEN      | u_int16_t    | length of all RDATA          |
+    | RDATA      | octet stream | {attribute,value} pairs      |
+    +------------+--------------+------------------------------+
+
+                +0 (MSB)                            +1 (LSB)
+    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+    |         EXTENDED-RCODE        |            VERSION            |
+    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+    | DO|                           Z                               |
+    +---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+---+
+    ```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edns {
+    /// The largest UDP payload the sender can reassemble and deliver
+    pub udp_payload_size: u16,
+    /// The upper 8 bits of the 12 bit RCODE, the lower 4 bits are in the header
+    pub extended_r_code: u8,
+    /// The EDNS version, 0 for RFC 6891
+    pub version: u8,
+    /// DNSSEC OK - the sender is able to accept DNSSEC security RRs
+    pub dnssec_ok: bool,
+    /// The remaining 15 flag bits, which must be zero
+    pub z: u16,
+    pub options: Vec<EdnsOption>,
+}
+
+impl Edns {
+    pub fn new() -> Edns {
+        Edns {
+            udp_payload_size: 1232,
+            extended_r_code: 0,
+            version: 0,
+            dnssec_ok: false,
+            z: 0,
+            options: Vec::new(),
+        }
+    }
+
+    /// Read the EDNS information of an OPT pseudo-record
+    pub fn from_record(record: &DnsRecord) -> Option<Edns> {
+        let options = match &record.r_data {
+            RData::OPT(options) => options.clone(),
+            _ => return None,
+        };
+
+        Some(Edns {
+            udp_payload_size: record.r_class.into(),
+            extended_r_code: (record.ttl >> 24) as u8,
+            version: ((record.ttl >> 16) & 0xFF) as u8,
+            dnssec_ok: (record.ttl & 0x8000) != 0,
+            z: (record.ttl & 0x7FFF) as u16,
+            options,
+        })
+    }
+
+    /// Build the OPT pseudo-record carrying this information
+    pub fn to_record(&self) -> DnsRecord {
+        let ttl = ((self.extended_r_code as u32) << 24)
+            | ((self.version as u32) << 16)
+            | ((self.dnssec_ok as u32) << 15)
+            | (self.z & 0x7FFF) as u32;
+
+        DnsRecord {
+            name: Name::root(),
+            r_type: ResourceType::OPT,
+            r_class: ResourceClass::from(self.udp_payload_size),
+            ttl,
+            rd_length: 0,
+            r_data: RData::OPT(self.options.clone()),
+        }
+    }
+}
+
+impl fmt::Display for Edns {
+    /// The OPT pseudosection as dig shows it, one line per option
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "; EDNS: version: {}, flags:", self.version)?;
+        if self.dnssec_ok {
+            write!(f, " do")?;
+        }
+        write!(f, "; udp: {}", self.udp_payload_size)?;
+        for option in &self.options {
+            write!(f, "\n; {}", option)?;
+        }
+        Ok(())
+    }
+}
+
+impl Default for Edns {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{IpAddr, Ipv4Addr};
+
+    use super::*;
+    use crate::domain::dns_packet::DnsPacket;
+    use crate::error::{DnsError, Section};
+
+    #[test]
+    fn splits_the_ttl_into_rcode_version_and_flags() {
+        let record = DnsRecord {
+            ttl: 0x01_02_80_05,
+            ..Edns::new().to_record()
+        };
+
+        let edns = Edns::from_record(&record).unwrap();
+        assert_eq!(edns.extended_r_code, 1);
+        assert_eq!(edns.version, 2);
+        assert!(edns.dnssec_ok);
+        assert_eq!(edns.z, 5);
+        assert_eq!(edns.to_record().ttl, record.ttl);
+    }
+
+    #[test]
+    fn carries_the_payload_size_as_class() {
+        let edns = Edns {
+            udp_payload_size: 4096,
+            ..Edns::new()
+        };
+
+        let record = edns.to_record();
+        assert!(record.name.is_root());
+        assert_eq!(record.r_type, ResourceType::OPT);
+        assert_eq!(u16::from(record.r_class), 4096);
+        assert_eq!(Edns::from_record(&record), Some(edns));
+    }
+
+    #[test]
+    fn options_round_trip() {
+        let mut packet = DnsPacket::new();
+        packet.edns = Some(Edns {
+            dnssec_ok: true,
+            options: vec![
+                EdnsOption::Nsid(b"ns1".to_vec()),
+                EdnsOption::ClientSubnet {
+                    source_prefix: 24,
+                    scope_prefix: 0,
+                    address: IpAddr::V4(Ipv4Addr::new(192, 0, 2, 0)),
+                },
+                EdnsOption::Cookie {
+                    client: [1, 2, 3, 4, 5, 6, 7, 8],
+                    server: vec![9; 16],
+                },
+                EdnsOption::Padding(12),
+                EdnsOption::ExtendedError {
+                    info_code: 18,
+                    extra_text: "prohibited".to_string(),
+                },
+                EdnsOption::Unknown {
+                    code: 65001,
+                    data: vec![0xAB],
+                },
+            ],
+            ..Edns::new()
+        });
+
+        let bytes = packet.to_bytes().unwrap();
+        let decoded = DnsPacket::from_bytes(&bytes).unwrap();
+        assert_eq!(decoded.edns, packet.edns);
+        assert!(decoded.resources.is_empty());
+    }
+
+    #[test]
+    fn rejects_a_second_opt() {
+        let mut packet = DnsPacket::new();
+        packet.resources.push(Edns::new().to_record());
+        packet.edns = Some(Edns::new());
+
+        assert_eq!(
+            DnsPacket::from_bytes(&packet.to_bytes().unwrap()).err(),
+            Some(DnsError::DuplicateOpt.in_record(Section::Additional, 1))
+        );
+    }
+
+    #[test]
+    fn rejects_opt_outside_the_additional_section() {
+        let mut packet = DnsPacket::new();
+        packet.answers.push(Edns::new().to_record());
+
+        assert_eq!(
+            DnsPacket::from_bytes(&packet.to_bytes().unwrap()).err(),
+            Some(DnsError::MisplacedOpt.in_record(Section::Answer, 0))
+        );
+    }
+}
@@ -1,8 +1,10 @@
+use crate::error::DnsError;
+use crate::protocol::header_flags::Flags;
 use crate::protocol::opcode_enum::OpCode;
 use crate::protocol::rcode_enum::RCode;
 
 /**
-```
+```text
   0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
 +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 |                      ID                       |
@@ -132,4 +134,106 @@ impl DnsHeader {
             resource_entries: 0,
         }
     }
+
+    /// Decode the fixed 12 octet header at the start of a message
+    pub fn from_bytes(bytes: &[u8]) -> Result<DnsHeader, DnsError> {
+        if bytes.len() < 12 {
+            return Err(DnsError::EndOfBuffer {
+                offset: bytes.len(),
+            });
+        }
+        let word = |i: usize| ((bytes[i] as u16) << 8) | bytes[i + 1] as u16;
+
+        let mut header = DnsHeader::new();
+        header.id = word(0);
+        header.set_flags_word(word(2));
+        header.questions = word(4);
+        header.answers = word(6);
+        header.authoritative_entries = word(8);
+        header.resource_entries = word(10);
+
+        Ok(header)
+    }
+
+    /// Encode the fixed 12 octet header
+    pub fn to_bytes(&self) -> [u8; 12] {
+        let mut bytes = [0u8; 12];
+        let words = [
+            self.id,
+            self.flags_word(),
+            self.questions,
+            self.answers,
+            self.authoritative_entries,
+            self.resource_entries,
+        ];
+        for (i, word) in words.iter().enumerate() {
+            bytes[i * 2..i * 2 + 2].copy_from_slice(&word.to_be_bytes());
+        }
+
+        bytes
+    }
+
+    /// The raw second word of the header: flags, OPCODE and the low 4 bits of RCODE
+    pub fn flags_word(&self) -> u16 {
+        self.flags().bits()
+            | ((u8::from(self.opcode) as u16 & 0x0F) << 11)
+            | (u16::from(self.r_code) & 0x0F)
+    }
+
+    /// Set the flags, OPCODE and RCODE from the raw second word of the header
+    pub fn set_flags_word(&mut self, word: u16) {
+        self.set_flags(Flags::from_bits_truncate(word));
+        self.opcode = (((word >> 11) & 0x0F) as u8).into();
+        self.r_code = (word & 0x0F).into();
+    }
+
+    /// The single bit flags of the header
+    pub fn flags(&self) -> Flags {
+        let mut flags = Flags::empty();
+        flags.set(Flags::QR, self.response);
+        flags.set(Flags::AA, self.authoritative_answer);
+        flags.set(Flags::TC, self.truncated_message);
+        flags.set(Flags::RD, self.recursion_desired);
+        flags.set(Flags::RA, self.recursion_available);
+        flags.set(Flags::Z, self.z);
+        flags.set(Flags::AD, self.authed_data);
+        flags.set(Flags::CD, self.checking_disabled);
+
+        flags
+    }
+
+    /// Set every single bit flag of the header, clearing the ones not in `flags`
+    pub fn set_flags(&mut self, flags: Flags) {
+        self.response = flags.contains(Flags::QR);
+        self.authoritative_answer = flags.contains(Flags::AA);
+        self.truncated_message = flags.contains(Flags::TC);
+        self.recursion_desired = flags.contains(Flags::RD);
+        self.recursion_available = flags.contains(Flags::RA);
+        self.z = flags.contains(Flags::Z);
+        self.authed_data = flags.contains(Flags::AD);
+        self.checking_disabled = flags.contains(Flags::CD);
+    }
+}
+
+impl Default for DnsHeader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_flags_word_round_trips() {
+        for word in 0..=u16::MAX {
+            let mut header = DnsHeader::new();
+            header.set_flags_word(word);
+            assert_eq!(header.flags_word(), word, "flags word {:#06x}", word);
+
+            let decoded = DnsHeader::from_bytes(&header.to_bytes()).unwrap();
+            assert_eq!(decoded.flags_word(), word, "flags word {:#06x}", word);
+        }
+    }
 }
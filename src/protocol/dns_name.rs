@@ -0,0 +1,600 @@
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use idna::uts46::{AsciiDenyList, DnsLength, Hyphens, Uts46};
+
+use crate::error::DnsError;
+
+/// The longest a label may be, in octets
+pub const MAX_LABEL_LEN: usize = 63;
+/// The longest a name may be in wire format, length octets and the root label included
+pub const MAX_NAME_LEN: usize = 255;
+
+/**
+    A domain name, kept as the length-prefixed labels it has on the wire,
+    so it is always within the limits of RFC 1035 section 3.1: labels of at
+    most 63 octets, the whole name at most 255 octets.
+
+    Labels are arbitrary octets and keep the case they were given in. Names
+    compare and hash ignoring ASCII case, as RFC 4343 asks, and order in
+    the canonical order of RFC 4034 section 6.1: label by label from the
+    root down, a name before the names below it.
+
+    In text, names are written absolute with a trailing dot and the root as
+    `.`. A dot inside a label is escaped as `\.`, and octets outside
+    printable ASCII as `\DDD` in decimal, so any name survives the trip to
+    text and back.
+
+    Internationalized names are carried as A-labels, the `xn--` Punycode
+    form of IDNA 2008. `from_unicode` converts a name typed by a user the
+    way UTS #46 does, and the alternate form `{:#}` displays the A-labels
+    of a name in Unicode again.
+*/
+#[derive(Clone)]
+pub struct Name {
+    /// The uncompressed wire format, ending with the null label of the root
+    wire: Vec<u8>,
+}
+
+impl Name {
+    pub fn root() -> Name {
+        Name { wire: vec![0] }
+    }
+
+    /// The name of `labels`, the first one farthest from the root
+    pub fn from_labels<I, L>(labels: I) -> Result<Name, DnsError>
+    where
+        I: IntoIterator<Item = L>,
+        L: AsRef<[u8]>,
+    {
+        let labels: Vec<L> = labels.into_iter().collect();
+        let text = || {
+            let mut text = String::new();
+            for label in &labels {
+                write_label(&mut text, label.as_ref()).unwrap();
+                text.push('.');
+            }
+            text
+        };
+
+        let mut wire = Vec::new();
+        for label in &labels {
+            let label = label.as_ref();
+            if label.is_empty() {
+                return Err(DnsError::EmptyLabel { name: text() });
+            }
+            if label.len() > MAX_LABEL_LEN {
+                return Err(DnsError::LabelTooLong {
+                    name: text(),
+                    len: label.len(),
+                });
+            }
+            wire.push(label.len() as u8);
+            wire.extend_from_slice(label);
+        }
+        wire.push(0);
+
+        if wire.len() > MAX_NAME_LEN {
+            return Err(DnsError::NameTooLong { name: text() });
+        }
+        Ok(Name { wire })
+    }
+
+    /**
+        Parse the text form of a name, telling whether it is absolute, i.e.
+        ends in an unescaped dot. Both the empty text and `.` are the root.
+    */
+    pub(crate) fn parse(text: &str) -> Result<(Name, bool), DnsError> {
+        if text.is_empty() || text == "." {
+            return Ok((Name::root(), true));
+        }
+        let invalid = || DnsError::InvalidEscape {
+            name: text.to_string(),
+        };
+
+        let mut labels = Vec::new();
+        let mut label = Vec::new();
+        let mut absolute = false;
+        let mut bytes = text.bytes();
+        while let Some(c) = bytes.next() {
+            absolute = false;
+            match c {
+                b'.' => {
+                    labels.push(std::mem::take(&mut label));
+                    absolute = true;
+                }
+                b'\\' => match bytes.next().ok_or_else(invalid)? {
+                    d if d.is_ascii_digit() => {
+                        let mut value = (d - b'0') as u32;
+                        for _ in 0..2 {
+                            match bytes.next() {
+                                Some(d) if d.is_ascii_digit() => {
+                                    value = value * 10 + (d - b'0') as u32
+                                }
+                                _ => return Err(invalid()),
+                            }
+                        }
+                        label.push(u8::try_from(value).map_err(|_| invalid())?);
+                    }
+                    c => label.push(c),
+                },
+                c => label.push(c),
+            }
+        }
+        if !absolute {
+            labels.push(label);
+        }
+
+        Ok((Name::from_labels(&labels)?, absolute))
+    }
+
+    /**
+        The name of Unicode text, its labels mapped and converted to
+        A-labels as UTS #46 processing does for lookups, e.g. `Bücher.de`
+        becomes `xn--bcher-kva.de.`. Hyphens and printable ASCII outside the
+        letter, digit and hyphen rule are let through, as names like
+        `_sip._tcp` and `*` need; spaces and control characters are not.
+    */
+    pub fn from_unicode(text: &str) -> Result<Name, DnsError> {
+        let invalid = || DnsError::Idna {
+            name: text.to_string(),
+        };
+        let ascii = Uts46::new()
+            .to_ascii(
+                text.as_bytes(),
+                AsciiDenyList::new(true, ""),
+                Hyphens::Allow,
+                DnsLength::Ignore,
+            )
+            .map_err(|_| invalid())?;
+
+        match ascii.strip_suffix('.').unwrap_or(&ascii) {
+            "" => Ok(Name::root()),
+            ascii => Name::from_labels(ascii.split('.')),
+        }
+    }
+
+    /// The name in text form with its A-labels in Unicode, the same as `format!("{:#}", name)`
+    pub fn to_unicode(&self) -> String {
+        format!("{:#}", self)
+    }
+
+    pub fn is_root(&self) -> bool {
+        self.wire.len() == 1
+    }
+
+    /// Whether the first label is `*`, which makes the name a wildcard (RFC 4592)
+    pub fn is_wildcard(&self) -> bool {
+        self.wire.starts_with(&[1, b'*'])
+    }
+
+    /// The number of labels, not counting the null label of the root
+    pub fn label_count(&self) -> usize {
+        self.labels().count()
+    }
+
+    /// The length of the name in wire format
+    pub fn wire_len(&self) -> usize {
+        self.wire.len()
+    }
+
+    /// The name in uncompressed wire format
+    pub fn as_wire(&self) -> &[u8] {
+        &self.wire
+    }
+
+    /// The labels, the first one farthest from the root, without the null label of the root
+    pub fn labels(&self) -> NameLabels<'_> {
+        NameLabels { wire: &self.wire }
+    }
+
+    /// Whether the name equals `ancestor` or lies below it
+    pub fn is_subdomain_of(&self, ancestor: &Name) -> bool {
+        match self.label_count().checked_sub(ancestor.label_count()) {
+            Some(skip) => self.wire[self.offset(skip)..].eq_ignore_ascii_case(&ancestor.wire),
+            None => false,
+        }
+    }
+
+    /// The name without its first label, `None` for the root
+    pub fn parent(&self) -> Option<Name> {
+        if self.is_root() {
+            return None;
+        }
+        Some(Name {
+            wire: self.wire[self.offset(1)..].to_vec(),
+        })
+    }
+
+    /// The ancestor made of the last `count` labels, or the name itself if it has no more
+    pub fn suffix(&self, count: usize) -> Name {
+        let skip = self.label_count().saturating_sub(count);
+        Name {
+            wire: self.wire[self.offset(skip)..].to_vec(),
+        }
+    }
+
+    /// The labels of this name followed by those of `other`, e.g. `www` and `example.com.`
+    pub fn append(&self, other: &Name) -> Result<Name, DnsError> {
+        Name::from_labels(self.labels().chain(other.labels()))
+    }
+
+    /// The name with `label` put in front, e.g. `*` for the wildcard below it
+    pub fn prepend(&self, label: &[u8]) -> Result<Name, DnsError> {
+        Name::from_labels([label])?.append(self)
+    }
+
+    /// The same name with ASCII letters lowercased, as in canonical form
+    pub fn to_lowercase(&self) -> Name {
+        Name {
+            wire: self.wire.to_ascii_lowercase(),
+        }
+    }
+
+    /**
+        Write the first `count` labels in text form, separated by dots but
+        without a final one, with `unicode` the A-labels in Unicode.
+    */
+    pub(crate) fn write_labels<W: fmt::Write>(
+        &self,
+        f: &mut W,
+        count: usize,
+        unicode: bool,
+    ) -> fmt::Result {
+        let mut delimiter = "";
+        for label in self.labels().take(count) {
+            f.write_str(delimiter)?;
+            match unicode.then(|| decode_a_label(label)).flatten() {
+                Some(decoded) => f.write_str(&decoded)?,
+                None => write_label(f, label)?,
+            }
+            delimiter = ".";
+        }
+        Ok(())
+    }
+
+    /// The offset in the wire format after the first `skip` labels
+    fn offset(&self, skip: usize) -> usize {
+        let mut offset = 0;
+        for _ in 0..skip {
+            offset += self.wire[offset] as usize + 1;
+        }
+        offset
+    }
+}
+
+impl Default for Name {
+    fn default() -> Self {
+        Self::root()
+    }
+}
+
+impl FromStr for Name {
+    type Err = DnsError;
+
+    /// Parse a name in text form, absolute whether or not it ends in a dot
+    fn from_str(text: &str) -> Result<Name, DnsError> {
+        Name::parse(text).map(|(name, _)| name)
+    }
+}
+
+impl PartialEq for Name {
+    fn eq(&self, other: &Name) -> bool {
+        // length octets are below 64, so only label octets are affected by case
+        self.wire.eq_ignore_ascii_case(&other.wire)
+    }
+}
+
+impl Eq for Name {}
+
+impl Hash for Name {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        for octet in &self.wire {
+            state.write_u8(octet.to_ascii_lowercase());
+        }
+    }
+}
+
+impl Ord for Name {
+    fn cmp(&self, other: &Name) -> Ordering {
+        let ours: Vec<&[u8]> = self.labels().collect();
+        let theirs: Vec<&[u8]> = other.labels().collect();
+
+        for (a, b) in ours.iter().rev().zip(theirs.iter().rev()) {
+            let a = a.iter().map(u8::to_ascii_lowercase);
+            let b = b.iter().map(u8::to_ascii_lowercase);
+            match a.cmp(b) {
+                Ordering::Equal => {}
+                ordering => return ordering,
+            }
+        }
+
+        ours.len().cmp(&theirs.len())
+    }
+}
+
+impl PartialOrd for Name {
+    fn partial_cmp(&self, other: &Name) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for Name {
+    /// The name in text form, e.g. `www.example.com.`, with `{:#}` its A-labels in Unicode
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_root() {
+            return write!(f, ".");
+        }
+        self.write_labels(f, usize::MAX, f.alternate())?;
+        write!(f, ".")
+    }
+}
+
+impl fmt::Debug for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Name({:?})", self.to_string())
+    }
+}
+
+/// Iterates the labels of a `Name`, see `Name::labels`
+#[derive(Clone)]
+pub struct NameLabels<'a> {
+    wire: &'a [u8],
+}
+
+impl<'a> Iterator for NameLabels<'a> {
+    type Item = &'a [u8];
+
+    fn next(&mut self) -> Option<&'a [u8]> {
+        let len = *self.wire.first()? as usize;
+        if len == 0 {
+            return None;
+        }
+        let label = &self.wire[1..=len];
+        self.wire = &self.wire[len + 1..];
+        Some(label)
+    }
+}
+
+/**
+    The Unicode form of an A-label, or `None` if the label is no A-label or
+    does not decode to a valid U-label.
+*/
+fn decode_a_label(label: &[u8]) -> Option<String> {
+    if !label.get(..4)?.eq_ignore_ascii_case(b"xn--") {
+        return None;
+    }
+    let (decoded, result) = Uts46::new().to_unicode(label, AsciiDenyList::EMPTY, Hyphens::Allow);
+    result.ok()?;
+    Some(decoded.into_owned())
+}
+
+/// A label in text form, escaping dots, octets special in master files and non-printable octets
+fn write_label<W: fmt::Write>(f: &mut W, label: &[u8]) -> fmt::Result {
+    for &c in label {
+        match c {
+            b'.' | b'"' | b'(' | b')' | b';' | b'\\' | b'@' | b'$' => write!(f, "\\{}", c as char)?,
+            0x21..=0x7E => write!(f, "{}", c as char)?,
+            _ => write!(f, "\\{:03}", c)?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::hash_map::DefaultHasher;
+
+    use super::*;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn hash(name: &Name) -> u64 {
+        let mut hasher = DefaultHasher::new();
+        name.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// A name with a label of `x` as long as each of `lens`
+    fn long_name(lens: &[usize]) -> Result<Name, DnsError> {
+        Name::from_labels(lens.iter().map(|&len| vec![b'x'; len]))
+    }
+
+    #[test]
+    fn parses_escapes() {
+        let parsed = name(r"a\.b.\065\255x.example");
+        let labels: Vec<&[u8]> = parsed.labels().collect();
+        assert_eq!(labels, [&b"a.b"[..], &[65, 255, b'x'], b"example"]);
+
+        for text in [r"\256.example", r"a\", r"a\1", r"a\12", r"a\1x.example"] {
+            assert!(
+                matches!(text.parse::<Name>(), Err(DnsError::InvalidEscape { .. })),
+                "{}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn tells_absolute_names_from_relative_ones() {
+        assert_eq!(
+            Name::parse("www.example").unwrap(),
+            (name("www.example"), false)
+        );
+        assert_eq!(
+            Name::parse("www.example.").unwrap(),
+            (name("www.example"), true)
+        );
+        assert_eq!(Name::parse(".").unwrap(), (Name::root(), true));
+        // an escaped dot ends no label
+        let (escaped, absolute) = Name::parse(r"www\.").unwrap();
+        assert!(!absolute);
+        assert_eq!(escaped.labels().collect::<Vec<_>>(), [b"www."]);
+
+        assert!(matches!(
+            "www..example".parse::<Name>(),
+            Err(DnsError::EmptyLabel { .. })
+        ));
+    }
+
+    #[test]
+    fn limits_labels_to_63_octets() {
+        assert_eq!(long_name(&[MAX_LABEL_LEN]).unwrap().wire_len(), 65);
+        assert!(matches!(
+            long_name(&[MAX_LABEL_LEN + 1]),
+            Err(DnsError::LabelTooLong { len: 64, .. })
+        ));
+    }
+
+    #[test]
+    fn limits_names_to_255_octets() {
+        assert_eq!(
+            long_name(&[63, 63, 63, 61]).unwrap().wire_len(),
+            MAX_NAME_LEN
+        );
+        assert!(matches!(
+            long_name(&[63, 63, 63, 62]),
+            Err(DnsError::NameTooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn ignores_case_in_equality_and_hashing() {
+        let lower = name("www.example.com");
+        let mixed = name("WWW.Example.COM");
+        assert_eq!(lower, mixed);
+        assert_eq!(hash(&lower), hash(&mixed));
+        // the case given is kept
+        assert_eq!(mixed.to_string(), "WWW.Example.COM.");
+
+        assert_ne!(lower, name("www.example.org"));
+        assert_ne!(lower, name("www.example.com.org"));
+    }
+
+    #[test]
+    fn orders_canonically() {
+        // the example of RFC 4034 section 6.1
+        let ordered: Vec<Name> = [
+            "example",
+            "a.example",
+            "yljkjljk.a.example",
+            "Z.a.example",
+            "zABC.a.EXAMPLE",
+            "z.example",
+            r"\001.z.example",
+            "*.z.example",
+            r"\200.z.example",
+        ]
+        .into_iter()
+        .map(name)
+        .collect();
+
+        let mut sorted = ordered.clone();
+        sorted.reverse();
+        sorted.sort();
+        assert_eq!(sorted, ordered);
+        assert!(Name::root() < ordered[0]);
+    }
+
+    #[test]
+    fn relates_names_to_their_ancestors() {
+        let www = name("www.example.com");
+        assert!(www.is_subdomain_of(&name("EXAMPLE.com")));
+        assert!(www.is_subdomain_of(&www));
+        assert!(www.is_subdomain_of(&Name::root()));
+        assert!(!name("example.com").is_subdomain_of(&www));
+        // only whole labels match
+        assert!(!name("wwwexample.com").is_subdomain_of(&name("example.com")));
+
+        assert_eq!(www.parent(), Some(name("example.com")));
+        assert_eq!(name("com").parent(), Some(Name::root()));
+        assert_eq!(Name::root().parent(), None);
+
+        assert_eq!(www.suffix(2), name("example.com"));
+        assert_eq!(www.suffix(0), Name::root());
+        assert_eq!(www.suffix(5), www);
+    }
+
+    #[test]
+    fn joining_names_keeps_the_limits() {
+        assert_eq!(
+            name("www").append(&name("example.com")).unwrap(),
+            name("www.example.com")
+        );
+        assert_eq!(name("example").prepend(b"*").unwrap(), name("*.example"));
+
+        // 253 octets, with room for one more label of one octet
+        let long = long_name(&[63, 63, 63, 59]).unwrap();
+        assert_eq!(long.prepend(b"a").unwrap().wire_len(), MAX_NAME_LEN);
+        assert!(matches!(
+            long.prepend(b"ab"),
+            Err(DnsError::NameTooLong { .. })
+        ));
+        assert!(matches!(
+            name("ab").append(&long),
+            Err(DnsError::NameTooLong { .. })
+        ));
+        assert!(matches!(
+            name("example").prepend(&[b'x'; 64]),
+            Err(DnsError::LabelTooLong { .. })
+        ));
+    }
+
+    #[test]
+    fn escapes_text_that_parses_back() {
+        let labels: [&[u8]; 4] = [b"a.b", b"\"q\";@", &[0, 0x7f, b' ', 200], b"\\"];
+        let escaped = Name::from_labels(labels).unwrap();
+        let text = escaped.to_string();
+        assert_eq!(text, r#"a\.b.\"q\"\;\@.\000\127\032\200.\\."#);
+
+        let parsed = name(&text);
+        assert_eq!(parsed.as_wire(), escaped.as_wire());
+        assert_eq!(Name::root().to_string(), ".");
+        assert_eq!(name("."), Name::root());
+    }
+
+    #[test]
+    fn converts_unicode_names_to_a_labels_and_back() {
+        let bucher = Name::from_unicode("Bücher.de").unwrap();
+        assert_eq!(bucher.to_string(), "xn--bcher-kva.de.");
+        assert_eq!(format!("{:#}", bucher), "bücher.de.");
+        assert_eq!(bucher.to_unicode(), "bücher.de.");
+
+        assert_eq!(Name::from_unicode("bücher.de.").unwrap(), bucher);
+        assert_eq!(Name::from_unicode("").unwrap(), Name::root());
+    }
+
+    #[test]
+    fn lets_through_the_ascii_names_need() {
+        for text in ["_sip._tcp.example.com", "*.example.com", "-x-.example"] {
+            assert_eq!(Name::from_unicode(text).unwrap(), name(text), "{}", text);
+        }
+    }
+
+    #[test]
+    fn rejects_spaces_and_control_characters() {
+        for text in ["a b.example", "a\u{7}.example", "a\u{0}b"] {
+            assert!(
+                matches!(Name::from_unicode(text), Err(DnsError::Idna { .. })),
+                "{:?}",
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn displays_invalid_a_labels_as_they_are() {
+        // not valid Punycode, and Punycode for an ASCII-only label
+        for text in ["xn--.example.", "xn--abc-.example."] {
+            assert_eq!(format!("{:#}", name(text)), text);
+        }
+        assert_eq!(decode_a_label(b"xn--bcher-kva").as_deref(), Some("bücher"));
+        assert_eq!(decode_a_label(b"bcher-kva"), None);
+        assert_eq!(decode_a_label(b"xn--"), None);
+        assert_eq!(decode_a_label(b"xn--abc-"), None);
+    }
+}
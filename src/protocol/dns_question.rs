@@ -1,7 +1,11 @@
+use std::fmt;
+
+use crate::protocol::dns_name::Name;
 use crate::protocol::question_class_enum::QuestionClass;
 use crate::protocol::question_type_enum::QuestionType;
+
 /**
-```
+```text
   0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
 +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 |                                               |
@@ -16,13 +20,13 @@ use crate::protocol::question_type_enum::QuestionType;
 */
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsQuestion {
-    pub name: String,
+    pub name: Name,
     pub q_type: QuestionType,
     pub q_class: QuestionClass,
 }
 
 impl DnsQuestion {
-    pub fn new(name: String, q_type: QuestionType, q_class: QuestionClass) -> DnsQuestion {
+    pub fn new(name: Name, q_type: QuestionType, q_class: QuestionClass) -> DnsQuestion {
         DnsQuestion {
             name,
             q_type,
@@ -30,3 +34,11 @@ impl DnsQuestion {
         }
     }
 }
+
+impl fmt::Display for DnsQuestion {
+    /// The question as dig shows it, e.g. `example.com. IN MX`, with `{:#}` the name in Unicode
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.name, f)?;
+        write!(f, " {} {}", self.q_class, self.q_type)
+    }
+}
@@ -1,8 +1,13 @@
+use std::fmt;
+use std::net::Ipv4Addr;
+
+use crate::protocol::dns_name::Name;
 use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
 use crate::protocol::resource_type_enum::ResourceType;
 
 /**
-```
+```text
   0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
 +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
 |                                               |
@@ -27,7 +32,7 @@ use crate::protocol::resource_type_enum::ResourceType;
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct DnsRecord {
     /// a domain name to which this resource record pertains.
-    pub name: String,
+    pub name: Name,
     /**
         two octets containing one of the RR type codes.  This
         field specifies the meaning of the data in the RDATA
@@ -49,7 +54,8 @@ pub struct DnsRecord {
     pub ttl: u32,
     /**
         an unsigned 16 bit integer that specifies the length in
-        octets of the RDATA field.
+        octets of the RDATA field. It is informational only: encoding
+        always derives RDLENGTH from the written RDATA.
     */
     pub rd_length: u16,
     /**
@@ -59,18 +65,48 @@ pub struct DnsRecord {
         For example, the if the TYPE is A and the CLASS is IN,
         the RDATA field is a 4 octet ARPA Internet address.
     */
-    pub r_data: String,
+    pub r_data: RData,
 }
 
 impl DnsRecord {
     pub fn new() -> DnsRecord {
         DnsRecord {
-            name: String::new(),
+            name: Name::root(),
             r_type: ResourceType::A,
             r_class: ResourceClass::IN,
             ttl: 0,
             rd_length: 0,
-            r_data: String::new(),
+            r_data: RData::A(Ipv4Addr::UNSPECIFIED),
+        }
+    }
+
+    /// A record of the Internet class whose TYPE is the one of its RDATA
+    pub fn with_r_data(name: Name, ttl: u32, r_data: RData) -> DnsRecord {
+        DnsRecord {
+            name,
+            r_type: r_data.r_type(),
+            r_class: ResourceClass::IN,
+            ttl,
+            rd_length: 0,
+            r_data,
         }
     }
 }
+
+impl Default for DnsRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Display for DnsRecord {
+    /**
+        The record in presentation format, e.g. `example.com. 300 IN MX 10
+        mail.example.com.`, with `{:#}` its names in Unicode.
+    */
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.name, f)?;
+        write!(f, " {} {} {} ", self.ttl, self.r_class, self.r_type)?;
+        fmt::Display::fmt(&self.r_data, f)
+    }
+}
@@ -0,0 +1,95 @@
+use std::fmt;
+use std::net::IpAddr;
+
+use crate::encoding::hex_encode;
+
+/**
+    An option carried in the RDATA of an OPT pseudo-record (RFC 6891
+    section 6.1.2).
+
+    ```text
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                  OPTION-CODE                  |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                 OPTION-LENGTH                 |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /                  OPTION-DATA                  /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdnsOption {
+    /// Name server identifier (RFC 5001), empty in queries
+    Nsid(Vec<u8>),
+    /// Client subnet (RFC 7871)
+    ClientSubnet {
+        /// The leftmost bits of the address the query was made for
+        source_prefix: u8,
+        /// The leftmost bits of the address the answer covers, 0 in queries
+        scope_prefix: u8,
+        /// Only the first `source_prefix` bits are sent
+        address: IpAddr,
+    },
+    /// DNS cookies (RFC 7873)
+    Cookie {
+        client: [u8; 8],
+        /// Empty, or 8 to 32 octets
+        server: Vec<u8>,
+    },
+    /// The number of zero octets padding the message (RFC 7830)
+    Padding(u16),
+    /// Extended DNS errors (RFC 8914)
+    ExtendedError { info_code: u16, extra_text: String },
+    /// Any option this implementation has no dedicated support for
+    Unknown { code: u16, data: Vec<u8> },
+}
+
+impl EdnsOption {
+    pub const NSID: u16 = 3;
+    pub const CLIENT_SUBNET: u16 = 8;
+    pub const COOKIE: u16 = 10;
+    pub const PADDING: u16 = 12;
+    pub const EXTENDED_ERROR: u16 = 15;
+
+    /// The OPTION-CODE of the option
+    pub fn code(&self) -> u16 {
+        match self {
+            EdnsOption::Nsid(_) => Self::NSID,
+            EdnsOption::ClientSubnet { .. } => Self::CLIENT_SUBNET,
+            EdnsOption::Cookie { .. } => Self::COOKIE,
+            EdnsOption::Padding(_) => Self::PADDING,
+            EdnsOption::ExtendedError { .. } => Self::EXTENDED_ERROR,
+            EdnsOption::Unknown { code, .. } => *code,
+        }
+    }
+}
+
+impl fmt::Display for EdnsOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EdnsOption::Nsid(id) => write!(f, "NSID: {}", hex_encode(id)),
+            EdnsOption::ClientSubnet {
+                source_prefix,
+                scope_prefix,
+                address,
+            } => write!(
+                f,
+                "CLIENT-SUBNET: {}/{}/{}",
+                address, source_prefix, scope_prefix
+            ),
+            EdnsOption::Cookie { client, server } => {
+                write!(f, "COOKIE: {}", hex_encode(client))?;
+                if !server.is_empty() {
+                    write!(f, " {}", hex_encode(server))?;
+                }
+                Ok(())
+            }
+            EdnsOption::Padding(len) => write!(f, "PADDING: {} octets", len),
+            EdnsOption::ExtendedError {
+                info_code,
+                extra_text,
+            } => write!(f, "EDE: {} {:?}", info_code, extra_text),
+            EdnsOption::Unknown { code, data } => write!(f, "OPT{}: {}", code, hex_encode(data)),
+        }
+    }
+}
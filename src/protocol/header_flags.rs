@@ -0,0 +1,134 @@
+use std::fmt;
+use std::ops::{BitAnd, BitOr, BitOrAssign, Not};
+
+/**
+    The single bit flags of the header's second 16 bit word, in their wire
+    positions. OPCODE and RCODE share the word but are not flags and are
+    masked out.
+
+    ```text
+      0  1  2  3  4  5  6  7  8  9  0  1  2  3  4  5
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |QR|   Opcode  |AA|TC|RD|RA| Z|AD|CD|   RCODE   |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+*/
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Flags(u16);
+
+impl Flags {
+    /// Query (0) or Response (1)
+    pub const QR: Flags = Flags(1 << 15);
+    /// Authoritative Answer
+    pub const AA: Flags = Flags(1 << 10);
+    /// TrunCation
+    pub const TC: Flags = Flags(1 << 9);
+    /// Recursion Desired
+    pub const RD: Flags = Flags(1 << 8);
+    /// Recursion Available
+    pub const RA: Flags = Flags(1 << 7);
+    /// Reserved, must be zero
+    pub const Z: Flags = Flags(1 << 6);
+    /// Authentic Data (RFC 4035)
+    pub const AD: Flags = Flags(1 << 5);
+    /// Checking Disabled (RFC 4035)
+    pub const CD: Flags = Flags(1 << 4);
+
+    /// Every flag bit of the word
+    pub const ALL: Flags = Flags(0x87F0);
+
+    pub const fn empty() -> Flags {
+        Flags(0)
+    }
+
+    /// The flags as they sit in the 16 bit word
+    pub const fn bits(&self) -> u16 {
+        self.0
+    }
+
+    /// The flags of a 16 bit word, dropping the OPCODE and RCODE bits
+    pub const fn from_bits_truncate(bits: u16) -> Flags {
+        Flags(bits & Self::ALL.0)
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    pub const fn contains(&self, other: Flags) -> bool {
+        (self.0 & other.0) == other.0
+    }
+
+    pub fn insert(&mut self, other: Flags) {
+        self.0 |= other.0;
+    }
+
+    pub fn remove(&mut self, other: Flags) {
+        self.0 &= !other.0;
+    }
+
+    /// Insert or remove the flags depending on `value`
+    pub fn set(&mut self, other: Flags, value: bool) {
+        if value {
+            self.insert(other);
+        } else {
+            self.remove(other);
+        }
+    }
+}
+
+impl BitOr for Flags {
+    type Output = Flags;
+
+    fn bitor(self, rhs: Flags) -> Flags {
+        Flags(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Flags {
+    fn bitor_assign(&mut self, rhs: Flags) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl BitAnd for Flags {
+    type Output = Flags;
+
+    fn bitand(self, rhs: Flags) -> Flags {
+        Flags(self.0 & rhs.0)
+    }
+}
+
+impl Not for Flags {
+    type Output = Flags;
+
+    fn not(self) -> Flags {
+        Flags(!self.0 & Self::ALL.0)
+    }
+}
+
+impl fmt::Display for Flags {
+    /// The lowercase mnemonics of the set flags, as dig prints them
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (Flags::QR, "qr"),
+            (Flags::AA, "aa"),
+            (Flags::TC, "tc"),
+            (Flags::RD, "rd"),
+            (Flags::RA, "ra"),
+            (Flags::Z, "z"),
+            (Flags::AD, "ad"),
+            (Flags::CD, "cd"),
+        ];
+
+        let mut delimiter = "";
+        for (flag, name) in names {
+            if self.contains(flag) {
+                write!(f, "{}{}", delimiter, name)?;
+                delimiter = " ";
+            }
+        }
+
+        Ok(())
+    }
+}
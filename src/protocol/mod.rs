@@ -1,9 +1,15 @@
+pub mod dns_edns;
 pub mod dns_header;
+pub mod dns_name;
 pub mod dns_question;
 pub mod dns_resource_record;
+pub mod edns_option_enum;
+pub mod header_flags;
 pub mod opcode_enum;
 pub mod question_class_enum;
 pub mod question_type_enum;
 pub mod rcode_enum;
 pub mod resource_class_enum;
+pub mod resource_data_enum;
 pub mod resource_type_enum;
+pub mod svc_param_enum;
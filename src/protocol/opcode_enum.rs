@@ -1,3 +1,5 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum OpCode {
@@ -7,7 +9,7 @@ pub enum OpCode {
     Query = 0u8,
 
     /**
-       an inverse query
+       an inverse query (Obsolete - RFC 3425)
     */
     IQuery = 1u8,
 
@@ -15,17 +17,38 @@ pub enum OpCode {
        a server status request
     */
     Status = 2u8,
-}
 
-impl TryFrom<u8> for OpCode {
-    type Error = &'static str;
+    /**
+       a notification of zone change (RFC 1996)
+    */
+    Notify = 4u8,
+
+    /**
+       a dynamic update (RFC 2136)
+    */
+    Update = 5u8,
+
+    /**
+       DNS Stateful Operations (RFC 8490)
+    */
+    Dso = 6u8,
+
+    /**
+       any value unassigned by IANA
+    */
+    Unknown(u8),
+}
 
-    fn try_from(v: u8) -> Result<Self, &'static str> {
+impl From<u8> for OpCode {
+    fn from(v: u8) -> Self {
         match v {
-            x if x == OpCode::Query.into() => Ok(OpCode::Query),
-            x if x == OpCode::IQuery.into() => Ok(OpCode::IQuery),
-            x if x == OpCode::Status.into() => Ok(OpCode::Status),
-            _ => Err("OpCode No Exist"),
+            x if x == OpCode::Query.into() => OpCode::Query,
+            x if x == OpCode::IQuery.into() => OpCode::IQuery,
+            x if x == OpCode::Status.into() => OpCode::Status,
+            x if x == OpCode::Notify.into() => OpCode::Notify,
+            x if x == OpCode::Update.into() => OpCode::Update,
+            x if x == OpCode::Dso.into() => OpCode::Dso,
+            x => OpCode::Unknown(x),
         }
     }
 }
@@ -36,6 +59,40 @@ impl From<OpCode> for u8 {
             OpCode::Query => 0,
             OpCode::IQuery => 1,
             OpCode::Status => 2,
+            OpCode::Notify => 4,
+            OpCode::Update => 5,
+            OpCode::Dso => 6,
+            OpCode::Unknown(x) => x,
+        }
+    }
+}
+
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OpCode::Query => write!(f, "QUERY"),
+            OpCode::IQuery => write!(f, "IQUERY"),
+            OpCode::Status => write!(f, "STATUS"),
+            OpCode::Notify => write!(f, "NOTIFY"),
+            OpCode::Update => write!(f, "UPDATE"),
+            OpCode::Dso => write!(f, "DSO"),
+            OpCode::Unknown(x) => write!(f, "OPCODE{}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn every_value_round_trips() {
+        for value in 0..16u8 {
+            let opcode = OpCode::from(value);
+            assert_eq!(u8::from(opcode), value);
+
+            let assigned = matches!(value, 0..=2 | 4..=6);
+            assert_eq!(!matches!(opcode, OpCode::Unknown(_)), assigned, "{}", value);
         }
     }
 }
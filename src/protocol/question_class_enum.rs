@@ -1,26 +1,22 @@
+use std::fmt;
+
 use crate::protocol::question_class_enum::QuestionClass::Base;
 use crate::protocol::resource_class_enum::ResourceClass;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u16)]
 pub enum QuestionClass {
+    /// Any CLASS, including the ones unknown to this implementation
     Base(ResourceClass),
     /// any class
     All = 255u16,
 }
 
-impl TryFrom<u16> for QuestionClass {
-    type Error = &'static str;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        let resource_bound: [u16; 2] = [ResourceClass::IN.into(), ResourceClass::HS.into()];
+impl From<u16> for QuestionClass {
+    fn from(value: u16) -> Self {
         match value {
-            x if resource_bound[0] <= x && x <= resource_bound[1] => {
-                let resource_class: ResourceClass = x.try_into()?;
-                Ok(Base(resource_class))
-            }
-            x if x == QuestionClass::All.into() => Ok(QuestionClass::All),
-            _ => Err("ResourceClass No Exist"),
+            x if x == QuestionClass::All.into() => QuestionClass::All,
+            x => Base(x.into()),
         }
     }
 }
@@ -33,3 +29,22 @@ impl From<QuestionClass> for u16 {
         }
     }
 }
+
+impl QuestionClass {
+    /// The QCLASS named by a mnemonic, e.g. `IN` or `ANY`, ignoring case
+    pub fn from_mnemonic(mnemonic: &str) -> Option<QuestionClass> {
+        if mnemonic.eq_ignore_ascii_case("ANY") {
+            return Some(QuestionClass::All);
+        }
+        ResourceClass::from_mnemonic(mnemonic).map(Base)
+    }
+}
+
+impl fmt::Display for QuestionClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Base(resource_class) => resource_class.fmt(f),
+            QuestionClass::All => write!(f, "ANY"),
+        }
+    }
+}
@@ -1,8 +1,11 @@
+use std::fmt;
+
 use crate::protocol::resource_type_enum::ResourceType;
 
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 #[repr(u16)]
 pub enum QuestionType {
+    /// Any TYPE, including the ones unknown to this implementation
     Base(ResourceType),
     /// A request for a transfer of an entire zone
     AxfR = 252u16,
@@ -14,21 +17,14 @@ pub enum QuestionType {
     All = 255u16,
 }
 
-impl TryFrom<u16> for QuestionType {
-    type Error = &'static str;
-
-    fn try_from(v: u16) -> Result<Self, &'static str> {
-        let resource_bound: [u16; 2] = [ResourceType::A.into(), ResourceType::Txt.into()];
+impl From<u16> for QuestionType {
+    fn from(v: u16) -> Self {
         match v {
-            x if resource_bound[0] <= x && x <= resource_bound[1] => {
-                let resource_type = x.try_into()?;
-                Ok(QuestionType::Base(resource_type))
-            }
-            x if x == QuestionType::AxfR.into() => Ok(QuestionType::AxfR),
-            x if x == QuestionType::MailB.into() => Ok(QuestionType::MailB),
-            x if x == QuestionType::MailA.into() => Ok(QuestionType::MailA),
-            x if x == QuestionType::All.into() => Ok(QuestionType::All),
-            _ => Err("QuestionType No Exist"),
+            x if x == QuestionType::AxfR.into() => QuestionType::AxfR,
+            x if x == QuestionType::MailB.into() => QuestionType::MailB,
+            x if x == QuestionType::MailA.into() => QuestionType::MailA,
+            x if x == QuestionType::All.into() => QuestionType::All,
+            x => QuestionType::Base(x.into()),
         }
     }
 }
@@ -44,3 +40,29 @@ impl From<QuestionType> for u16 {
         }
     }
 }
+
+impl QuestionType {
+    /// The QTYPE named by a mnemonic, e.g. `MX`, `AXFR` or `ANY`, ignoring case
+    pub fn from_mnemonic(mnemonic: &str) -> Option<QuestionType> {
+        let q_type = match mnemonic.to_ascii_uppercase().as_str() {
+            "AXFR" => QuestionType::AxfR,
+            "MAILB" => QuestionType::MailB,
+            "MAILA" => QuestionType::MailA,
+            "ANY" => QuestionType::All,
+            _ => return ResourceType::from_mnemonic(mnemonic).map(QuestionType::Base),
+        };
+        Some(q_type)
+    }
+}
+
+impl fmt::Display for QuestionType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuestionType::Base(resource_type) => resource_type.fmt(f),
+            QuestionType::AxfR => write!(f, "AXFR"),
+            QuestionType::MailB => write!(f, "MAILB"),
+            QuestionType::MailA => write!(f, "MAILA"),
+            QuestionType::All => write!(f, "ANY"),
+        }
+    }
+}
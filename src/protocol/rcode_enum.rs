@@ -1,21 +1,23 @@
+use std::fmt;
+
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
-#[repr(u8)]
+#[repr(u16)]
 pub enum RCode {
     /**
        No error condition
     */
-    NoError = 0u8,
+    NoError = 0u16,
     /**
        Format error - The name server was
        unable to interpret the query.
     */
-    FormErr = 1u8,
+    FormErr = 1u16,
     /**
        Server failure - The name server was
        unable to process this query due to a
        problem with the name server.
     */
-    ServFail = 2u8,
+    ServFail = 2u16,
     /**
        Name Error - Meaningful only for
        responses from an authoritative name
@@ -23,12 +25,12 @@ pub enum RCode {
        domain name referenced in the query does
        not exist.
     */
-    NameErr = 3u8,
+    NameErr = 3u16,
     /**
        Not Implemented - The name server does
        not support the requested kind of query.
     */
-    NotImp = 4u8,
+    NotImp = 4u16,
     /**
        Refused - The name server refuses to
        perform the specified operation for
@@ -37,27 +39,102 @@ pub enum RCode {
        information to the particular requester,
        or a name server may not wish to perform
        a particular operation (e.g., zone
+       transfer) for particular data.
+    */
+    Refused = 5u16,
+    /**
+       Name Exists when it should not (RFC 2136)
+    */
+    YxDomain = 6u16,
+    /**
+       RR Set Exists when it should not (RFC 2136)
+    */
+    YxRrSet = 7u16,
+    /**
+       RR Set that should exist does not (RFC 2136)
+    */
+    NxRrSet = 8u16,
+    /**
+       Server Not Authoritative for zone (RFC 2136), or
+       Not Authorized (RFC 8945)
+    */
+    NotAuth = 9u16,
+    /**
+       Name not contained in zone (RFC 2136)
+    */
+    NotZone = 10u16,
+    /**
+       DSO-TYPE Not Implemented (RFC 8490)
+    */
+    DsoTypeNi = 11u16,
+    /**
+       Bad OPT Version (RFC 6891), or TSIG Signature
+       Failure (RFC 8945). Needs the extended RCODE.
+    */
+    BadVers = 16u16,
+    /**
+       Key not recognized (RFC 8945)
+    */
+    BadKey = 17u16,
+    /**
+       Signature out of time window (RFC 8945)
+    */
+    BadTime = 18u16,
+    /**
+       Bad TKEY Mode (RFC 2930)
+    */
+    BadMode = 19u16,
+    /**
+       Duplicate key name (RFC 2930)
     */
-    Refused = 5u8,
+    BadName = 20u16,
+    /**
+       Algorithm not supported (RFC 2930)
+    */
+    BadAlg = 21u16,
+    /**
+       Bad Truncation (RFC 8945)
+    */
+    BadTrunc = 22u16,
+    /**
+       Bad/missing Server Cookie (RFC 7873)
+    */
+    BadCookie = 23u16,
+    /**
+       any value of the 12 bit extended RCODE unassigned by IANA
+    */
+    Unknown(u16),
 }
 
-impl TryFrom<u8> for RCode {
-    type Error = &'static str;
-
-    fn try_from(v: u8) -> Result<Self, &'static str> {
+impl From<u16> for RCode {
+    fn from(v: u16) -> Self {
         match v {
-            x if x == RCode::NoError.into() => Ok(RCode::NoError),
-            x if x == RCode::FormErr.into() => Ok(RCode::FormErr),
-            x if x == RCode::ServFail.into() => Ok(RCode::ServFail),
-            x if x == RCode::NameErr.into() => Ok(RCode::NameErr),
-            x if x == RCode::NotImp.into() => Ok(RCode::NotImp),
-            x if x == RCode::Refused.into() => Ok(RCode::Refused),
-            _ => Err("RCode No Exist"),
+            x if x == RCode::NoError.into() => RCode::NoError,
+            x if x == RCode::FormErr.into() => RCode::FormErr,
+            x if x == RCode::ServFail.into() => RCode::ServFail,
+            x if x == RCode::NameErr.into() => RCode::NameErr,
+            x if x == RCode::NotImp.into() => RCode::NotImp,
+            x if x == RCode::Refused.into() => RCode::Refused,
+            x if x == RCode::YxDomain.into() => RCode::YxDomain,
+            x if x == RCode::YxRrSet.into() => RCode::YxRrSet,
+            x if x == RCode::NxRrSet.into() => RCode::NxRrSet,
+            x if x == RCode::NotAuth.into() => RCode::NotAuth,
+            x if x == RCode::NotZone.into() => RCode::NotZone,
+            x if x == RCode::DsoTypeNi.into() => RCode::DsoTypeNi,
+            x if x == RCode::BadVers.into() => RCode::BadVers,
+            x if x == RCode::BadKey.into() => RCode::BadKey,
+            x if x == RCode::BadTime.into() => RCode::BadTime,
+            x if x == RCode::BadMode.into() => RCode::BadMode,
+            x if x == RCode::BadName.into() => RCode::BadName,
+            x if x == RCode::BadAlg.into() => RCode::BadAlg,
+            x if x == RCode::BadTrunc.into() => RCode::BadTrunc,
+            x if x == RCode::BadCookie.into() => RCode::BadCookie,
+            x => RCode::Unknown(x),
         }
     }
 }
 
-impl From<RCode> for u8 {
+impl From<RCode> for u16 {
     fn from(code: RCode) -> Self {
         match code {
             RCode::NoError => 0,
@@ -66,6 +143,86 @@ impl From<RCode> for u8 {
             RCode::NameErr => 3,
             RCode::NotImp => 4,
             RCode::Refused => 5,
+            RCode::YxDomain => 6,
+            RCode::YxRrSet => 7,
+            RCode::NxRrSet => 8,
+            RCode::NotAuth => 9,
+            RCode::NotZone => 10,
+            RCode::DsoTypeNi => 11,
+            RCode::BadVers => 16,
+            RCode::BadKey => 17,
+            RCode::BadTime => 18,
+            RCode::BadMode => 19,
+            RCode::BadName => 20,
+            RCode::BadAlg => 21,
+            RCode::BadTrunc => 22,
+            RCode::BadCookie => 23,
+            RCode::Unknown(x) => x,
         }
     }
 }
+
+impl fmt::Display for RCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RCode::NoError => write!(f, "NOERROR"),
+            RCode::FormErr => write!(f, "FORMERR"),
+            RCode::ServFail => write!(f, "SERVFAIL"),
+            RCode::NameErr => write!(f, "NXDOMAIN"),
+            RCode::NotImp => write!(f, "NOTIMP"),
+            RCode::Refused => write!(f, "REFUSED"),
+            RCode::YxDomain => write!(f, "YXDOMAIN"),
+            RCode::YxRrSet => write!(f, "YXRRSET"),
+            RCode::NxRrSet => write!(f, "NXRRSET"),
+            RCode::NotAuth => write!(f, "NOTAUTH"),
+            RCode::NotZone => write!(f, "NOTZONE"),
+            RCode::DsoTypeNi => write!(f, "DSOTYPENI"),
+            RCode::BadVers => write!(f, "BADVERS"),
+            RCode::BadKey => write!(f, "BADKEY"),
+            RCode::BadTime => write!(f, "BADTIME"),
+            RCode::BadMode => write!(f, "BADMODE"),
+            RCode::BadName => write!(f, "BADNAME"),
+            RCode::BadAlg => write!(f, "BADALG"),
+            RCode::BadTrunc => write!(f, "BADTRUNC"),
+            RCode::BadCookie => write!(f, "BADCOOKIE"),
+            RCode::Unknown(x) => write!(f, "RCODE{}", x),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::dns_packet::DnsPacket;
+
+    #[test]
+    fn every_value_round_trips() {
+        for value in 0..4096u16 {
+            let r_code = RCode::from(value);
+            assert_eq!(u16::from(r_code), value);
+
+            let assigned = matches!(value, 0..=11 | 16..=23);
+            assert_eq!(!matches!(r_code, RCode::Unknown(_)), assigned, "{}", value);
+        }
+    }
+
+    #[test]
+    fn extended_rcodes_split_between_header_and_opt() {
+        let mut packet = DnsPacket::new();
+        packet.set_extended_r_code(RCode::BadCookie);
+        assert_eq!(packet.header.r_code, RCode::from(23 & 0x0F));
+        assert_eq!(
+            packet.edns.as_ref().map(|edns| edns.extended_r_code),
+            Some(1)
+        );
+
+        let decoded = DnsPacket::from_bytes(&packet.to_bytes().unwrap()).unwrap();
+        assert_eq!(decoded.extended_r_code(), RCode::BadCookie);
+
+        // without EDNS the header holds the whole RCODE
+        let mut packet = DnsPacket::new();
+        packet.set_extended_r_code(RCode::NameErr);
+        assert!(packet.edns.is_none());
+        assert_eq!(packet.extended_r_code(), RCode::NameErr);
+    }
+}
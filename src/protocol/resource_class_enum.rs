@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum ResourceClass {
     /// the Internet
@@ -9,18 +11,18 @@ pub enum ResourceClass {
     CH = 3u16,
     /// Hesiod [Dyer 87]
     HS = 4u16,
+    /// Any CLASS this implementation has no dedicated support for
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for ResourceClass {
-    type Error = &'static str;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
+impl From<u16> for ResourceClass {
+    fn from(value: u16) -> Self {
         match value {
-            x if x == ResourceClass::IN.into() => Ok(ResourceClass::IN),
-            x if x == ResourceClass::CS.into() => Ok(ResourceClass::CS),
-            x if x == ResourceClass::CH.into() => Ok(ResourceClass::CH),
-            x if x == ResourceClass::HS.into() => Ok(ResourceClass::HS),
-            _ => Err("ResourceClass No Exist"),
+            x if x == ResourceClass::IN.into() => ResourceClass::IN,
+            x if x == ResourceClass::CS.into() => ResourceClass::CS,
+            x if x == ResourceClass::CH.into() => ResourceClass::CH,
+            x if x == ResourceClass::HS.into() => ResourceClass::HS,
+            x => ResourceClass::Unknown(x),
         }
     }
 }
@@ -32,6 +34,41 @@ impl From<ResourceClass> for u16 {
             ResourceClass::CS => 2u16,
             ResourceClass::CH => 3u16,
             ResourceClass::HS => 4u16,
+            ResourceClass::Unknown(x) => x,
+        }
+    }
+}
+
+impl ResourceClass {
+    /// The CLASS named by a mnemonic or the RFC 3597 CLASSnnn form, ignoring case
+    pub fn from_mnemonic(mnemonic: &str) -> Option<ResourceClass> {
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let r_class = match mnemonic.as_str() {
+            "IN" => ResourceClass::IN,
+            "CS" => ResourceClass::CS,
+            "CH" => ResourceClass::CH,
+            "HS" => ResourceClass::HS,
+            _ => {
+                return mnemonic
+                    .strip_prefix("CLASS")?
+                    .parse::<u16>()
+                    .ok()
+                    .map(Into::into)
+            }
+        };
+        Some(r_class)
+    }
+}
+
+impl fmt::Display for ResourceClass {
+    /// The mnemonic of the CLASS, or the RFC 3597 generic CLASSnnn form
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceClass::IN => write!(f, "IN"),
+            ResourceClass::CS => write!(f, "CS"),
+            ResourceClass::CH => write!(f, "CH"),
+            ResourceClass::HS => write!(f, "HS"),
+            ResourceClass::Unknown(x) => write!(f, "CLASS{}", x),
         }
     }
 }
@@ -0,0 +1,454 @@
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::encoding::hex_encode;
+use crate::protocol::dns_name::Name;
+use crate::protocol::edns_option_enum::EdnsOption;
+use crate::protocol::resource_type_enum::ResourceType;
+use crate::protocol::svc_param_enum::SvcParam;
+
+/**
+    The RDATA of a resource record, decoded according to its TYPE.
+
+    Domain names embedded in the RDATA are stored fully decompressed, and
+    <character-string>s are kept as raw octets since RFC 1035 does not
+    restrict them to any character set.
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RData {
+    /// A 32 bit Internet address
+    A(Ipv4Addr),
+    /// A host which should be authoritative for the specified class and domain
+    NS(Name),
+    /// A host which has a mail agent for the domain (Obsolete - use MX)
+    MD(Name),
+    /// A host which has a mail agent for the domain (Obsolete - use MX)
+    MF(Name),
+    /// The canonical or primary name for the owner. The owner name is an alias
+    CName(Name),
+    /**
+    ```text
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /                     MNAME                     /
+    /                                               /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /                     RNAME                     /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                    SERIAL                     |
+    |                                               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                    REFRESH                    |
+    |                                               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                     RETRY                     |
+    |                                               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                    EXPIRE                     |
+    |                                               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                    MINIMUM                    |
+    |                                               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+    */
+    SOA {
+        /// The name server that was the original or primary source of data for this zone
+        m_name: Name,
+        /// The mailbox of the person responsible for this zone
+        r_name: Name,
+        /// The version number of the original copy of the zone
+        serial: u32,
+        /// Time interval before the zone should be refreshed
+        refresh: u32,
+        /// Time interval that should elapse before a failed refresh should be retried
+        retry: u32,
+        /// Upper limit on the time interval that can elapse before the zone is no longer authoritative
+        expire: u32,
+        /// The minimum TTL field that should be exported with any RR from this zone
+        minimum: u32,
+    },
+    /// A host which has the specified mailbox
+    MB(Name),
+    /// A mailbox which is a member of the mail group specified by the domain name
+    MG(Name),
+    /// A mailbox which is the proper rename of the specified mailbox
+    MR(Name),
+    /// Anything at all may be in the RDATA field so long as it is 65535 octets or less
+    Null(Vec<u8>),
+    /**
+    ```text
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                    ADDRESS                    |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |       PROTOCOL        |                       |
+    +--+--+--+--+--+--+--+--+                       |
+    |                                               |
+    /                   <BIT MAP>                   /
+    /                                               /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+    */
+    WKS {
+        address: Ipv4Addr,
+        /// An 8 bit IP protocol number
+        protocol: u8,
+        /// One bit per port of the specified protocol
+        bit_map: Vec<u8>,
+    },
+    /// A domain name which points to some location in the domain name space
+    PTR(Name),
+    HInfo {
+        /// A <character-string> which specifies the CPU type
+        cpu: Vec<u8>,
+        /// A <character-string> which specifies the operating system type
+        os: Vec<u8>,
+    },
+    MInfo {
+        /// A mailbox which is responsible for the mailing list or mailbox
+        r_mail_bx: Name,
+        /// A mailbox which is to receive error messages related to the mailing list or mailbox
+        e_mail_bx: Name,
+    },
+    MX {
+        /// The preference given to this RR among others at the same owner, lower values are preferred
+        preference: u16,
+        /// A host willing to act as a mail exchange for the owner name
+        exchange: Name,
+    },
+    /// One or more <character-string>s
+    Txt(Vec<Vec<u8>>),
+    /// A 128 bit IPv6 address
+    AAAA(Ipv6Addr),
+    SRV {
+        /// The priority of this target host, lower values are preferred
+        priority: u16,
+        /// A relative weight for entries with the same priority
+        weight: u16,
+        /// The port on this target host of this service
+        port: u16,
+        /// The domain name of the target host, never compressed
+        target: Name,
+    },
+    NAPTR {
+        /// The order in which the NAPTR records must be processed
+        order: u16,
+        /// The order in which NAPTR records with equal order should be processed
+        preference: u16,
+        /// A <character-string> of flags controlling the rewriting
+        flags: Vec<u8>,
+        /// A <character-string> naming the available services
+        services: Vec<u8>,
+        /// A <character-string> holding a substitution expression
+        regexp: Vec<u8>,
+        /// The next domain name to query for, never compressed
+        replacement: Name,
+    },
+    /// The target the subtree below the owner name is redirected to, never compressed
+    DName(Name),
+    /// The options of an OPT pseudo-record, see `Edns`
+    OPT(Vec<EdnsOption>),
+    SSHFP {
+        /// The algorithm of the public key
+        algorithm: u8,
+        /// The message digest algorithm used to calculate the fingerprint
+        fp_type: u8,
+        fingerprint: Vec<u8>,
+    },
+    TLSA {
+        /// How the certificate association is matched against the TLS handshake
+        cert_usage: u8,
+        /// Which part of the TLS certificate is matched
+        selector: u8,
+        /// How the certificate association is presented
+        matching_type: u8,
+        cert_data: Vec<u8>,
+    },
+    SVCB {
+        /// 0 for AliasMode, otherwise the priority of the ServiceMode record
+        priority: u16,
+        /// The domain name of the alias or the alternative endpoint, never compressed
+        target: Name,
+        /// The SvcParams in ascending key order
+        params: Vec<SvcParam>,
+    },
+    /// A SVCB record for the HTTPS scheme, sharing its format
+    HTTPS {
+        priority: u16,
+        target: Name,
+        params: Vec<SvcParam>,
+    },
+    CAA {
+        /// The issuer critical flag is the high order bit
+        flags: u8,
+        /// The property identifier, such as "issue", "issuewild" or "iodef"
+        tag: Vec<u8>,
+        /// The property value
+        value: Vec<u8>,
+    },
+    /**
+        RDATA this implementation does not understand, kept as the opaque
+        octets it was received as (RFC 3597) so it re-encodes unchanged.
+    */
+    Unknown { r_type: ResourceType, data: Vec<u8> },
+}
+
+impl RData {
+    /// The TYPE of the resource records carrying this RDATA
+    pub fn r_type(&self) -> ResourceType {
+        match self {
+            RData::A(_) => ResourceType::A,
+            RData::NS(_) => ResourceType::NS,
+            RData::MD(_) => ResourceType::MD,
+            RData::MF(_) => ResourceType::MF,
+            RData::CName(_) => ResourceType::CName,
+            RData::SOA { .. } => ResourceType::SOA,
+            RData::MB(_) => ResourceType::MB,
+            RData::MG(_) => ResourceType::MG,
+            RData::MR(_) => ResourceType::MR,
+            RData::Null(_) => ResourceType::Null,
+            RData::WKS { .. } => ResourceType::WKS,
+            RData::PTR(_) => ResourceType::PTR,
+            RData::HInfo { .. } => ResourceType::HInfo,
+            RData::MInfo { .. } => ResourceType::MInfo,
+            RData::MX { .. } => ResourceType::MX,
+            RData::Txt(_) => ResourceType::Txt,
+            RData::AAAA(_) => ResourceType::AAAA,
+            RData::SRV { .. } => ResourceType::SRV,
+            RData::NAPTR { .. } => ResourceType::NAPTR,
+            RData::DName(_) => ResourceType::DName,
+            RData::OPT(_) => ResourceType::OPT,
+            RData::SSHFP { .. } => ResourceType::SSHFP,
+            RData::TLSA { .. } => ResourceType::TLSA,
+            RData::SVCB { .. } => ResourceType::SVCB,
+            RData::HTTPS { .. } => ResourceType::HTTPS,
+            RData::CAA { .. } => ResourceType::CAA,
+            RData::Unknown { r_type, .. } => *r_type,
+        }
+    }
+}
+
+impl RData {
+    /// Presentation format with the names at or below `origin` written relative to it
+    pub fn relative_to<'a>(&'a self, origin: &'a Name) -> RelativeRData<'a> {
+        RelativeRData {
+            r_data: self,
+            origin,
+        }
+    }
+
+    fn write(&self, f: &mut fmt::Formatter<'_>, origin: Option<&Name>) -> fmt::Result {
+        let unicode = f.alternate();
+        match self {
+            RData::A(address) => write!(f, "{}", address),
+            RData::NS(name)
+            | RData::MD(name)
+            | RData::MF(name)
+            | RData::CName(name)
+            | RData::MB(name)
+            | RData::MG(name)
+            | RData::MR(name)
+            | RData::PTR(name) => write_name(f, name, origin, unicode),
+            RData::SOA {
+                m_name,
+                r_name,
+                serial,
+                refresh,
+                retry,
+                expire,
+                minimum,
+            } => {
+                write_name(f, m_name, origin, unicode)?;
+                write!(f, " ")?;
+                write_name(f, r_name, origin, unicode)?;
+                write!(
+                    f,
+                    " {} {} {} {} {}",
+                    serial, refresh, retry, expire, minimum
+                )
+            }
+            RData::Null(data) => write_generic(f, data),
+            RData::WKS {
+                address,
+                protocol,
+                bit_map,
+            } => {
+                write!(f, "{} {}", address, protocol)?;
+                for (i, byte) in bit_map.iter().enumerate() {
+                    for bit in 0..8 {
+                        if byte & (0x80 >> bit) != 0 {
+                            write!(f, " {}", i * 8 + bit)?;
+                        }
+                    }
+                }
+                Ok(())
+            }
+            RData::HInfo { cpu, os } => {
+                write_character_string(f, cpu)?;
+                write!(f, " ")?;
+                write_character_string(f, os)
+            }
+            RData::MInfo {
+                r_mail_bx,
+                e_mail_bx,
+            } => {
+                write_name(f, r_mail_bx, origin, unicode)?;
+                write!(f, " ")?;
+                write_name(f, e_mail_bx, origin, unicode)
+            }
+            RData::MX {
+                preference,
+                exchange,
+            } => {
+                write!(f, "{} ", preference)?;
+                write_name(f, exchange, origin, unicode)
+            }
+            RData::Txt(strings) => {
+                let mut delimiter = "";
+                for string in strings {
+                    write!(f, "{}", delimiter)?;
+                    write_character_string(f, string)?;
+                    delimiter = " ";
+                }
+                Ok(())
+            }
+            RData::AAAA(address) => write!(f, "{}", address),
+            RData::SRV {
+                priority,
+                weight,
+                port,
+                target,
+            } => {
+                write!(f, "{} {} {} ", priority, weight, port)?;
+                write_name(f, target, origin, unicode)
+            }
+            RData::NAPTR {
+                order,
+                preference,
+                flags,
+                services,
+                regexp,
+                replacement,
+            } => {
+                write!(f, "{} {} ", order, preference)?;
+                write_character_string(f, flags)?;
+                write!(f, " ")?;
+                write_character_string(f, services)?;
+                write!(f, " ")?;
+                write_character_string(f, regexp)?;
+                write!(f, " ")?;
+                write_name(f, replacement, origin, unicode)
+            }
+            RData::DName(name) => write_name(f, name, origin, unicode),
+            RData::OPT(options) => {
+                let mut delimiter = "";
+                for option in options {
+                    write!(f, "{}{}", delimiter, option)?;
+                    delimiter = "; ";
+                }
+                Ok(())
+            }
+            RData::SSHFP {
+                algorithm,
+                fp_type,
+                fingerprint,
+            } => write!(f, "{} {} {}", algorithm, fp_type, hex_encode(fingerprint)),
+            RData::TLSA {
+                cert_usage,
+                selector,
+                matching_type,
+                cert_data,
+            } => write!(
+                f,
+                "{} {} {} {}",
+                cert_usage,
+                selector,
+                matching_type,
+                hex_encode(cert_data)
+            ),
+            RData::SVCB {
+                priority,
+                target,
+                params,
+            }
+            | RData::HTTPS {
+                priority,
+                target,
+                params,
+            } => {
+                write!(f, "{} ", priority)?;
+                write_name(f, target, origin, unicode)?;
+                for param in params {
+                    write!(f, " {}", param)?;
+                }
+                Ok(())
+            }
+            RData::CAA { flags, tag, value } => {
+                write!(f, "{} {} ", flags, String::from_utf8_lossy(tag))?;
+                write_character_string(f, value)
+            }
+            RData::Unknown { data, .. } => write_generic(f, data),
+        }
+    }
+}
+
+impl fmt::Display for RData {
+    /// The RDATA in master file presentation format, with `{:#}` names in Unicode
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.write(f, None)
+    }
+}
+
+/// RDATA displayed with names relative to an origin, see `RData::relative_to`
+pub struct RelativeRData<'a> {
+    r_data: &'a RData,
+    origin: &'a Name,
+}
+
+impl fmt::Display for RelativeRData<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.r_data.write(f, Some(self.origin))
+    }
+}
+
+/**
+    A name in presentation format: absolute with a trailing dot, or relative
+    to `origin` when it is at or below it, `@` being the origin itself.
+    With `unicode` its A-labels are written in Unicode.
+*/
+pub(crate) fn write_name<W: fmt::Write>(
+    f: &mut W,
+    name: &Name,
+    origin: Option<&Name>,
+    unicode: bool,
+) -> fmt::Result {
+    match origin {
+        Some(origin) if name == origin => write!(f, "@"),
+        Some(origin) if name.is_subdomain_of(origin) => {
+            name.write_labels(f, name.label_count() - origin.label_count(), unicode)
+        }
+        _ if unicode => write!(f, "{:#}", name),
+        _ => write!(f, "{}", name),
+    }
+}
+
+/// A quoted <character-string>, escaping quotes, backslashes and non-printable octets
+fn write_character_string(f: &mut fmt::Formatter<'_>, string: &[u8]) -> fmt::Result {
+    write!(f, "\"")?;
+    for &c in string {
+        match c {
+            b'"' | b'\\' => write!(f, "\\{}", c as char)?,
+            0x20..=0x7E => write!(f, "{}", c as char)?,
+            _ => write!(f, "\\{:03}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+/// The RFC 3597 generic encoding: `\# <length> <hex>`
+fn write_generic(f: &mut fmt::Formatter<'_>, data: &[u8]) -> fmt::Result {
+    write!(f, "\\# {}", data.len())?;
+    if !data.is_empty() {
+        write!(f, " {}", hex_encode(data))?;
+    }
+    Ok(())
+}
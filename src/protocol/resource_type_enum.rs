@@ -1,4 +1,6 @@
-#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+use std::fmt;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
 #[repr(u16)]
 pub enum ResourceType {
     /// A host address
@@ -33,28 +35,60 @@ pub enum ResourceType {
     MX = 15u16,
     /// Text strings
     Txt = 16u16,
+    /// A host IPv6 address (RFC 3596)
+    AAAA = 28u16,
+    /// The location of a service (RFC 2782)
+    SRV = 33u16,
+    /// A naming authority pointer (RFC 3403)
+    NAPTR = 35u16,
+    /// Redirection of a whole subtree of the domain name space (RFC 6672)
+    DName = 39u16,
+    /// The EDNS(0) pseudo-record (RFC 6891), never cached or stored in zones
+    OPT = 41u16,
+    /// A SSH key fingerprint (RFC 4255)
+    SSHFP = 44u16,
+    /// A TLS certificate association (RFC 6698)
+    TLSA = 52u16,
+    /// A general purpose service binding (RFC 9460)
+    SVCB = 64u16,
+    /// A service binding for HTTPS (RFC 9460)
+    HTTPS = 65u16,
+    /// A certification authority authorization (RFC 8659)
+    CAA = 257u16,
+    /// Any TYPE this implementation has no dedicated support for
+    Unknown(u16),
 }
 
-impl TryFrom<u16> for ResourceType {
-    type Error = &'static str;
-
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
+impl From<u16> for ResourceType {
+    fn from(value: u16) -> Self {
         match value {
-            x if x == ResourceType::A.into() => Ok(ResourceType::A),
-            x if x == ResourceType::NS.into() => Ok(ResourceType::NS),
-            x if x == ResourceType::MD.into() => Ok(ResourceType::MD),
-            x if x == ResourceType::MF.into() => Ok(ResourceType::MF),
-            x if x == ResourceType::CName.into() => Ok(ResourceType::CName),
-            x if x == ResourceType::SOA.into() => Ok(ResourceType::SOA),
-            x if x == ResourceType::MB.into() => Ok(ResourceType::MB),
-            x if x == ResourceType::Null.into() => Ok(ResourceType::Null),
-            x if x == ResourceType::WKS.into() => Ok(ResourceType::WKS),
-            x if x == ResourceType::PTR.into() => Ok(ResourceType::PTR),
-            x if x == ResourceType::HInfo.into() => Ok(ResourceType::HInfo),
-            x if x == ResourceType::MInfo.into() => Ok(ResourceType::MInfo),
-            x if x == ResourceType::MX.into() => Ok(ResourceType::MX),
-            x if x == ResourceType::Txt.into() => Ok(ResourceType::Txt),
-            _ => Err("ResourceType No Exist"),
+            x if x == ResourceType::A.into() => ResourceType::A,
+            x if x == ResourceType::NS.into() => ResourceType::NS,
+            x if x == ResourceType::MD.into() => ResourceType::MD,
+            x if x == ResourceType::MF.into() => ResourceType::MF,
+            x if x == ResourceType::CName.into() => ResourceType::CName,
+            x if x == ResourceType::SOA.into() => ResourceType::SOA,
+            x if x == ResourceType::MB.into() => ResourceType::MB,
+            x if x == ResourceType::MG.into() => ResourceType::MG,
+            x if x == ResourceType::MR.into() => ResourceType::MR,
+            x if x == ResourceType::Null.into() => ResourceType::Null,
+            x if x == ResourceType::WKS.into() => ResourceType::WKS,
+            x if x == ResourceType::PTR.into() => ResourceType::PTR,
+            x if x == ResourceType::HInfo.into() => ResourceType::HInfo,
+            x if x == ResourceType::MInfo.into() => ResourceType::MInfo,
+            x if x == ResourceType::MX.into() => ResourceType::MX,
+            x if x == ResourceType::Txt.into() => ResourceType::Txt,
+            x if x == ResourceType::AAAA.into() => ResourceType::AAAA,
+            x if x == ResourceType::SRV.into() => ResourceType::SRV,
+            x if x == ResourceType::NAPTR.into() => ResourceType::NAPTR,
+            x if x == ResourceType::DName.into() => ResourceType::DName,
+            x if x == ResourceType::OPT.into() => ResourceType::OPT,
+            x if x == ResourceType::SSHFP.into() => ResourceType::SSHFP,
+            x if x == ResourceType::TLSA.into() => ResourceType::TLSA,
+            x if x == ResourceType::SVCB.into() => ResourceType::SVCB,
+            x if x == ResourceType::HTTPS.into() => ResourceType::HTTPS,
+            x if x == ResourceType::CAA.into() => ResourceType::CAA,
+            x => ResourceType::Unknown(x),
         }
     }
 }
@@ -78,6 +112,95 @@ impl From<ResourceType> for u16 {
             ResourceType::MInfo => 14u16,
             ResourceType::MX => 15u16,
             ResourceType::Txt => 16u16,
+            ResourceType::AAAA => 28u16,
+            ResourceType::SRV => 33u16,
+            ResourceType::NAPTR => 35u16,
+            ResourceType::DName => 39u16,
+            ResourceType::OPT => 41u16,
+            ResourceType::SSHFP => 44u16,
+            ResourceType::TLSA => 52u16,
+            ResourceType::SVCB => 64u16,
+            ResourceType::HTTPS => 65u16,
+            ResourceType::CAA => 257u16,
+            ResourceType::Unknown(x) => x,
+        }
+    }
+}
+
+impl ResourceType {
+    /// The TYPE named by a mnemonic or the RFC 3597 TYPEnnn form, ignoring case
+    pub fn from_mnemonic(mnemonic: &str) -> Option<ResourceType> {
+        let mnemonic = mnemonic.to_ascii_uppercase();
+        let r_type = match mnemonic.as_str() {
+            "A" => ResourceType::A,
+            "NS" => ResourceType::NS,
+            "MD" => ResourceType::MD,
+            "MF" => ResourceType::MF,
+            "CNAME" => ResourceType::CName,
+            "SOA" => ResourceType::SOA,
+            "MB" => ResourceType::MB,
+            "MG" => ResourceType::MG,
+            "MR" => ResourceType::MR,
+            "NULL" => ResourceType::Null,
+            "WKS" => ResourceType::WKS,
+            "PTR" => ResourceType::PTR,
+            "HINFO" => ResourceType::HInfo,
+            "MINFO" => ResourceType::MInfo,
+            "MX" => ResourceType::MX,
+            "TXT" => ResourceType::Txt,
+            "AAAA" => ResourceType::AAAA,
+            "SRV" => ResourceType::SRV,
+            "NAPTR" => ResourceType::NAPTR,
+            "DNAME" => ResourceType::DName,
+            "OPT" => ResourceType::OPT,
+            "SSHFP" => ResourceType::SSHFP,
+            "TLSA" => ResourceType::TLSA,
+            "SVCB" => ResourceType::SVCB,
+            "HTTPS" => ResourceType::HTTPS,
+            "CAA" => ResourceType::CAA,
+            _ => {
+                return mnemonic
+                    .strip_prefix("TYPE")?
+                    .parse::<u16>()
+                    .ok()
+                    .map(Into::into)
+            }
+        };
+        Some(r_type)
+    }
+}
+
+impl fmt::Display for ResourceType {
+    /// The mnemonic of the TYPE, or the RFC 3597 generic TYPEnnn form
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ResourceType::A => write!(f, "A"),
+            ResourceType::NS => write!(f, "NS"),
+            ResourceType::MD => write!(f, "MD"),
+            ResourceType::MF => write!(f, "MF"),
+            ResourceType::CName => write!(f, "CNAME"),
+            ResourceType::SOA => write!(f, "SOA"),
+            ResourceType::MB => write!(f, "MB"),
+            ResourceType::MG => write!(f, "MG"),
+            ResourceType::MR => write!(f, "MR"),
+            ResourceType::Null => write!(f, "NULL"),
+            ResourceType::WKS => write!(f, "WKS"),
+            ResourceType::PTR => write!(f, "PTR"),
+            ResourceType::HInfo => write!(f, "HINFO"),
+            ResourceType::MInfo => write!(f, "MINFO"),
+            ResourceType::MX => write!(f, "MX"),
+            ResourceType::Txt => write!(f, "TXT"),
+            ResourceType::AAAA => write!(f, "AAAA"),
+            ResourceType::SRV => write!(f, "SRV"),
+            ResourceType::NAPTR => write!(f, "NAPTR"),
+            ResourceType::DName => write!(f, "DNAME"),
+            ResourceType::OPT => write!(f, "OPT"),
+            ResourceType::SSHFP => write!(f, "SSHFP"),
+            ResourceType::TLSA => write!(f, "TLSA"),
+            ResourceType::SVCB => write!(f, "SVCB"),
+            ResourceType::HTTPS => write!(f, "HTTPS"),
+            ResourceType::CAA => write!(f, "CAA"),
+            ResourceType::Unknown(x) => write!(f, "TYPE{}", x),
         }
     }
 }
@@ -0,0 +1,131 @@
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use crate::encoding::base64_encode;
+
+/**
+    A SvcParam of a SVCB or HTTPS record (RFC 9460 section 2.1), a key with
+    a value whose format depends on the key.
+
+    ```text
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                  SvcParamKey                  |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                 length of value               |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /                 SvcParamValue                 /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+*/
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SvcParam {
+    /// The keys that are mandatory for the service to function
+    Mandatory(Vec<u16>),
+    /// The ALPN protocol identifiers supported by the service
+    Alpn(Vec<Vec<u8>>),
+    /// The default ALPN protocol is not supported
+    NoDefaultAlpn,
+    /// The alternative TCP or UDP port
+    Port(u16),
+    Ipv4Hint(Vec<Ipv4Addr>),
+    /// An ECHConfigList (Encrypted ClientHello)
+    Ech(Vec<u8>),
+    Ipv6Hint(Vec<Ipv6Addr>),
+    /// Any key this implementation has no dedicated support for
+    Unknown {
+        key: u16,
+        value: Vec<u8>,
+    },
+}
+
+impl SvcParam {
+    pub const MANDATORY: u16 = 0;
+    pub const ALPN: u16 = 1;
+    pub const NO_DEFAULT_ALPN: u16 = 2;
+    pub const PORT: u16 = 3;
+    pub const IPV4_HINT: u16 = 4;
+    pub const ECH: u16 = 5;
+    pub const IPV6_HINT: u16 = 6;
+
+    /// The SvcParamKey of the parameter
+    pub fn key(&self) -> u16 {
+        match self {
+            SvcParam::Mandatory(_) => Self::MANDATORY,
+            SvcParam::Alpn(_) => Self::ALPN,
+            SvcParam::NoDefaultAlpn => Self::NO_DEFAULT_ALPN,
+            SvcParam::Port(_) => Self::PORT,
+            SvcParam::Ipv4Hint(_) => Self::IPV4_HINT,
+            SvcParam::Ech(_) => Self::ECH,
+            SvcParam::Ipv6Hint(_) => Self::IPV6_HINT,
+            SvcParam::Unknown { key, .. } => *key,
+        }
+    }
+
+    /// The presentation name of a SvcParamKey
+    pub fn key_name(key: u16) -> String {
+        match key {
+            Self::MANDATORY => "mandatory".to_string(),
+            Self::ALPN => "alpn".to_string(),
+            Self::NO_DEFAULT_ALPN => "no-default-alpn".to_string(),
+            Self::PORT => "port".to_string(),
+            Self::IPV4_HINT => "ipv4hint".to_string(),
+            Self::ECH => "ech".to_string(),
+            Self::IPV6_HINT => "ipv6hint".to_string(),
+            key => format!("key{}", key),
+        }
+    }
+}
+
+impl fmt::Display for SvcParam {
+    /// The key=value presentation format of RFC 9460 appendix A
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Self::key_name(self.key()))?;
+
+        match self {
+            SvcParam::Mandatory(keys) => {
+                let keys: Vec<String> = keys.iter().map(|key| Self::key_name(*key)).collect();
+                write!(f, "={}", keys.join(","))
+            }
+            SvcParam::Alpn(ids) => {
+                write!(f, "=\"")?;
+                let mut delimiter = "";
+                for id in ids {
+                    write!(f, "{}", delimiter)?;
+                    for &c in id {
+                        match c {
+                            // commas inside an id are escaped twice, once for the value list
+                            b',' => write!(f, "\\\\,")?,
+                            b'"' | b'\\' => write!(f, "\\{}", c as char)?,
+                            0x21..=0x7E => write!(f, "{}", c as char)?,
+                            _ => write!(f, "\\{:03}", c)?,
+                        }
+                    }
+                    delimiter = ",";
+                }
+                write!(f, "\"")
+            }
+            SvcParam::NoDefaultAlpn => Ok(()),
+            SvcParam::Port(port) => write!(f, "={}", port),
+            SvcParam::Ipv4Hint(addresses) => {
+                let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+                write!(f, "={}", addresses.join(","))
+            }
+            SvcParam::Ech(config) => write!(f, "={}", base64_encode(config)),
+            SvcParam::Ipv6Hint(addresses) => {
+                let addresses: Vec<String> = addresses.iter().map(|a| a.to_string()).collect();
+                write!(f, "={}", addresses.join(","))
+            }
+            SvcParam::Unknown { value, .. } => {
+                write!(f, "=\"")?;
+                for &c in value {
+                    match c {
+                        b'"' | b'\\' => write!(f, "\\{}", c as char)?,
+                        0x21..=0x7E => write!(f, "{}", c as char)?,
+                        _ => write!(f, "\\{:03}", c)?,
+                    }
+                }
+                write!(f, "\"")
+            }
+        }
+    }
+}
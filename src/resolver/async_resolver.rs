@@ -0,0 +1,507 @@
+use std::collections::HashMap;
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_builder::random_id;
+use crate::domain::dns_packet_ref::DnsPacketRef;
+use crate::domain::dns_packet_writer::MAX_MESSAGE_SIZE;
+use crate::error::ResolveError;
+use crate::protocol::dns_name::Name;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::transport::tcp_framing::{read_message_async, write_message_async};
+
+type Pending = Arc<Mutex<Queries>>;
+
+/// Queries waiting for a response, by the message ID they were sent with
+#[derive(Default)]
+struct Queries {
+    by_id: HashMap<u16, Outstanding>,
+    /// Why the receiving task stopped, after which no query can be answered
+    failure: Option<io::Error>,
+}
+
+impl Queries {
+    /// The error that stopped the receiving task, for each query to report
+    fn failure(&self) -> Option<io::Error> {
+        self.failure
+            .as_ref()
+            .map(|e| io::Error::new(e.kind(), e.to_string()))
+    }
+}
+
+struct Outstanding {
+    query: DnsPacket,
+    sender: oneshot::Sender<DnsPacket>,
+}
+
+/**
+    An asynchronous stub resolver that multiplexes any number of concurrent
+    queries over one UDP socket connected to an upstream name server.
+
+    Every query in flight is sent under a message ID of its own, chosen at
+    random, and a background task hands each datagram to the query whose ID
+    and questions it answers; anything else is dropped. The caller's ID is
+    put back on the response. Each of `attempts` waits up to `timeout`, and
+    a truncated response is retried over TCP.
+
+    The futures are cancellation safe: dropping one before it completes
+    forgets the query, and a late response to it is discarded. Cloning the
+    resolver shares the socket; the task stops with the last clone. Should
+    receiving fail, the queries in flight and every later one fail with
+    that error.
+*/
+#[derive(Clone)]
+pub struct AsyncResolver {
+    inner: Arc<Inner>,
+    timeout: Duration,
+    attempts: usize,
+    tcp_fallback: bool,
+}
+
+struct Inner {
+    socket: Arc<UdpSocket>,
+    pending: Pending,
+    task: JoinHandle<()>,
+}
+
+impl Drop for Inner {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+impl AsyncResolver {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const DEFAULT_ATTEMPTS: usize = 2;
+
+    /// Bind a socket for `upstream` and start receiving on it, within a Tokio runtime
+    pub async fn bind(upstream: SocketAddr) -> io::Result<AsyncResolver> {
+        let local: SocketAddr = if upstream.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = Arc::new(UdpSocket::bind(local).await?);
+        socket.connect(upstream).await?;
+
+        let pending = Pending::default();
+        let task = tokio::spawn(Self::receive(socket.clone(), pending.clone()));
+
+        Ok(AsyncResolver {
+            inner: Arc::new(Inner {
+                socket,
+                pending,
+                task,
+            }),
+            timeout: AsyncResolver::DEFAULT_TIMEOUT,
+            attempts: AsyncResolver::DEFAULT_ATTEMPTS,
+            tcp_fallback: true,
+        })
+    }
+
+    /// How long to wait for a response to each attempt
+    pub fn timeout(mut self, timeout: Duration) -> AsyncResolver {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many times a query is sent before giving up, at least once
+    pub fn attempts(mut self, attempts: usize) -> AsyncResolver {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Whether a truncated response is retried over TCP, on by default
+    pub fn tcp_fallback(mut self, value: bool) -> AsyncResolver {
+        self.tcp_fallback = value;
+        self
+    }
+
+    /// Ask the upstream for `q_type` records of `name` with recursion desired
+    pub async fn lookup(
+        &self,
+        name: &Name,
+        q_type: QuestionType,
+    ) -> Result<DnsPacket, ResolveError> {
+        let query = DnsPacket::query().question(name, q_type).build();
+        self.query(&query).await
+    }
+
+    /// Send `query` and wait for the response answering it
+    pub async fn query(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let mut sent = query.clone();
+        let mut receiver = self.register(&mut sent)?;
+        let _guard = Deregister {
+            pending: &self.inner.pending,
+            id: sent.header.id,
+        };
+        let bytes = sent.to_bytes()?;
+
+        for _ in 0..self.attempts {
+            self.inner.socket.send(&bytes).await?;
+
+            match tokio::time::timeout(self.timeout, &mut receiver).await {
+                Ok(Ok(response)) if response.header.truncated_message && self.tcp_fallback => {
+                    return self.query_tcp(query).await;
+                }
+                Ok(Ok(mut response)) => {
+                    response.header.id = query.header.id;
+                    return Ok(response);
+                }
+                // the receiving task has stopped, dropping every sender
+                Ok(Err(_)) => {
+                    let failure = self.inner.pending.lock().unwrap().failure();
+                    return Err(failure
+                        .unwrap_or_else(|| io::Error::other("receiving stopped"))
+                        .into());
+                }
+                Err(_) => {}
+            }
+        }
+
+        Err(ResolveError::Timeout {
+            attempts: self.attempts,
+        })
+    }
+
+    /**
+        Send `query` over a new TCP connection to the upstream, all of it
+        within the timeout of one attempt.
+    */
+    pub async fn query_tcp(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let exchange = async {
+            let mut stream = TcpStream::connect(self.inner.socket.peer_addr()?).await?;
+            stream.set_nodelay(true)?;
+            write_message_async(&mut stream, &query.to_bytes()?).await?;
+
+            loop {
+                let message = read_message_async(&mut stream)
+                    .await?
+                    .ok_or_else(|| io::Error::from(io::ErrorKind::UnexpectedEof))?;
+                let response = DnsPacket::from_bytes(&message)?;
+                if response.is_response_to(query) {
+                    return Ok(response);
+                }
+            }
+        };
+
+        tokio::time::timeout(self.timeout, exchange)
+            .await
+            .unwrap_or(Err(ResolveError::Timeout { attempts: 1 }))
+    }
+
+    /// Give `query` an ID no other query in flight has and record it as outstanding
+    fn register(
+        &self,
+        query: &mut DnsPacket,
+    ) -> Result<oneshot::Receiver<DnsPacket>, ResolveError> {
+        let mut pending = self.inner.pending.lock().unwrap();
+        if let Some(failure) = pending.failure() {
+            return Err(failure.into());
+        }
+        if pending.by_id.len() > u16::MAX as usize {
+            return Err(io::Error::other("all message IDs are in use").into());
+        }
+
+        query.header.id = std::iter::repeat_with(random_id)
+            .find(|id| !pending.by_id.contains_key(id))
+            .unwrap();
+
+        let (sender, receiver) = oneshot::channel();
+        let outstanding = Outstanding {
+            query: query.clone(),
+            sender,
+        };
+        pending.by_id.insert(query.header.id, outstanding);
+
+        Ok(receiver)
+    }
+
+    /// Hand every datagram that answers an outstanding query to its waiter
+    async fn receive(socket: Arc<UdpSocket>, pending: Pending) {
+        let mut buf = vec![0; MAX_MESSAGE_SIZE];
+        loop {
+            let len = match socket.recv(&mut buf).await {
+                Ok(len) => len,
+                // an ICMP error for an earlier datagram; the query will time out
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => return Self::fail(&pending, e),
+            };
+            let bytes = &buf[..len];
+
+            let id = match DnsPacketRef::new(bytes) {
+                Ok(view) => view.id(),
+                Err(_) => continue,
+            };
+            if !pending.lock().unwrap().by_id.contains_key(&id) {
+                continue;
+            }
+            let response = match DnsPacket::from_bytes(bytes) {
+                Ok(response) => response,
+                Err(_) => continue,
+            };
+
+            let mut pending = pending.lock().unwrap();
+            let answers = pending
+                .by_id
+                .get(&id)
+                .is_some_and(|outstanding| response.is_response_to(&outstanding.query));
+            if answers {
+                let outstanding = pending.by_id.remove(&id).unwrap();
+                let _ = outstanding.sender.send(response);
+            }
+        }
+    }
+
+    /// Keep the error that stopped receiving, and wake every waiting query by dropping its sender
+    fn fail(pending: &Pending, error: io::Error) {
+        let mut pending = pending.lock().unwrap();
+        pending.failure = Some(error);
+        pending.by_id.clear();
+    }
+}
+
+/// Forgets an outstanding query when its future completes or is dropped
+struct Deregister<'a> {
+    pending: &'a Pending,
+    id: u16,
+}
+
+impl Drop for Deregister<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut pending) = self.pending.lock() {
+            pending.by_id.remove(&self.id);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "tokio"))]
+mod tests {
+    use std::future::Future;
+    use std::net::{TcpListener, UdpSocket as StdUdpSocket};
+    use std::thread;
+
+    use super::*;
+    use crate::protocol::dns_resource_record::DnsRecord;
+    use crate::protocol::resource_data_enum::RData;
+    use crate::protocol::resource_type_enum::ResourceType;
+    use crate::transport::tcp_framing::TcpConnection;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn block_on<F: Future>(future: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(future)
+    }
+
+    fn a_query(owner: &str) -> DnsPacket {
+        DnsPacket::query()
+            .question(&name(owner), QuestionType::Base(ResourceType::A))
+            .build()
+    }
+
+    fn answer(query: &DnsPacket, octet: u8) -> DnsPacket {
+        let record = DnsRecord::with_r_data(
+            query.questions[0].name.clone(),
+            300,
+            RData::A([192, 0, 2, octet].into()),
+        );
+        DnsPacket::response_to(query).answer(record).build()
+    }
+
+    /**
+        A fake upstream on a UDP socket of its own, handing every `batch`
+        datagrams it receives to `respond` with the socket to answer on,
+        until `respond` returns false.
+    */
+    fn fake_udp<F>(batch: usize, mut respond: F) -> SocketAddr
+    where
+        F: FnMut(&StdUdpSocket, SocketAddr, Vec<DnsPacket>) -> bool + Send + 'static,
+    {
+        let socket = StdUdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_MESSAGE_SIZE];
+            loop {
+                let mut queries = Vec::new();
+                let mut peer = addr;
+                while queries.len() < batch {
+                    let (len, from) = socket.recv_from(&mut buf).unwrap();
+                    queries.push(DnsPacket::from_bytes(&buf[..len]).unwrap());
+                    peer = from;
+                }
+                if !respond(&socket, peer, queries) {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    fn send(socket: &StdUdpSocket, peer: SocketAddr, packet: &DnsPacket) {
+        socket.send_to(&packet.to_bytes().unwrap(), peer).unwrap();
+    }
+
+    fn octet(response: &DnsPacket) -> u8 {
+        match response.answers[0].r_data {
+            RData::A(address) => address.octets()[3],
+            ref other => panic!("expected an address, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn matches_responses_arriving_out_of_order() {
+        let upstream = fake_udp(2, |socket, peer, queries| {
+            for query in queries.iter().rev() {
+                let octet = if query.questions[0].name == name("one.example") {
+                    1
+                } else {
+                    2
+                };
+                send(socket, peer, &answer(query, octet));
+            }
+            false
+        });
+
+        let responses = block_on(async {
+            let resolver = AsyncResolver::bind(upstream).await.unwrap();
+            let one = tokio::spawn({
+                let resolver = resolver.clone();
+                async move { resolver.query(&a_query("one.example")).await }
+            });
+            let two = resolver.query(&a_query("two.example")).await;
+            (one.await.unwrap(), two)
+        });
+
+        assert_eq!(octet(&responses.0.unwrap()), 1);
+        assert_eq!(octet(&responses.1.unwrap()), 2);
+    }
+
+    #[test]
+    fn drops_responses_with_another_id_or_question() {
+        let upstream = fake_udp(1, |socket, peer, queries| {
+            let query = &queries[0];
+            let mut other_id = answer(query, 65);
+            other_id.header.id = query.header.id.wrapping_add(1);
+            send(socket, peer, &other_id);
+
+            let mut spoofed = answer(query, 66);
+            spoofed.questions[0].name = name("elsewhere.example");
+            send(socket, peer, &spoofed);
+            send(socket, peer, &answer(query, 1));
+            false
+        });
+
+        let response = block_on(async {
+            let resolver = AsyncResolver::bind(upstream).await.unwrap();
+            resolver.query(&a_query("www.example")).await
+        });
+        assert_eq!(octet(&response.unwrap()), 1);
+    }
+
+    #[test]
+    fn puts_back_the_callers_id() {
+        let upstream = fake_udp(1, |socket, peer, queries| {
+            send(socket, peer, &answer(&queries[0], 1));
+            false
+        });
+
+        let mut query = a_query("www.example");
+        query.header.id = 0x4242;
+        let response = block_on(async {
+            let resolver = AsyncResolver::bind(upstream).await.unwrap();
+            resolver.query(&query).await
+        });
+        assert_eq!(response.unwrap().header.id, 0x4242);
+    }
+
+    #[test]
+    fn forgets_dropped_queries() {
+        let upstream = fake_udp(1, |_, _, _| true);
+
+        block_on(async {
+            let resolver = AsyncResolver::bind(upstream).await.unwrap();
+            let query = a_query("www.example");
+            let expired =
+                tokio::time::timeout(Duration::from_millis(50), resolver.query(&query)).await;
+
+            assert!(expired.is_err());
+            assert!(resolver.inner.pending.lock().unwrap().by_id.is_empty());
+        });
+    }
+
+    #[test]
+    fn reports_why_receiving_stopped() {
+        let upstream = fake_udp(1, |_, _, _| true);
+
+        block_on(async {
+            let resolver = AsyncResolver::bind(upstream)
+                .await
+                .unwrap()
+                .timeout(Duration::from_secs(30));
+            let waiting = tokio::spawn({
+                let resolver = resolver.clone();
+                async move { resolver.query(&a_query("www.example")).await }
+            });
+            while resolver.inner.pending.lock().unwrap().by_id.is_empty() {
+                tokio::task::yield_now().await;
+            }
+
+            let error = io::Error::new(io::ErrorKind::PermissionDenied, "no more receiving");
+            AsyncResolver::fail(&resolver.inner.pending, error);
+
+            for result in [
+                waiting.await.unwrap(),
+                resolver.query(&a_query("www.example")).await,
+            ] {
+                match result {
+                    Err(ResolveError::Io(e)) => {
+                        assert_eq!(e.kind(), io::ErrorKind::PermissionDenied)
+                    }
+                    other => panic!("expected the receive error, got {:?}", other),
+                }
+            }
+        });
+    }
+
+    #[test]
+    fn falls_back_to_tcp_when_truncated() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let socket = StdUdpSocket::bind(addr).unwrap();
+
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_MESSAGE_SIZE];
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+            let truncated = DnsPacket::response_to(&query).truncated(true).build();
+            send(&socket, peer, &truncated);
+        });
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut connection = TcpConnection::new(stream);
+            let query = connection.read_packet().unwrap().unwrap();
+            connection.write_packet(&answer(&query, 1)).unwrap();
+        });
+
+        let response = block_on(async {
+            let resolver = AsyncResolver::bind(addr).await.unwrap();
+            resolver.query(&a_query("www.example")).await
+        })
+        .unwrap();
+        assert!(!response.header.truncated_message);
+        assert_eq!(octet(&response), 1);
+    }
+}
@@ -0,0 +1,550 @@
+use std::collections::{BTreeMap, HashMap};
+use std::time::{Duration, Instant};
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::rcode_enum::RCode;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+use crate::server::authority::MAX_CNAME_CHAIN;
+
+/// What the cache knows about a name and type, with the TTLs left
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Cached {
+    /// The RRset of the name and type
+    Records(Vec<DnsRecord>),
+    /// The name exists without records of the type, as the SOA of its zone says
+    NoData(DnsRecord),
+    /// The name does not exist, as the SOA of its zone says
+    NxDomain(DnsRecord),
+}
+
+/**
+    How far data may be trusted, by the section it arrived in (a simplified
+    form of the ranking of RFC 2181 section 5.4.1). Cached data is not
+    replaced by less trustworthy data until it expires.
+*/
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Trust {
+    Additional,
+    Authority,
+    Answer,
+}
+
+/// An RRset or negative answer, `None` as the type standing for every type of the name
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct Key {
+    name: Name,
+    r_type: Option<ResourceType>,
+    r_class: ResourceClass,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    cached: Cached,
+    expires: Instant,
+    trust: Trust,
+    /// When the entry was last used, as a position in the LRU order
+    used: u64,
+}
+
+/**
+    A cache of RRsets and negative answers for resolvers, keyed by name,
+    type and class.
+
+    TTLs count down while data is cached, so what is read back carries
+    the time it may still be used; expired entries are dropped when read.
+    TTLs are clamped to `min_ttl` and `max_ttl` when cached. Negative
+    answers are cached only with the SOA of their zone, for the lower of
+    its TTL and MINIMUM field (RFC 2308 section 5), at most
+    `max_negative_ttl`; NXDOMAIN covers every type of the name.
+
+    Responses are cached with `insert_response`, which keeps only records
+    at or below the zone of the server that sent them, so a server cannot
+    plant records for names it has no authority over. When the cache is
+    full, the least recently used entry makes way.
+*/
+#[derive(Debug, Clone)]
+pub struct Cache {
+    entries: HashMap<Key, Entry>,
+    /// The keys of the entries by their `used` position, least recently used first
+    order: BTreeMap<u64, Key>,
+    clock: u64,
+    capacity: usize,
+    min_ttl: u32,
+    max_ttl: u32,
+    max_negative_ttl: u32,
+}
+
+impl Cache {
+    pub const DEFAULT_CAPACITY: usize = 10_000;
+    pub const DEFAULT_MAX_TTL: u32 = 86_400;
+    /// Three hours, the upper end of what RFC 2308 section 5 suggests
+    pub const DEFAULT_MAX_NEGATIVE_TTL: u32 = 10_800;
+
+    pub fn new() -> Cache {
+        Cache {
+            entries: HashMap::new(),
+            order: BTreeMap::new(),
+            clock: 0,
+            capacity: Cache::DEFAULT_CAPACITY,
+            min_ttl: 0,
+            max_ttl: Cache::DEFAULT_MAX_TTL,
+            max_negative_ttl: Cache::DEFAULT_MAX_NEGATIVE_TTL,
+        }
+    }
+
+    /// How many RRsets and negative answers are kept, at least one
+    pub fn capacity(mut self, capacity: usize) -> Cache {
+        self.capacity = capacity.max(1);
+        self
+    }
+
+    /// The shortest time anything is cached for
+    pub fn min_ttl(mut self, ttl: u32) -> Cache {
+        self.min_ttl = ttl;
+        self
+    }
+
+    /// The longest time records are cached for
+    pub fn max_ttl(mut self, ttl: u32) -> Cache {
+        self.max_ttl = ttl;
+        self
+    }
+
+    /// The longest time negative answers are cached for
+    pub fn max_negative_ttl(mut self, ttl: u32) -> Cache {
+        self.max_negative_ttl = ttl;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+
+    /// What is known about `r_type` records of `name`, unless it has expired
+    pub fn get(
+        &mut self,
+        name: &Name,
+        r_type: ResourceType,
+        r_class: ResourceClass,
+    ) -> Option<Cached> {
+        let now = Instant::now();
+
+        for r_type in [None, Some(r_type)] {
+            let key = Key {
+                name: name.clone(),
+                r_type,
+                r_class,
+            };
+            if let Some(cached) = self.read(&key, now) {
+                return Some(cached);
+            }
+        }
+
+        None
+    }
+
+    /**
+        Cache `records` as RRsets, each with the lowest TTL of its records
+        (RFC 2181 section 5.2). OPT pseudo-records are skipped.
+    */
+    pub fn insert(&mut self, records: &[DnsRecord], trust: Trust) {
+        let mut rrsets: Vec<(Key, Vec<DnsRecord>)> = Vec::new();
+        for record in records {
+            if record.r_type == ResourceType::OPT {
+                continue;
+            }
+
+            let key = Key {
+                name: record.name.clone(),
+                r_type: Some(record.r_type),
+                r_class: record.r_class,
+            };
+            match rrsets.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, rrset)) if rrset.contains(record) => {}
+                Some((_, rrset)) => rrset.push(record.clone()),
+                None => rrsets.push((key, vec![record.clone()])),
+            }
+        }
+
+        for (key, rrset) in rrsets {
+            let ttl = rrset.iter().map(|record| record.ttl).min().unwrap_or(0);
+            let ttl = ttl.clamp(self.min_ttl, self.max_ttl.max(self.min_ttl));
+            if self.store(key.clone(), Cached::Records(rrset), ttl, trust) {
+                // the name exists after all
+                self.remove(&Key {
+                    r_type: None,
+                    ..key
+                });
+            }
+        }
+    }
+
+    /**
+        Cache that `name` has no `r_type` records, or with `None` that it
+        does not exist at all, on the word of the SOA of its zone.
+    */
+    pub fn insert_negative(&mut self, name: &Name, r_type: Option<ResourceType>, soa: &DnsRecord) {
+        let minimum = match soa.r_data {
+            RData::SOA { minimum, .. } => minimum,
+            _ => return,
+        };
+        let ttl = soa.ttl.min(minimum).min(self.max_negative_ttl);
+        let ttl = ttl.max(self.min_ttl);
+
+        let key = Key {
+            name: name.clone(),
+            r_type,
+            r_class: soa.r_class,
+        };
+        let cached = match r_type {
+            Some(_) => Cached::NoData(soa.clone()),
+            None => Cached::NxDomain(soa.clone()),
+        };
+        self.store(key, cached, ttl, Trust::Answer);
+    }
+
+    /**
+        Cache what a response from a server for the zone `bailiwick` says:
+        its records at or below the zone, and a negative answer to its
+        question when the SOA of the zone comes with one.
+    */
+    pub fn insert_response(&mut self, response: &DnsPacket, bailiwick: &Name) {
+        let in_bailiwick = |records: &[DnsRecord]| -> Vec<DnsRecord> {
+            records
+                .iter()
+                .filter(|record| record.name.is_subdomain_of(bailiwick))
+                .cloned()
+                .collect()
+        };
+
+        let answers = in_bailiwick(&response.answers);
+        let authorities = in_bailiwick(&response.authorities);
+        self.insert(&answers, Trust::Answer);
+        self.insert(&authorities, Trust::Authority);
+        self.insert(&in_bailiwick(&response.resources), Trust::Additional);
+
+        let question = match response.questions.as_slice() {
+            [question] => question,
+            _ => return,
+        };
+        let r_type = match question.q_type {
+            QuestionType::Base(r_type) => r_type,
+            _ => return,
+        };
+        // the name a DNAME leads to cannot be told from the CNAMEs alone
+        let dname = answers
+            .iter()
+            .any(|record| record.r_type == ResourceType::DName);
+        let soa = match authorities
+            .iter()
+            .find(|record| record.r_type == ResourceType::SOA)
+        {
+            Some(soa) if !dname => soa,
+            _ => return,
+        };
+
+        // the negative answer is about the name at the end of the CNAME chain (RFC 2308 section 2.1)
+        let mut name = question.name.clone();
+        for _ in 0..MAX_CNAME_CHAIN {
+            let owned = |record: &&DnsRecord| record.name == name;
+            if answers
+                .iter()
+                .filter(owned)
+                .any(|record| record.r_type == r_type)
+            {
+                return;
+            }
+            match answers
+                .iter()
+                .filter(owned)
+                .find_map(|record| match &record.r_data {
+                    RData::CName(target) => Some(target.clone()),
+                    _ => None,
+                }) {
+                Some(target) => name = target,
+                None => break,
+            }
+        }
+
+        match response.header.r_code {
+            RCode::NameErr => self.insert_negative(&name, None, soa),
+            RCode::NoError => self.insert_negative(&name, Some(r_type), soa),
+            _ => {}
+        }
+    }
+
+    /// The entry under `key` with its TTLs counted down, moved to the back of the LRU order
+    fn read(&mut self, key: &Key, now: Instant) -> Option<Cached> {
+        let entry = self.entries.get(key)?;
+        let remaining = entry.expires.saturating_duration_since(now);
+        if remaining.is_zero() {
+            self.remove(key);
+            return None;
+        }
+        let ttl = remaining.as_secs() as u32;
+
+        self.clock += 1;
+        let entry = self.entries.get_mut(key)?;
+        self.order.remove(&entry.used);
+        entry.used = self.clock;
+        self.order.insert(entry.used, key.clone());
+
+        let with_ttl = |record: &DnsRecord| DnsRecord {
+            ttl,
+            ..record.clone()
+        };
+        Some(match &entry.cached {
+            Cached::Records(records) => Cached::Records(records.iter().map(with_ttl).collect()),
+            Cached::NoData(soa) => Cached::NoData(with_ttl(soa)),
+            Cached::NxDomain(soa) => Cached::NxDomain(with_ttl(soa)),
+        })
+    }
+
+    /**
+        Put an entry in the cache unless an unexpired one under the same key
+        is more trustworthy, evicting the least recently used entry to make
+        room. Whether the entry was stored.
+    */
+    fn store(&mut self, key: Key, cached: Cached, ttl: u32, trust: Trust) -> bool {
+        let now = Instant::now();
+        if let Some(existing) = self.entries.get(&key) {
+            if existing.trust > trust && existing.expires > now {
+                return false;
+            }
+            self.remove(&key);
+        }
+        if ttl == 0 {
+            return false;
+        }
+
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+
+        self.clock += 1;
+        self.order.insert(self.clock, key.clone());
+        self.entries.insert(
+            key,
+            Entry {
+                cached,
+                expires: now + Duration::from_secs(ttl as u64),
+                trust,
+                used: self.clock,
+            },
+        );
+        true
+    }
+
+    fn remove(&mut self, key: &Key) {
+        if let Some(entry) = self.entries.remove(key) {
+            self.order.remove(&entry.used);
+        }
+    }
+}
+
+impl Default for Cache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+    use std::thread;
+
+    use super::*;
+    use crate::protocol::dns_question::DnsQuestion;
+    use crate::protocol::question_class_enum::QuestionClass;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn a(owner: &str, ttl: u32, last_octet: u8) -> DnsRecord {
+        DnsRecord::with_r_data(
+            name(owner),
+            ttl,
+            RData::A(Ipv4Addr::new(192, 0, 2, last_octet)),
+        )
+    }
+
+    fn soa(zone: &str) -> DnsRecord {
+        let soa = RData::SOA {
+            m_name: name("ns.example.com"),
+            r_name: name("hostmaster.example.com"),
+            serial: 1,
+            refresh: 3600,
+            retry: 600,
+            expire: 86400,
+            minimum: 60,
+        };
+        DnsRecord::with_r_data(name(zone), 3600, soa)
+    }
+
+    fn get(cache: &mut Cache, owner: &str, r_type: ResourceType) -> Option<Cached> {
+        cache.get(&name(owner), r_type, ResourceClass::IN)
+    }
+
+    /// The TTLs of the cached A records of `owner`
+    fn ttls(cache: &mut Cache, owner: &str) -> Vec<u32> {
+        match get(cache, owner, ResourceType::A) {
+            Some(Cached::Records(records)) => records.iter().map(|record| record.ttl).collect(),
+            other => panic!("expected records, got {:?}", other),
+        }
+    }
+
+    fn response(owner: &str, r_type: ResourceType, r_code: RCode) -> DnsPacket {
+        let mut response = DnsPacket::new();
+        response.header.response = true;
+        response.header.r_code = r_code;
+        response.questions.push(DnsQuestion::new(
+            name(owner),
+            QuestionType::Base(r_type),
+            QuestionClass::Base(ResourceClass::IN),
+        ));
+        response
+    }
+
+    #[test]
+    fn counts_ttls_down_until_expiry() {
+        let mut cache = Cache::new();
+        cache.insert(
+            &[a("www.example.com", 300, 1), a("www.example.com", 100, 2)],
+            Trust::Answer,
+        );
+        cache.insert(&[a("short.example.com", 1, 1)], Trust::Answer);
+        cache.insert(&[a("zero.example.com", 0, 1)], Trust::Answer);
+
+        // an RRset lives as long as its shortest TTL
+        let ttls = ttls(&mut cache, "www.example.com");
+        assert_eq!(ttls.len(), 2);
+        assert!(
+            ttls.iter().all(|ttl| (99..=100).contains(ttl)),
+            "{:?}",
+            ttls
+        );
+        assert_eq!(get(&mut cache, "zero.example.com", ResourceType::A), None);
+
+        thread::sleep(Duration::from_millis(1100));
+        assert_eq!(get(&mut cache, "short.example.com", ResourceType::A), None);
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn clamps_ttls() {
+        let mut cache = Cache::new().min_ttl(60).max_ttl(120);
+        cache.insert(
+            &[
+                a("low.example.com", 5, 1),
+                a("high.example.com", 100_000, 1),
+            ],
+            Trust::Answer,
+        );
+
+        assert!((59..=60).contains(&ttls(&mut cache, "low.example.com")[0]));
+        assert!((119..=120).contains(&ttls(&mut cache, "high.example.com")[0]));
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used() {
+        let mut cache = Cache::new().capacity(2);
+        cache.insert(&[a("a.example.com", 300, 1)], Trust::Answer);
+        cache.insert(&[a("b.example.com", 300, 2)], Trust::Answer);
+        get(&mut cache, "a.example.com", ResourceType::A);
+        cache.insert(&[a("c.example.com", 300, 3)], Trust::Answer);
+
+        assert_eq!(cache.len(), 2);
+        assert!(get(&mut cache, "a.example.com", ResourceType::A).is_some());
+        assert!(get(&mut cache, "b.example.com", ResourceType::A).is_none());
+        assert!(get(&mut cache, "c.example.com", ResourceType::A).is_some());
+    }
+
+    #[test]
+    fn keeps_more_trustworthy_data() {
+        let mut cache = Cache::new();
+        cache.insert(&[a("www.example.com", 300, 1)], Trust::Answer);
+        cache.insert(&[a("www.example.com", 300, 2)], Trust::Additional);
+
+        match get(&mut cache, "www.example.com", ResourceType::A) {
+            Some(Cached::Records(records)) => {
+                assert_eq!(records.len(), 1);
+                assert_eq!(records[0].r_data, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+            }
+            other => panic!("expected records, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn keeps_only_records_in_bailiwick() {
+        let mut response = response("www.example.com", ResourceType::A, RCode::NoError);
+        response.answers.push(a("www.example.com", 300, 1));
+        response.authorities.push(DnsRecord::with_r_data(
+            name("com"),
+            300,
+            RData::NS(name("ns.evil.net")),
+        ));
+        response.resources.push(a("ns.evil.net", 300, 66));
+
+        let mut cache = Cache::new();
+        cache.insert_response(&response, &name("example.com"));
+
+        assert!(get(&mut cache, "www.example.com", ResourceType::A).is_some());
+        assert!(get(&mut cache, "com", ResourceType::NS).is_none());
+        assert!(get(&mut cache, "ns.evil.net", ResourceType::A).is_none());
+        assert_eq!(cache.len(), 1);
+    }
+
+    #[test]
+    fn caches_negative_answers() {
+        let mut nxdomain = response("missing.example.com", ResourceType::A, RCode::NameErr);
+        nxdomain.authorities.push(soa("example.com"));
+        // NODATA for the name the alias leads to
+        let mut nodata = response("alias.example.com", ResourceType::MX, RCode::NoError);
+        nodata.answers.push(DnsRecord::with_r_data(
+            name("alias.example.com"),
+            300,
+            RData::CName(name("www.example.com")),
+        ));
+        nodata.authorities.push(soa("example.com"));
+
+        let mut cache = Cache::new();
+        cache.insert_response(&nxdomain, &name("example.com"));
+        cache.insert_response(&nodata, &name("example.com"));
+
+        // NXDOMAIN covers every type, for the MINIMUM of the SOA
+        match get(&mut cache, "missing.example.com", ResourceType::Txt) {
+            Some(Cached::NxDomain(soa)) => assert!(soa.ttl <= 60),
+            other => panic!("expected NXDOMAIN, got {:?}", other),
+        }
+        assert!(matches!(
+            get(&mut cache, "www.example.com", ResourceType::MX),
+            Some(Cached::NoData(_))
+        ));
+        assert!(get(&mut cache, "www.example.com", ResourceType::A).is_none());
+
+        // the name exists after all
+        cache.insert(&[a("missing.example.com", 300, 1)], Trust::Answer);
+        assert!(matches!(
+            get(&mut cache, "missing.example.com", ResourceType::A),
+            Some(Cached::Records(_))
+        ));
+    }
+}
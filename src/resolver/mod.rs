@@ -0,0 +1,5 @@
+#[cfg(feature = "tokio")]
+pub mod async_resolver;
+pub mod cache;
+pub mod recursive_resolver;
+pub mod stub_resolver;
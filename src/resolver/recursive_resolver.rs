@@ -0,0 +1,977 @@
+use std::collections::HashSet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::error::ResolveError;
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::rcode_enum::RCode;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+use crate::resolver::cache::{Cache, Cached};
+use crate::resolver::stub_resolver::StubResolver;
+use crate::server::authority::MAX_CNAME_CHAIN;
+use crate::server::dns_server::RequestHandler;
+use crate::server::zone::type_matches;
+
+/// The IPv4 addresses of the root name servers, from IANA's root hints file
+pub const ROOT_HINTS: [(&str, Ipv4Addr); 13] = [
+    ("a.root-servers.net", Ipv4Addr::new(198, 41, 0, 4)),
+    ("b.root-servers.net", Ipv4Addr::new(170, 247, 170, 2)),
+    ("c.root-servers.net", Ipv4Addr::new(192, 33, 4, 12)),
+    ("d.root-servers.net", Ipv4Addr::new(199, 7, 91, 13)),
+    ("e.root-servers.net", Ipv4Addr::new(192, 203, 230, 10)),
+    ("f.root-servers.net", Ipv4Addr::new(192, 5, 5, 241)),
+    ("g.root-servers.net", Ipv4Addr::new(192, 112, 36, 4)),
+    ("h.root-servers.net", Ipv4Addr::new(198, 97, 190, 53)),
+    ("i.root-servers.net", Ipv4Addr::new(192, 36, 148, 17)),
+    ("j.root-servers.net", Ipv4Addr::new(192, 58, 128, 30)),
+    ("k.root-servers.net", Ipv4Addr::new(193, 0, 14, 129)),
+    ("l.root-servers.net", Ipv4Addr::new(199, 7, 83, 42)),
+    ("m.root-servers.net", Ipv4Addr::new(202, 12, 27, 33)),
+];
+
+/// The TYPE of DS records (RFC 4034), which have no `ResourceType` of their own
+const DS: u16 = 43;
+
+/// The payload size advertised to name servers, small enough to avoid IP fragmentation
+const EDNS_PAYLOAD_SIZE: u16 = 1232;
+
+/**
+    A blocking recursive resolver, which finds answers itself by asking
+    the authoritative name servers, starting from the root (RFC 1034
+    section 5.3.3).
+
+    Each query walks down from the root hints, following referrals to the
+    name servers of ever closer zones. Glue is taken only from the servers
+    of the zone it lies in; name servers without usable glue have their
+    addresses resolved in turn. Records outside the zone of the server
+    that sent them are ignored. CNAME chains are chased and DNAME records
+    applied (RFC 6672) until the answer or a negative response is found.
+
+    With QNAME minimisation (RFC 9156), on by default, the servers of a
+    zone are asked only about the name one label below it, so they learn
+    no more of the query than they need to refer it. Every query may send
+    at most `max_queries` messages, name server lookups included, and each
+    lookup of a name server address counts as one of them even when it is
+    answered from the cache. A name server is not looked up again while its
+    own lookup is under way, which breaks cycles of glueless delegations.
+    When none of the servers of a zone can be reached, iteration falls back
+    once to the servers of the zone above it.
+
+    Every response is cached, within the bailiwick of the server that sent
+    it. Answers, aliases and negative answers are then taken from the
+    cache, and iteration starts at the closest zone whose name servers are
+    cached. Clones of a resolver share its cache.
+*/
+#[derive(Clone, Debug)]
+pub struct RecursiveResolver {
+    root_hints: Vec<(Name, IpAddr)>,
+    port: u16,
+    timeout: Duration,
+    max_queries: usize,
+    qname_minimisation: bool,
+    cache: Arc<Mutex<Cache>>,
+}
+
+/// The name servers of a zone, with their addresses once they are known
+struct Delegation {
+    zone: Name,
+    servers: Vec<(Name, Option<Vec<IpAddr>>)>,
+}
+
+/// What is left for one query to do: the messages it may still send, and the name servers being looked up
+struct Work {
+    queries: usize,
+    resolving: HashSet<Name>,
+}
+
+/// What the records of a response led to for one name
+struct Resolution {
+    answers: Vec<DnsRecord>,
+    authorities: Vec<DnsRecord>,
+    r_code: RCode,
+}
+
+impl RecursiveResolver {
+    pub const DEFAULT_PORT: u16 = 53;
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+    pub const DEFAULT_MAX_QUERIES: usize = 64;
+
+    pub fn new() -> RecursiveResolver {
+        RecursiveResolver {
+            root_hints: ROOT_HINTS
+                .iter()
+                .map(|&(name, address)| {
+                    let name = Name::from_str(name).expect("the root hints are valid names");
+                    (name, address.into())
+                })
+                .collect(),
+            port: RecursiveResolver::DEFAULT_PORT,
+            timeout: RecursiveResolver::DEFAULT_TIMEOUT,
+            max_queries: RecursiveResolver::DEFAULT_MAX_QUERIES,
+            qname_minimisation: true,
+            cache: Arc::new(Mutex::new(Cache::new())),
+        }
+    }
+
+    /// The root name servers to start from, in place of the built-in `ROOT_HINTS`
+    pub fn root_hints(mut self, hints: Vec<(Name, IpAddr)>) -> RecursiveResolver {
+        self.root_hints = hints;
+        self
+    }
+
+    /// The port every name server is asked on
+    pub fn port(mut self, port: u16) -> RecursiveResolver {
+        self.port = port;
+        self
+    }
+
+    /// How long to wait for each name server before trying the next
+    pub fn timeout(mut self, timeout: Duration) -> RecursiveResolver {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many messages one query may send in all, at least one
+    pub fn max_queries(mut self, max_queries: usize) -> RecursiveResolver {
+        self.max_queries = max_queries.max(1);
+        self
+    }
+
+    /// Whether name servers are asked about no more of the name than needed
+    pub fn qname_minimisation(mut self, value: bool) -> RecursiveResolver {
+        self.qname_minimisation = value;
+        self
+    }
+
+    /// The cache to use in place of a default one, e.g. with other TTL limits
+    pub fn cache(mut self, cache: Cache) -> RecursiveResolver {
+        self.cache = Arc::new(Mutex::new(cache));
+        self
+    }
+
+    /// Resolve `q_type` records of `name`
+    pub fn lookup(&self, name: &Name, q_type: QuestionType) -> Result<DnsPacket, ResolveError> {
+        let query = DnsPacket::query().question(name, q_type).build();
+        self.query(&query)
+    }
+
+    /**
+        Resolve the question of `query` and answer it as a recursive server
+        would, the aliases followed and the final records in the answer
+        section. A query without exactly one question gets FORMERR.
+    */
+    pub fn query(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let response = DnsPacket::response_to(query).recursion_available(true);
+        let question = match query.questions.as_slice() {
+            [question] => question,
+            _ => return Ok(response.r_code(RCode::FormErr).build()),
+        };
+
+        let mut work = Work {
+            queries: self.max_queries,
+            resolving: HashSet::new(),
+        };
+        let resolution = self.resolve(&question.name, question.q_type, &mut work)?;
+
+        let mut response = response.r_code(resolution.r_code);
+        for record in resolution.answers {
+            response = response.answer(record);
+        }
+        for record in resolution.authorities {
+            response = response.authority(record);
+        }
+        Ok(response.build())
+    }
+
+    /// Follow `name` through aliases to its `q_type` records or a negative answer
+    fn resolve(
+        &self,
+        name: &Name,
+        q_type: QuestionType,
+        work: &mut Work,
+    ) -> Result<Resolution, ResolveError> {
+        let mut name = name.clone();
+        let mut answers = Vec::new();
+        let mut seen = HashSet::from([name.clone()]);
+
+        loop {
+            if let Some(cached) = self.cached(&name, q_type) {
+                let (authorities, r_code) = match cached {
+                    Cached::Records(records) => {
+                        answers.extend(records);
+                        (Vec::new(), RCode::NoError)
+                    }
+                    Cached::NoData(soa) => (vec![soa], RCode::NoError),
+                    Cached::NxDomain(soa) => (vec![soa], RCode::NameErr),
+                };
+                return Ok(Resolution {
+                    answers,
+                    authorities,
+                    r_code,
+                });
+            }
+            if let Some((records, target)) = self.cached_alias(&name, q_type) {
+                answers.extend(records);
+                if !seen.insert(target.clone()) || seen.len() > MAX_CNAME_CHAIN {
+                    return Err(ResolveError::AliasLoop {
+                        name: target.to_string(),
+                    });
+                }
+                name = target;
+                continue;
+            }
+
+            let (response, zone) = self.iterate(&name, q_type, work)?;
+            let records: Vec<&DnsRecord> = response
+                .answers
+                .iter()
+                .filter(|record| record.name.is_subdomain_of(&zone))
+                .collect();
+
+            let mut current = name.clone();
+            loop {
+                let owned: Vec<&DnsRecord> = records
+                    .iter()
+                    .copied()
+                    .filter(|record| record.name == current)
+                    .collect();
+
+                let matching: Vec<DnsRecord> = owned
+                    .iter()
+                    .filter(|record| type_matches(q_type, record.r_type))
+                    .map(|&record| record.clone())
+                    .collect();
+                if !matching.is_empty() {
+                    answers.extend(matching);
+                    return Ok(Resolution {
+                        answers,
+                        authorities: Vec::new(),
+                        r_code: RCode::NoError,
+                    });
+                }
+
+                let target = match Self::alias(&records, &owned, &current) {
+                    Some((records, Some(target))) => {
+                        answers.extend(records);
+                        target
+                    }
+                    Some((records, None)) => {
+                        // the substituted name would be too long (RFC 6672 section 2.2)
+                        answers.extend(records);
+                        return Ok(Resolution {
+                            answers,
+                            authorities: Vec::new(),
+                            r_code: RCode::YxDomain,
+                        });
+                    }
+                    None => break,
+                };
+
+                if !seen.insert(target.clone()) || seen.len() > MAX_CNAME_CHAIN {
+                    return Err(ResolveError::AliasLoop {
+                        name: target.to_string(),
+                    });
+                }
+                current = target;
+            }
+
+            // a server that followed the aliases inside its zone answered for the last one
+            let concluded = response.header.authoritative_answer
+                && current.is_subdomain_of(&zone)
+                && (response.header.r_code == RCode::NameErr
+                    || response
+                        .authorities
+                        .iter()
+                        .any(|record| record.r_type == ResourceType::SOA));
+            if current != name && !concluded {
+                name = current;
+                continue;
+            }
+
+            let authorities = response
+                .authorities
+                .iter()
+                .filter(|record| record.r_type == ResourceType::SOA)
+                .filter(|record| record.name.is_subdomain_of(&zone))
+                .cloned()
+                .collect();
+            return Ok(Resolution {
+                answers,
+                authorities,
+                r_code: response.header.r_code,
+            });
+        }
+    }
+
+    /**
+        The records aliasing `name` to another name, a CNAME it owns or a
+        DNAME of one of its ancestors with the CNAME it stands for, and the
+        name aliased to. The name is `None` if a DNAME would make it too
+        long.
+    */
+    fn alias(
+        records: &[&DnsRecord],
+        owned: &[&DnsRecord],
+        name: &Name,
+    ) -> Option<(Vec<DnsRecord>, Option<Name>)> {
+        for record in owned {
+            if let RData::CName(target) = &record.r_data {
+                return Some((vec![(*record).clone()], Some(target.clone())));
+            }
+        }
+
+        for record in records {
+            if let RData::DName(target) = &record.r_data {
+                if record.name == *name || !name.is_subdomain_of(&record.name) {
+                    continue;
+                }
+                return Some(match Self::synthesize(record, name, target) {
+                    Some(cname) => {
+                        let target = match &cname.r_data {
+                            RData::CName(target) => Some(target.clone()),
+                            _ => None,
+                        };
+                        (vec![(*record).clone(), cname], target)
+                    }
+                    None => (vec![(*record).clone()], None),
+                });
+            }
+        }
+
+        None
+    }
+
+    /// What the cache holds about `q_type` records of `name`, for questions of a single type
+    fn cached(&self, name: &Name, q_type: QuestionType) -> Option<Cached> {
+        let QuestionType::Base(r_type) = q_type else {
+            return None;
+        };
+        self.cache
+            .lock()
+            .unwrap()
+            .get(name, r_type, ResourceClass::IN)
+    }
+
+    /// A cached CNAME of `name` and the name it leads to, unless CNAMEs are asked for
+    fn cached_alias(&self, name: &Name, q_type: QuestionType) -> Option<(Vec<DnsRecord>, Name)> {
+        if q_type == QuestionType::Base(ResourceType::CName) {
+            return None;
+        }
+        let Cached::Records(records) =
+            self.cached(name, QuestionType::Base(ResourceType::CName))?
+        else {
+            return None;
+        };
+        let target = records.iter().find_map(|record| match &record.r_data {
+            RData::CName(target) => Some(target.clone()),
+            _ => None,
+        })?;
+        Some((records, target))
+    }
+
+    /**
+        The CNAME a DNAME record stands for at `name`, or `None` if the name
+        it leads to would be too long.
+    */
+    fn synthesize(dname: &DnsRecord, name: &Name, target: &Name) -> Option<DnsRecord> {
+        let prefix = name.label_count() - dname.name.label_count();
+        let substituted =
+            Name::from_labels(name.labels().take(prefix).chain(target.labels())).ok()?;
+
+        Some(DnsRecord::with_r_data(
+            name.clone(),
+            dname.ttl,
+            RData::CName(substituted),
+        ))
+    }
+
+    /**
+        Walk down from the closest known servers to those of the zone `name`
+        is in, and return their response about it along with the zone.
+    */
+    fn iterate(
+        &self,
+        name: &Name,
+        q_type: QuestionType,
+        work: &mut Work,
+    ) -> Result<(DnsPacket, Name), ResolveError> {
+        let labels = name.label_count();
+        let q_type_ds = q_type == QuestionType::Base(ResourceType::Unknown(DS));
+        let mut delegation = match q_type_ds {
+            true => self.closest_delegation(&name.parent().unwrap_or_default()),
+            false => self.closest_delegation(name),
+        };
+        let mut asked = delegation.zone.label_count() + 1;
+        let mut abandoned = HashSet::new();
+
+        loop {
+            let minimised = self.qname_minimisation && asked < labels;
+            let (q_name, q_type) = if minimised {
+                (name.suffix(asked), QuestionType::Base(ResourceType::A))
+            } else {
+                (name.clone(), q_type)
+            };
+
+            let response = match self.ask(&mut delegation, &q_name, q_type, work) {
+                // the servers of the zone may be reachable through the zone above
+                Err(ResolveError::NoNameservers { .. })
+                    if !delegation.zone.is_root() && abandoned.insert(delegation.zone.clone()) =>
+                {
+                    let parent = delegation.zone.parent().unwrap_or_default();
+                    delegation = self.closest_delegation(&parent);
+                    asked = delegation.zone.label_count() + 1;
+                    continue;
+                }
+                result => result?,
+            };
+            self.cache
+                .lock()
+                .unwrap()
+                .insert_response(&response, &delegation.zone);
+
+            if let Some(referral) = Self::referral(&response, &delegation.zone, &q_name) {
+                // DS records are held above the zone cut, by the parent (RFC 4035 section 3.1.4.1)
+                if q_type_ds && referral.zone == *name {
+                    asked = labels;
+                    continue;
+                }
+                asked = referral.zone.label_count() + 1;
+                delegation = referral;
+                continue;
+            }
+
+            // no zone cut at the minimised name, so ask about one more label, unless
+            // it does not exist, and neither does anything below it (RFC 8020)
+            if minimised && response.header.r_code != RCode::NameErr {
+                asked += 1;
+                continue;
+            }
+
+            return Ok((response, delegation.zone));
+        }
+    }
+
+    /**
+        The servers of the closest zone at or above `name` with cached NS
+        records, or the root hints.
+    */
+    fn closest_delegation(&self, name: &Name) -> Delegation {
+        let mut cache = self.cache.lock().unwrap();
+        let mut zone = name.clone();
+
+        while !zone.is_root() {
+            if let Some(Cached::Records(records)) =
+                cache.get(&zone, ResourceType::NS, ResourceClass::IN)
+            {
+                let servers = records
+                    .iter()
+                    .filter_map(|record| match &record.r_data {
+                        RData::NS(server) => Some(server.clone()),
+                        _ => None,
+                    })
+                    .map(|server| {
+                        let mut addresses = Vec::new();
+                        for r_type in [ResourceType::A, ResourceType::AAAA] {
+                            if let Some(Cached::Records(records)) =
+                                cache.get(&server, r_type, ResourceClass::IN)
+                            {
+                                addresses.extend(records.iter().filter_map(address));
+                            }
+                        }
+                        (server, Some(addresses).filter(|a| !a.is_empty()))
+                    })
+                    .collect();
+
+                return Delegation { zone, servers };
+            }
+            zone = zone.parent().unwrap_or_default();
+        }
+
+        Delegation {
+            zone: Name::root(),
+            servers: self
+                .root_hints
+                .iter()
+                .map(|(name, address)| (name.clone(), Some(vec![*address])))
+                .collect(),
+        }
+    }
+
+    /**
+        Ask the servers of a delegation in turn until one answers with
+        NOERROR or NXDOMAIN, authoritatively or with a referral further
+        down. Those with known addresses go first, then
+        those whose addresses must be looked up, which are kept in the
+        delegation for the next question to its zone.
+    */
+    fn ask(
+        &self,
+        delegation: &mut Delegation,
+        name: &Name,
+        q_type: QuestionType,
+        work: &mut Work,
+    ) -> Result<DnsPacket, ResolveError> {
+        let query = DnsPacket::query()
+            .question(name, q_type)
+            .recursion_desired(false)
+            .edns_payload_size(EDNS_PAYLOAD_SIZE)
+            .build();
+
+        let mut tried = HashSet::new();
+        for look_up in [false, true] {
+            for i in 0..delegation.servers.len() {
+                let (server, addresses) = delegation.servers[i].clone();
+                let addresses = match addresses {
+                    Some(addresses) if !look_up => addresses,
+                    // without glue the server of a zone cannot be found through the zone itself
+                    None if look_up && !server.is_subdomain_of(&delegation.zone) => {
+                        let addresses = self.addresses(&server, work)?;
+                        delegation.servers[i].1 = Some(addresses.clone());
+                        addresses
+                    }
+                    _ => continue,
+                };
+
+                for address in addresses {
+                    if tried.insert(address) {
+                        let zone = &delegation.zone;
+                        if let Some(response) = self.send(&query, zone, address, work)? {
+                            return Ok(response);
+                        }
+                    }
+                }
+            }
+        }
+
+        Err(ResolveError::NoNameservers {
+            zone: delegation.zone.to_string(),
+        })
+    }
+
+    /**
+        Send `query` to one server of `zone`, taking it from the budget.
+        `None` if the server cannot be reached, answers with an error, or is
+        lame: neither authoritative for the name nor referring to a zone
+        below `zone`. The next server should be tried then.
+    */
+    fn send(
+        &self,
+        query: &DnsPacket,
+        zone: &Name,
+        address: IpAddr,
+        work: &mut Work,
+    ) -> Result<Option<DnsPacket>, ResolveError> {
+        self.charge(work)?;
+
+        let upstream = StubResolver::new(SocketAddr::new(address, self.port))
+            .timeout(self.timeout)
+            .attempts(1);
+        let response = match upstream.query(query) {
+            Ok(response) if matches!(response.header.r_code, RCode::NoError | RCode::NameErr) => {
+                response
+            }
+            Ok(_) | Err(_) => return Ok(None),
+        };
+
+        let name = &query.questions[0].name;
+        if response.header.authoritative_answer || Self::referral(&response, zone, name).is_some() {
+            Ok(Some(response))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Take one message from the budget of the query
+    fn charge(&self, work: &mut Work) -> Result<(), ResolveError> {
+        if work.queries == 0 {
+            return Err(ResolveError::WorkLimit {
+                queries: self.max_queries,
+            });
+        }
+        work.queries -= 1;
+        Ok(())
+    }
+
+    /**
+        The addresses of a name server, resolved with the rest of the
+        budget. None while the server is already being looked up, as its
+        addresses cannot depend on themselves.
+    */
+    fn addresses(&self, server: &Name, work: &mut Work) -> Result<Vec<IpAddr>, ResolveError> {
+        if !work.resolving.insert(server.clone()) {
+            return Ok(Vec::new());
+        }
+        let addresses = self.charge(work).and_then(|()| self.look_up(server, work));
+        work.resolving.remove(server);
+
+        addresses
+    }
+
+    /// The addresses of the A records of a name server, or else of its AAAA records
+    fn look_up(&self, server: &Name, work: &mut Work) -> Result<Vec<IpAddr>, ResolveError> {
+        let mut addresses = Vec::new();
+        for r_type in [ResourceType::A, ResourceType::AAAA] {
+            let resolution = match self.resolve(server, QuestionType::Base(r_type), work) {
+                Ok(resolution) => resolution,
+                Err(e @ ResolveError::WorkLimit { .. }) => return Err(e),
+                Err(_) => continue,
+            };
+            addresses.extend(
+                resolution
+                    .answers
+                    .iter()
+                    .filter_map(|record| match record.r_data {
+                        RData::A(address) => Some(IpAddr::from(address)),
+                        RData::AAAA(address) => Some(IpAddr::from(address)),
+                        _ => None,
+                    }),
+            );
+            if !addresses.is_empty() {
+                break;
+            }
+        }
+
+        Ok(addresses)
+    }
+
+    /**
+        The delegation a response refers `name` to: NS records in the
+        authority section for a zone below `zone` that holds the name, with
+        the glue lying inside `zone`. `None` for anything else, including
+        referrals back up the tree, which would go nowhere.
+    */
+    fn referral(response: &DnsPacket, zone: &Name, name: &Name) -> Option<Delegation> {
+        if response.header.r_code != RCode::NoError || !response.answers.is_empty() {
+            return None;
+        }
+
+        let cut = response
+            .authorities
+            .iter()
+            .filter(|record| record.r_type == ResourceType::NS)
+            .map(|record| &record.name)
+            .find(|cut| *cut != zone && cut.is_subdomain_of(zone) && name.is_subdomain_of(cut))?;
+
+        let servers = response
+            .authorities
+            .iter()
+            .filter(|record| record.name == *cut)
+            .filter_map(|record| match &record.r_data {
+                RData::NS(server) => Some(server.clone()),
+                _ => None,
+            })
+            .map(|server| {
+                let glue = Some(Self::glue(response, &server))
+                    .filter(|glue| !glue.is_empty() && server.is_subdomain_of(zone));
+                (server, glue)
+            })
+            .collect();
+
+        Some(Delegation {
+            zone: cut.clone(),
+            servers,
+        })
+    }
+
+    /// The addresses for `server` in the additional section
+    fn glue(response: &DnsPacket, server: &Name) -> Vec<IpAddr> {
+        response
+            .resources
+            .iter()
+            .filter(|record| record.name == *server)
+            .filter_map(address)
+            .collect()
+    }
+}
+
+impl Default for RecursiveResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RequestHandler for RecursiveResolver {
+    /// Resolve the query, answering SERVFAIL when resolution fails
+    fn handle(&self, query: &DnsPacket) -> DnsPacket {
+        self.query(query).unwrap_or_else(|_| {
+            DnsPacket::response_to(query)
+                .recursion_available(true)
+                .r_code(RCode::ServFail)
+                .build()
+        })
+    }
+}
+
+/// The address an A or AAAA record holds
+fn address(record: &DnsRecord) -> Option<IpAddr> {
+    match record.r_data {
+        RData::A(address) => Some(address.into()),
+        RData::AAAA(address) => Some(address.into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+
+    use super::*;
+    use crate::server::authority::Authority;
+    use crate::server::dns_server::DnsServer;
+    use crate::server::zone::Zone;
+    use crate::zone_file::parser::ZoneParser;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    /// A zone from master file text with its SOA record, every line giving its owner
+    fn zone(text: &str) -> Zone {
+        let text: Vec<&str> = text.lines().map(str::trim_start).collect();
+        Zone::from_records(ZoneParser::new().parse(&text.join("\n")).unwrap()).unwrap()
+    }
+
+    /// Serve `zones` on `address`, at `port` or with 0 a port the system picks, and return the port
+    fn serve(address: [u8; 4], port: u16, zones: Vec<Zone>) -> u16 {
+        let mut authority = Authority::new();
+        for zone in zones {
+            authority.add_zone(zone);
+        }
+        let server = DnsServer::bind(SocketAddr::new(address.into(), port), authority).unwrap();
+        let port = server.local_addr().unwrap().port();
+        thread::spawn(move || server.run());
+        port
+    }
+
+    /**
+        A root zone on `127.0.n.1` delegating `com` to `127.0.n.2`, served
+        with the other zones on their addresses in `127.0.n.0/24`, and a
+        resolver starting from it. Each test takes a network of its own.
+    */
+    fn hierarchy(n: u8, com: &str, others: Vec<([u8; 4], Zone)>) -> RecursiveResolver {
+        let root = zone(&format!(
+            "$ORIGIN .
+            $TTL 300
+            @ SOA a.root-servers.net. hostmaster. 1 3600 600 86400 60
+            @ NS a.root-servers.net.
+            a.root-servers.net. A 127.0.{n}.1
+            com. NS ns.com.
+            ns.com. A 127.0.{n}.2"
+        ));
+        let com = zone(&format!(
+            "$ORIGIN com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns A 127.0.{n}.2
+            {com}"
+        ));
+
+        let port = serve([127, 0, n, 1], 0, vec![root]);
+        serve([127, 0, n, 2], port, vec![com]);
+        for (address, zone) in others {
+            serve(address, port, vec![zone]);
+        }
+
+        RecursiveResolver::new()
+            .root_hints(vec![(name("a.root-servers.net"), [127, 0, n, 1].into())])
+            .port(port)
+            .timeout(Duration::from_millis(500))
+    }
+
+    /// The zone `<label>.com` served on `127.0.n.3`, with the records of `text`
+    fn second_level(n: u8, label: &str, text: &str) -> ([u8; 4], Zone) {
+        let zone = zone(&format!(
+            "$ORIGIN {label}.com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns A 127.0.{n}.3
+            {text}"
+        ));
+        ([127, 0, n, 3], zone)
+    }
+
+    fn lookup_a(resolver: &RecursiveResolver, text: &str) -> Result<DnsPacket, ResolveError> {
+        resolver.lookup(&name(text), QuestionType::Base(ResourceType::A))
+    }
+
+    #[test]
+    fn glueless_delegation_cycle_fails() {
+        let resolver = hierarchy(
+            1,
+            "a NS ns.b
+            b NS ns.a",
+            Vec::new(),
+        );
+
+        // the second lookup starts from the NS sets the first one cached
+        for _ in 0..2 {
+            assert!(matches!(
+                lookup_a(&resolver, "www.a.com"),
+                Err(ResolveError::NoNameservers { .. } | ResolveError::WorkLimit { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn expired_glue_falls_back_to_the_parent() {
+        let example = zone(
+            "$ORIGIN example.com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns 1 A 127.0.2.3
+            www A 192.0.2.1
+            mail A 192.0.2.2",
+        );
+        let resolver = hierarchy(
+            2,
+            "example NS ns.example
+            ns.example 1 A 127.0.2.3",
+            vec![([127, 0, 2, 3], example)],
+        );
+
+        assert_eq!(
+            lookup_a(&resolver, "www.example.com")
+                .unwrap()
+                .answers
+                .len(),
+            1
+        );
+        thread::sleep(Duration::from_millis(1100));
+
+        let response = lookup_a(&resolver, "mail.example.com").unwrap();
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 2].into()));
+    }
+
+    #[test]
+    fn follows_referrals_with_glue() {
+        let resolver = hierarchy(
+            3,
+            "example NS ns.example
+            ns.example A 127.0.3.3",
+            vec![second_level(3, "example", "www A 192.0.2.1")],
+        );
+
+        let response = lookup_a(&resolver, "www.example.com").unwrap();
+        assert_eq!(response.header.r_code, RCode::NoError);
+        assert_eq!(response.answers.len(), 1);
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 1].into()));
+    }
+
+    #[test]
+    fn looks_up_glueless_name_servers() {
+        let hosting = zone(
+            "$ORIGIN hosting.com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns A 127.0.4.4
+            dns A 127.0.4.3",
+        );
+        let resolver = hierarchy(
+            4,
+            "hosting NS ns.hosting
+            ns.hosting A 127.0.4.4
+            example NS dns.hosting",
+            vec![
+                ([127, 0, 4, 4], hosting),
+                second_level(4, "example", "www A 192.0.2.1"),
+            ],
+        );
+
+        let response = lookup_a(&resolver, "www.example.com").unwrap();
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 1].into()));
+    }
+
+    #[test]
+    fn chases_aliases_across_zones() {
+        let (_, other) = second_level(5, "other", "www A 192.0.2.7");
+        let resolver = hierarchy(
+            5,
+            "example NS ns.example
+            ns.example A 127.0.5.3
+            other NS ns.other
+            ns.other A 127.0.5.4",
+            vec![
+                second_level(5, "example", "www CNAME www.other.com."),
+                ([127, 0, 5, 4], other),
+            ],
+        );
+
+        let response = lookup_a(&resolver, "www.example.com").unwrap();
+        assert_eq!(response.answers.len(), 2);
+        assert_eq!(
+            response.answers[0].r_data,
+            RData::CName(name("www.other.com"))
+        );
+        assert_eq!(response.answers[1].r_data, RData::A([192, 0, 2, 7].into()));
+    }
+
+    #[test]
+    fn stops_at_the_work_limit() {
+        let resolver = hierarchy(
+            6,
+            "example NS ns.example
+            ns.example A 127.0.6.3",
+            vec![second_level(6, "example", "www A 192.0.2.1")],
+        );
+
+        // the root, com and example.com each need a query
+        assert!(matches!(
+            lookup_a(&resolver.clone().max_queries(2), "www.example.com"),
+            Err(ResolveError::WorkLimit { queries: 2 })
+        ));
+        assert!(lookup_a(&resolver.max_queries(3), "www.example.com").is_ok());
+    }
+
+    /// A server answering every query with an empty NOERROR response that is not authoritative
+    struct Lame(Arc<AtomicUsize>);
+
+    impl RequestHandler for Lame {
+        fn handle(&self, query: &DnsPacket) -> DnsPacket {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            DnsPacket::response_to(query).build()
+        }
+    }
+
+    #[test]
+    fn skips_lame_servers() {
+        let example = zone(
+            "$ORIGIN example.com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns A 127.0.7.3
+            ns A 127.0.7.4
+            www A 192.0.2.1",
+        );
+        let resolver = hierarchy(
+            7,
+            "example NS ns.example
+            ns.example A 127.0.7.3
+            ns.example A 127.0.7.4",
+            vec![([127, 0, 7, 4], example)],
+        );
+        let asked = Arc::new(AtomicUsize::new(0));
+        let lame = DnsServer::bind(
+            SocketAddr::new([127, 0, 7, 3].into(), resolver.port),
+            Lame(asked.clone()),
+        )
+        .unwrap();
+        thread::spawn(move || lame.run());
+
+        let response = lookup_a(&resolver, "www.example.com").unwrap();
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 1].into()));
+        assert_eq!(asked.load(Ordering::SeqCst), 1);
+    }
+}
@@ -0,0 +1,282 @@
+use std::io;
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_ref::DnsPacketRef;
+use crate::domain::dns_packet_writer::MAX_MESSAGE_SIZE;
+use crate::error::{DnsError, ResolveError};
+use crate::protocol::dns_name::Name;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::transport::tcp_framing::{is_timeout, TcpConnection};
+
+/**
+    A blocking stub resolver that hands every query to one upstream name
+    server, which is expected to do the recursion.
+
+    A query is sent over UDP and the first response answering it is
+    returned; datagrams with another ID or other questions are dropped, as
+    RFC 5452 section 9.1 asks. Each of `attempts` waits up to `timeout`
+    before the query is sent again. A truncated response is retried over
+    TCP (RFC 7766 section 5).
+*/
+#[derive(Clone, Debug)]
+pub struct StubResolver {
+    upstream: SocketAddr,
+    timeout: Duration,
+    attempts: usize,
+    tcp_fallback: bool,
+}
+
+impl StubResolver {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+    pub const DEFAULT_ATTEMPTS: usize = 2;
+
+    pub fn new(upstream: SocketAddr) -> StubResolver {
+        StubResolver {
+            upstream,
+            timeout: StubResolver::DEFAULT_TIMEOUT,
+            attempts: StubResolver::DEFAULT_ATTEMPTS,
+            tcp_fallback: true,
+        }
+    }
+
+    pub fn upstream(&self) -> SocketAddr {
+        self.upstream
+    }
+
+    /// How long to wait for a response to each attempt
+    pub fn timeout(mut self, timeout: Duration) -> StubResolver {
+        self.timeout = timeout;
+        self
+    }
+
+    /// How many times a query is sent over UDP before giving up, at least once
+    pub fn attempts(mut self, attempts: usize) -> StubResolver {
+        self.attempts = attempts.max(1);
+        self
+    }
+
+    /// Whether a truncated response is retried over TCP, on by default
+    pub fn tcp_fallback(mut self, value: bool) -> StubResolver {
+        self.tcp_fallback = value;
+        self
+    }
+
+    /// Ask the upstream for `q_type` records of `name` with recursion desired
+    pub fn lookup(&self, name: &Name, q_type: QuestionType) -> Result<DnsPacket, ResolveError> {
+        let query = DnsPacket::query().question(name, q_type).build();
+        self.query(&query)
+    }
+
+    /// Send `query` over UDP, falling back to TCP if the response is truncated
+    pub fn query(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let response = self.query_udp(query)?;
+        if response.header.truncated_message && self.tcp_fallback {
+            return self.query_tcp(query);
+        }
+
+        Ok(response)
+    }
+
+    /// Send `query` over UDP only, with the configured timeout and attempts
+    pub fn query_udp(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let bytes = query.to_bytes()?;
+
+        let local: SocketAddr = if self.upstream.is_ipv4() {
+            (Ipv4Addr::UNSPECIFIED, 0).into()
+        } else {
+            (Ipv6Addr::UNSPECIFIED, 0).into()
+        };
+        let socket = UdpSocket::bind(local)?;
+        // connecting makes the kernel drop datagrams from any other source
+        socket.connect(self.upstream)?;
+
+        let mut buf = vec![0; MAX_MESSAGE_SIZE];
+        let mut malformed = None;
+        for _ in 0..self.attempts {
+            socket.send(&bytes)?;
+
+            let deadline = Instant::now() + self.timeout;
+            while let Some(remaining) = deadline
+                .checked_duration_since(Instant::now())
+                .filter(|d| !d.is_zero())
+            {
+                socket.set_read_timeout(Some(remaining))?;
+                let len = match socket.recv(&mut buf) {
+                    Ok(len) => len,
+                    Err(e) if is_timeout(&e) => break,
+                    // an ICMP error from an earlier datagram, not a verdict on this one
+                    Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                    Err(e) => return Err(e.into()),
+                };
+
+                match Self::accept(query, &buf[..len]) {
+                    Ok(Some(response)) => return Ok(response),
+                    Ok(None) => {}
+                    Err(e) => malformed = Some(e),
+                }
+            }
+        }
+
+        Err(match malformed {
+            Some(e) => e.into(),
+            None => ResolveError::Timeout {
+                attempts: self.attempts,
+            },
+        })
+    }
+
+    /**
+        Send `query` over a new TCP connection. The timeout applies to
+        connecting and to every read and write.
+    */
+    pub fn query_tcp(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        TcpConnection::connect(self.upstream, self.timeout)?.exchange(query)
+    }
+
+    /**
+        The response in `buf` if it answers `query`. Messages with another
+        ID are skipped without being decoded; one with the right ID that
+        does not decode is reported, since it most likely came from the
+        upstream.
+    */
+    fn accept(query: &DnsPacket, buf: &[u8]) -> Result<Option<DnsPacket>, DnsError> {
+        match DnsPacketRef::new(buf) {
+            Ok(view) if view.id() == query.header.id => {}
+            _ => return Ok(None),
+        }
+
+        let response = DnsPacket::from_bytes(buf)?;
+        Ok(Some(response).filter(|response| response.is_response_to(query)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::protocol::dns_resource_record::DnsRecord;
+    use crate::protocol::resource_data_enum::RData;
+    use crate::protocol::resource_type_enum::ResourceType;
+    use crate::transport::tcp_framing::TcpConnection;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn answer(query: &DnsPacket, octet: u8) -> DnsPacket {
+        let record = DnsRecord::with_r_data(
+            name("example.com"),
+            300,
+            RData::A([192, 0, 2, octet].into()),
+        );
+        DnsPacket::response_to(query).answer(record).build()
+    }
+
+    /**
+        A fake upstream on a UDP socket of its own, handing each datagram
+        it receives to `respond` with the socket to answer on, until
+        `respond` returns false.
+    */
+    fn fake_udp<F>(mut respond: F) -> SocketAddr
+    where
+        F: FnMut(&UdpSocket, SocketAddr, DnsPacket) -> bool + Send + 'static,
+    {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let addr = socket.local_addr().unwrap();
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_MESSAGE_SIZE];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).unwrap();
+                let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+                if !respond(&socket, peer, query) {
+                    break;
+                }
+            }
+        });
+        addr
+    }
+
+    fn send(socket: &UdpSocket, peer: SocketAddr, packet: &DnsPacket) {
+        socket.send_to(&packet.to_bytes().unwrap(), peer).unwrap();
+    }
+
+    fn lookup(upstream: SocketAddr) -> Result<DnsPacket, ResolveError> {
+        StubResolver::new(upstream)
+            .timeout(Duration::from_millis(300))
+            .lookup(&name("example.com"), QuestionType::Base(ResourceType::A))
+    }
+
+    #[test]
+    fn skips_responses_with_another_id_or_question() {
+        let upstream = fake_udp(|socket, peer, query| {
+            let mut other_id = answer(&query, 1);
+            other_id.header.id = query.header.id.wrapping_add(1);
+            send(socket, peer, &other_id);
+
+            let mut other_question = answer(&query, 2);
+            other_question.questions[0].name = name("example.net");
+            send(socket, peer, &other_question);
+
+            send(socket, peer, &answer(&query, 3));
+            false
+        });
+
+        let response = lookup(upstream).unwrap();
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 3].into()));
+    }
+
+    #[test]
+    fn retries_after_a_timeout() {
+        let mut seen = 0;
+        let upstream = fake_udp(move |socket, peer, query| {
+            seen += 1;
+            if seen == 2 {
+                send(socket, peer, &answer(&query, 1));
+            }
+            seen < 2
+        });
+
+        let response = lookup(upstream).unwrap();
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn gives_up_after_the_last_attempt() {
+        let upstream = fake_udp(|_, _, _| true);
+
+        let result = StubResolver::new(upstream)
+            .timeout(Duration::from_millis(100))
+            .attempts(2)
+            .lookup(&name("example.com"), QuestionType::Base(ResourceType::A));
+        assert!(matches!(result, Err(ResolveError::Timeout { attempts: 2 })));
+    }
+
+    #[test]
+    fn falls_back_to_tcp_when_truncated() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let socket = UdpSocket::bind(addr).unwrap();
+
+        thread::spawn(move || {
+            let mut buf = vec![0; MAX_MESSAGE_SIZE];
+            let (len, peer) = socket.recv_from(&mut buf).unwrap();
+            let query = DnsPacket::from_bytes(&buf[..len]).unwrap();
+            let truncated = DnsPacket::response_to(&query).truncated(true).build();
+            send(&socket, peer, &truncated);
+        });
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut connection = TcpConnection::new(stream);
+            let query = connection.read_packet().unwrap().unwrap();
+            connection.write_packet(&answer(&query, 1)).unwrap();
+        });
+
+        let response = lookup(addr).unwrap();
+        assert!(!response.header.truncated_message);
+        assert_eq!(response.answers.len(), 1);
+    }
+}
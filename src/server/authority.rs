@@ -0,0 +1,337 @@
+use std::collections::HashSet;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_builder::ResponseBuilder;
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_question::DnsQuestion;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::opcode_enum::OpCode;
+use crate::protocol::question_class_enum::QuestionClass;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::rcode_enum::RCode;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+use crate::server::dns_server::RequestHandler;
+use crate::server::zone::{Lookup, Zone};
+
+/// The longest CNAME chain followed within a zone before answering with what was found
+pub const MAX_CNAME_CHAIN: usize = 16;
+
+/**
+    An authoritative name server for a set of in-memory zones.
+
+    Questions are answered from the most specific zone holding the name,
+    with AA set, as described in RFC 1034 section 4.3.2: CNAME chains are
+    followed while they stay in the zone, names below a zone cut get a
+    referral with the NS records in the authority section and glue in the
+    additional section, and negative answers carry the SOA of the zone with
+    NXDOMAIN told apart from NODATA (RFC 2308). Names in no zone are
+    REFUSED.
+*/
+#[derive(Debug, Clone, Default)]
+pub struct Authority {
+    zones: Vec<Zone>,
+}
+
+impl Authority {
+    pub fn new() -> Authority {
+        Authority { zones: Vec::new() }
+    }
+
+    pub fn add_zone(&mut self, zone: Zone) {
+        self.zones.push(zone);
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// The zone with the longest origin that `name` falls in
+    pub fn find_zone(&self, name: &Name) -> Option<&Zone> {
+        self.zones
+            .iter()
+            .filter(|zone| zone.contains(name))
+            .max_by_key(|zone| zone.origin().label_count())
+    }
+
+    pub fn answer(&self, query: &DnsPacket) -> DnsPacket {
+        let response = DnsPacket::response_to(query);
+
+        if query.header.opcode != OpCode::Query {
+            return response.r_code(RCode::NotImp).build();
+        }
+        let question = match query.questions.as_slice() {
+            [question] => question,
+            _ => return response.r_code(RCode::FormErr).build(),
+        };
+        if question.q_type == QuestionType::AxfR {
+            return response.r_code(RCode::NotImp).build();
+        }
+
+        let in_class = matches!(
+            question.q_class,
+            QuestionClass::Base(ResourceClass::IN) | QuestionClass::All
+        );
+        match self.find_zone(&question.name) {
+            Some(zone) if in_class => Self::resolve(zone, question, response),
+            _ => response.r_code(RCode::Refused).build(),
+        }
+    }
+
+    fn resolve(zone: &Zone, question: &DnsQuestion, response: ResponseBuilder) -> DnsPacket {
+        let mut response = response.authoritative(true);
+        let mut name = question.name.clone();
+        let mut seen = HashSet::new();
+
+        let mut answers = Vec::new();
+        let mut authorities = Vec::new();
+        let mut r_code = RCode::NoError;
+
+        loop {
+            seen.insert(name.clone());
+
+            match zone.lookup(&name, question.q_type) {
+                Lookup::Answer(records) => {
+                    answers.extend(records);
+                    break;
+                }
+                Lookup::CName(record) => {
+                    let target = match &record.r_data {
+                        RData::CName(target) => target.clone(),
+                        _ => unreachable!(),
+                    };
+                    answers.push(record);
+
+                    let chased = zone.contains(&target)
+                        && !seen.contains(&target)
+                        && seen.len() < MAX_CNAME_CHAIN;
+                    if !chased {
+                        break;
+                    }
+                    name = target;
+                }
+                Lookup::NoData => {
+                    authorities.extend(Self::negative_soa(zone));
+                    break;
+                }
+                Lookup::NxDomain => {
+                    // the RCODE describes the last name of the chain (RFC 6604)
+                    r_code = RCode::NameErr;
+                    authorities.extend(Self::negative_soa(zone));
+                    break;
+                }
+                Lookup::Referral(ns) => {
+                    // AA covers only the aliases followed inside the zone
+                    response = response.authoritative(!answers.is_empty());
+                    authorities.extend(ns);
+                    break;
+                }
+            }
+        }
+
+        let additionals = Self::additional(zone, answers.iter().chain(&authorities));
+
+        for record in answers {
+            response = response.answer(record);
+        }
+        for record in authorities {
+            response = response.authority(record);
+        }
+        for record in additionals {
+            response = response.additional(record);
+        }
+
+        response.r_code(r_code).build()
+    }
+
+    /**
+        The SOA record to put in a negative answer, with the TTL lowered to
+        its MINIMUM field, which gives how long the answer may be cached
+        (RFC 2308 section 3).
+    */
+    fn negative_soa(zone: &Zone) -> Option<DnsRecord> {
+        let mut soa = zone.soa()?.clone();
+        if let RData::SOA { minimum, .. } = soa.r_data {
+            soa.ttl = soa.ttl.min(minimum);
+        }
+        Some(soa)
+    }
+
+    /**
+        Addresses the zone has for the names `records` point to, the glue of
+        a referral included (RFC 1035 section 4.3.2, step 6).
+    */
+    fn additional<'a>(zone: &Zone, records: impl Iterator<Item = &'a DnsRecord>) -> Vec<DnsRecord> {
+        let mut additionals: Vec<DnsRecord> = Vec::new();
+
+        for record in records {
+            let target = match &record.r_data {
+                RData::NS(target) => target,
+                RData::MX { exchange, .. } => exchange,
+                RData::SRV { target, .. } => target,
+                _ => continue,
+            };
+
+            for r_type in [ResourceType::A, ResourceType::AAAA] {
+                for address in zone.of_type(target, r_type) {
+                    if !additionals.contains(&address) {
+                        additionals.push(address);
+                    }
+                }
+            }
+        }
+
+        additionals
+    }
+}
+
+impl RequestHandler for Authority {
+    fn handle(&self, query: &DnsPacket) -> DnsPacket {
+        self.answer(query)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::dns_packet_builder::QueryBuilder;
+    use crate::zone_file::parser::ZoneParser;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn authority() -> Authority {
+        let text = "
+            $ORIGIN example.com.
+            $TTL 300
+            @ SOA ns hostmaster 1 3600 600 86400 60
+            @ NS ns
+            ns A 192.0.2.53
+            www A 192.0.2.1
+            mail MX 10 www
+            alias CNAME www
+            outside CNAME www.example.net.
+            a.b.c A 192.0.2.10
+            *.wild TXT \"wildcard\"
+            host.wild A 192.0.2.9
+            sub NS ns.sub
+            ns.sub A 192.0.2.54";
+        let text: Vec<&str> = text.lines().map(str::trim_start).collect();
+        let records = ZoneParser::new().parse(&text.join("\n")).unwrap();
+
+        let mut authority = Authority::new();
+        authority.add_zone(Zone::from_records(records).unwrap());
+        authority
+    }
+
+    fn ask(text: &str, r_type: ResourceType) -> DnsPacket {
+        let query = QueryBuilder::new()
+            .question(&name(text), QuestionType::Base(r_type))
+            .build();
+        authority().answer(&query)
+    }
+
+    fn types(records: &[DnsRecord]) -> Vec<ResourceType> {
+        records.iter().map(|record| record.r_type).collect()
+    }
+
+    #[test]
+    fn answers_authoritatively() {
+        let response = ask("www.example.com", ResourceType::A);
+
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.header.r_code, RCode::NoError);
+        assert_eq!(response.answers[0].r_data, RData::A([192, 0, 2, 1].into()));
+        assert_eq!(response.answers.len(), 1);
+    }
+
+    #[test]
+    fn adds_the_addresses_of_targets() {
+        let response = ask("mail.example.com", ResourceType::MX);
+
+        assert_eq!(response.resources.len(), 1);
+        assert_eq!(response.resources[0].name, name("www.example.com"));
+    }
+
+    #[test]
+    fn tells_nxdomain_from_nodata() {
+        let response = ask("missing.example.com", ResourceType::A);
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.header.r_code, RCode::NameErr);
+        assert!(response.answers.is_empty());
+        assert_eq!(types(&response.authorities), [ResourceType::SOA]);
+        // the SOA is cached for its MINIMUM at most
+        assert_eq!(response.authorities[0].ttl, 60);
+
+        // an empty non-terminal exists, and so does an owner of other types
+        for (text, r_type) in [
+            ("b.c.example.com", ResourceType::A),
+            ("www.example.com", ResourceType::MX),
+        ] {
+            let response = ask(text, r_type);
+            assert_eq!(response.header.r_code, RCode::NoError);
+            assert!(response.answers.is_empty());
+            assert_eq!(types(&response.authorities), [ResourceType::SOA]);
+        }
+    }
+
+    #[test]
+    fn refers_below_zone_cuts_with_glue() {
+        let response = ask("host.sub.example.com", ResourceType::A);
+
+        assert!(!response.header.authoritative_answer);
+        assert_eq!(response.header.r_code, RCode::NoError);
+        assert!(response.answers.is_empty());
+        assert_eq!(
+            response.authorities[0].r_data,
+            RData::NS(name("ns.sub.example.com"))
+        );
+        assert_eq!(
+            response.resources[0].r_data,
+            RData::A([192, 0, 2, 54].into())
+        );
+    }
+
+    #[test]
+    fn chases_aliases_inside_the_zone() {
+        let response = ask("alias.example.com", ResourceType::A);
+        assert_eq!(
+            types(&response.answers),
+            [ResourceType::CName, ResourceType::A]
+        );
+        assert_eq!(response.answers[1].name, name("www.example.com"));
+
+        // the target is for the resolver to find
+        let response = ask("outside.example.com", ResourceType::A);
+        assert!(response.header.authoritative_answer);
+        assert_eq!(response.header.r_code, RCode::NoError);
+        assert_eq!(types(&response.answers), [ResourceType::CName]);
+
+        let response = ask("alias.example.com", ResourceType::CName);
+        assert_eq!(types(&response.answers), [ResourceType::CName]);
+    }
+
+    #[test]
+    fn synthesizes_from_wildcards() {
+        for text in ["any.wild.example.com", "deeper.any.wild.example.com"] {
+            let response = ask(text, ResourceType::Txt);
+            assert_eq!(response.header.r_code, RCode::NoError);
+            assert_eq!(response.answers[0].name, name(text));
+        }
+
+        // names that exist are not covered by the wildcard
+        let response = ask("host.wild.example.com", ResourceType::Txt);
+        assert!(response.answers.is_empty());
+        assert_eq!(response.header.r_code, RCode::NoError);
+    }
+
+    #[test]
+    fn refuses_names_outside_its_zones() {
+        let response = ask("www.example.org", ResourceType::A);
+
+        assert_eq!(response.header.r_code, RCode::Refused);
+        assert!(!response.header.authoritative_answer);
+    }
+}
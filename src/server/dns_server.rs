@@ -0,0 +1,255 @@
+use std::io;
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::Duration;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_ref::DnsPacketRef;
+use crate::domain::dns_packet_writer::MAX_MESSAGE_SIZE;
+use crate::protocol::rcode_enum::RCode;
+use crate::transport::tcp_framing::TcpConnection;
+
+/// The largest UDP response to a query without EDNS (RFC 1035 section 4.2.1)
+pub const MAX_UDP_SIZE: usize = 512;
+
+/// Turns a decoded query into the response to send back
+pub trait RequestHandler: Send + Sync + 'static {
+    fn handle(&self, query: &DnsPacket) -> DnsPacket;
+}
+
+/**
+    Serves a `RequestHandler` over UDP and TCP on the same address.
+
+    UDP responses larger than the client can take, 512 octets or its EDNS
+    payload size, are cut down to the header and question with TC set, so
+    the client asks again over TCP. TCP connections are served by a thread
+    each and closed after the idle timeout. Queries that do not decode get
+    FORMERR when at least their header does, and are ignored otherwise.
+
+    A datagram that cannot be received or answered is skipped, so one
+    unreachable client does not end the service; its error goes to the
+    `on_error` hook, if one is set.
+*/
+pub struct DnsServer<H: RequestHandler> {
+    udp: UdpSocket,
+    tcp: TcpListener,
+    handler: Arc<H>,
+    idle_timeout: Duration,
+    on_error: Option<ErrorHook>,
+}
+
+/// Called with the errors a server skips rather than stopping for
+pub type ErrorHook = Arc<dyn Fn(&io::Error) + Send + Sync>;
+
+impl<H: RequestHandler> DnsServer<H> {
+    pub const DEFAULT_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+
+    /**
+        Bind the UDP socket and TCP listener. With port 0 the system picks
+        a port for TCP and UDP is bound to the same one.
+    */
+    pub fn bind(addr: SocketAddr, handler: H) -> io::Result<DnsServer<H>> {
+        let tcp = TcpListener::bind(addr)?;
+        let udp = UdpSocket::bind(tcp.local_addr()?)?;
+
+        Ok(DnsServer {
+            udp,
+            tcp,
+            handler: Arc::new(handler),
+            idle_timeout: Self::DEFAULT_IDLE_TIMEOUT,
+            on_error: None,
+        })
+    }
+
+    /// How long a TCP connection may go without a query before it is closed
+    pub fn idle_timeout(mut self, timeout: Duration) -> DnsServer<H> {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Report the datagrams that could not be received or answered to `hook`
+    pub fn on_error<F>(mut self, hook: F) -> DnsServer<H>
+    where
+        F: Fn(&io::Error) + Send + Sync + 'static,
+    {
+        self.on_error = Some(Arc::new(hook));
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.tcp.local_addr()
+    }
+
+    pub fn handler(&self) -> &H {
+        &self.handler
+    }
+
+    /**
+        Serve UDP and TCP on a thread each until either socket fails,
+        returning the error that stopped it.
+    */
+    pub fn run(self) -> io::Result<()> {
+        let (sender, receiver) = mpsc::channel();
+
+        let udp = self.udp.try_clone()?;
+        let handler = self.handler.clone();
+        let on_error = self.on_error.clone();
+        let udp_sender = sender.clone();
+        thread::spawn(move || {
+            let _ = udp_sender.send(Self::serve_udp(udp, handler, on_error));
+        });
+
+        let tcp = self.tcp.try_clone()?;
+        let handler = self.handler.clone();
+        let idle_timeout = self.idle_timeout;
+        thread::spawn(move || {
+            let _ = sender.send(Self::accept_tcp(tcp, handler, idle_timeout));
+        });
+
+        receiver.recv().unwrap_or(Ok(()))
+    }
+
+    /**
+        Answer datagrams until receiving fails in a way that leaves the
+        socket unusable. A failed send concerns one client only, and so do
+        receive errors reporting ICMP messages about earlier datagrams.
+    */
+    fn serve_udp(
+        socket: UdpSocket,
+        handler: Arc<H>,
+        on_error: Option<ErrorHook>,
+    ) -> io::Result<()> {
+        let report = |e: io::Error| {
+            if let Some(hook) = &on_error {
+                hook(&e);
+            }
+        };
+
+        let mut buf = vec![0; MAX_MESSAGE_SIZE];
+        loop {
+            let (len, peer) = match socket.recv_from(&mut buf) {
+                Ok(received) => received,
+                Err(e) if is_transient(&e) => {
+                    report(e);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            if let Some(response) = respond(handler.as_ref(), &buf[..len], MAX_UDP_SIZE) {
+                if let Err(e) = socket.send_to(&response, peer) {
+                    report(io::Error::new(
+                        e.kind(),
+                        format!("sending to {} failed: {}", peer, e),
+                    ));
+                }
+            }
+        }
+    }
+
+    fn accept_tcp(
+        listener: TcpListener,
+        handler: Arc<H>,
+        idle_timeout: Duration,
+    ) -> io::Result<()> {
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) if e.kind() == io::ErrorKind::ConnectionAborted => continue,
+                Err(e) => return Err(e),
+            };
+
+            let handler = handler.clone();
+            thread::spawn(move || Self::serve_tcp(stream, handler, idle_timeout));
+        }
+
+        Ok(())
+    }
+
+    fn serve_tcp(stream: TcpStream, handler: Arc<H>, idle_timeout: Duration) -> io::Result<()> {
+        stream.set_nodelay(true)?;
+        let mut connection = TcpConnection::new(stream);
+        connection.set_idle_timeout(Some(idle_timeout))?;
+
+        while let Some(query) = connection.read_message()? {
+            if let Some(response) = respond(handler.as_ref(), &query, MAX_MESSAGE_SIZE) {
+                connection.write_message(&response)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Whether a receive error is about earlier traffic rather than the socket itself
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::ConnectionReset
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::HostUnreachable
+            | io::ErrorKind::NetworkUnreachable
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::WouldBlock
+            | io::ErrorKind::TimedOut
+    )
+}
+
+/**
+    The encoded response to the query in `bytes`, at most `limit` octets
+    unless the query advertises a larger EDNS payload size. `None` when
+    there is nothing to answer, such as a response or a runt message.
+*/
+pub fn respond<H: RequestHandler + ?Sized>(
+    handler: &H,
+    bytes: &[u8],
+    limit: usize,
+) -> Option<Vec<u8>> {
+    let query = match DnsPacket::from_bytes(bytes) {
+        Ok(query) => query,
+        Err(_) => {
+            let header = DnsPacketRef::new(bytes).ok()?.header().ok()?;
+            if header.response {
+                return None;
+            }
+
+            let mut query = DnsPacket::new();
+            query.header = header;
+            query.questions.clear();
+            let response = DnsPacket::response_to(&query)
+                .r_code(RCode::FormErr)
+                .build();
+            return response.to_bytes().ok();
+        }
+    };
+    if query.header.response {
+        return None;
+    }
+
+    let response = handler.handle(&query);
+    let limit = match &query.edns {
+        Some(edns) => limit.max(edns.udp_payload_size as usize),
+        None => limit,
+    };
+
+    let bytes = match response.to_bytes() {
+        Ok(bytes) => bytes,
+        Err(_) => {
+            let failure = DnsPacket::response_to(&query)
+                .r_code(RCode::ServFail)
+                .build();
+            return failure.to_bytes().ok();
+        }
+    };
+    if bytes.len() <= limit {
+        return Some(bytes);
+    }
+
+    let mut truncated = response;
+    truncated.header.truncated_message = true;
+    truncated.answers.clear();
+    truncated.authorities.clear();
+    truncated.resources.clear();
+    truncated.to_bytes().ok()
+}
@@ -0,0 +1,410 @@
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{mpsc, Arc};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_builder::random_id;
+use crate::error::ResolveError;
+use crate::protocol::rcode_enum::RCode;
+use crate::resolver::stub_resolver::StubResolver;
+use crate::server::dns_server::RequestHandler;
+
+/// How a forwarder picks the upstreams to send a query to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Strategy {
+    /// Try the upstreams in the order given, moving on when one fails
+    Failover,
+    /// Start each query at the upstream after the one the last query started at
+    RoundRobin,
+    /// Try the upstreams in order of their smoothed round-trip time, fastest first
+    FastestRtt,
+    /// Send the query to every upstream at once and take the first good response
+    Race,
+}
+
+impl Strategy {
+    /// The strategy named as in its `Display` form, e.g. `round-robin`
+    pub fn from_name(name: &str) -> Option<Strategy> {
+        let strategy = match name.to_ascii_lowercase().as_str() {
+            "failover" => Strategy::Failover,
+            "round-robin" => Strategy::RoundRobin,
+            "fastest-rtt" => Strategy::FastestRtt,
+            "race" => Strategy::Race,
+            _ => return None,
+        };
+        Some(strategy)
+    }
+}
+
+impl fmt::Display for Strategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Strategy::Failover => write!(f, "failover"),
+            Strategy::RoundRobin => write!(f, "round-robin"),
+            Strategy::FastestRtt => write!(f, "fastest-rtt"),
+            Strategy::Race => write!(f, "race"),
+        }
+    }
+}
+
+/// An upstream with what has been measured of it
+#[derive(Debug)]
+struct Upstream {
+    addr: SocketAddr,
+    /// The smoothed round-trip time in microseconds, 0 until the first exchange
+    srtt: AtomicU64,
+}
+
+/**
+    Relays queries to a pool of upstream name servers, which do the
+    recursion, and hands their responses back.
+
+    Each query goes upstream under an ID of its own, and the response gets
+    the client's ID back before it is returned. An upstream that does not
+    respond, or answers with anything but NOERROR or NXDOMAIN, counts as
+    failed and the next one is tried, in an order set by the `Strategy`.
+    When every upstream fails, the last error response is passed on, or
+    SERVFAIL if there was none.
+
+    Served by a `DnsServer`, it makes a local forwarder for UDP and TCP
+    clients.
+*/
+#[derive(Debug)]
+pub struct Forwarder {
+    upstreams: Arc<Vec<Upstream>>,
+    strategy: Strategy,
+    timeout: Duration,
+    /// Where the next round-robin query starts
+    next: AtomicUsize,
+}
+
+impl Forwarder {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(2);
+
+    /// A failover forwarder to `upstreams`, which must not be empty
+    pub fn new(upstreams: Vec<SocketAddr>) -> Forwarder {
+        assert!(!upstreams.is_empty(), "a forwarder needs an upstream");
+
+        let upstreams = upstreams
+            .into_iter()
+            .map(|addr| Upstream {
+                addr,
+                srtt: AtomicU64::new(0),
+            })
+            .collect();
+
+        Forwarder {
+            upstreams: Arc::new(upstreams),
+            strategy: Strategy::Failover,
+            timeout: Forwarder::DEFAULT_TIMEOUT,
+            next: AtomicUsize::new(0),
+        }
+    }
+
+    pub fn strategy(mut self, strategy: Strategy) -> Forwarder {
+        self.strategy = strategy;
+        self
+    }
+
+    /// How long to wait for an upstream before it counts as failed
+    pub fn timeout(mut self, timeout: Duration) -> Forwarder {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn upstreams(&self) -> Vec<SocketAddr> {
+        self.upstreams
+            .iter()
+            .map(|upstream| upstream.addr)
+            .collect()
+    }
+
+    /// The smoothed round-trip time of each upstream, `None` until measured
+    pub fn round_trip_times(&self) -> Vec<Option<Duration>> {
+        self.upstreams
+            .iter()
+            .map(|upstream| match upstream.srtt.load(Ordering::Relaxed) {
+                0 => None,
+                micros => Some(Duration::from_micros(micros)),
+            })
+            .collect()
+    }
+
+    /// Relay `query` upstream and return the response under the query's ID
+    pub fn forward(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let mut relayed = query.clone();
+        relayed.header.id = random_id();
+
+        let mut response = match self.strategy {
+            Strategy::Failover => self.in_turn(&relayed, (0..self.upstreams.len()).collect()),
+            Strategy::RoundRobin => self.in_turn(&relayed, self.round_robin_order()),
+            Strategy::FastestRtt => self.in_turn(&relayed, self.fastest_order()),
+            Strategy::Race => self.race(&relayed),
+        }?;
+        response.header.id = query.header.id;
+
+        Ok(response)
+    }
+
+    /// The upstreams in order, starting after where the last query started
+    fn round_robin_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.upstreams.len()).collect();
+        let start = self.next.fetch_add(1, Ordering::Relaxed) % order.len();
+        order.rotate_left(start);
+        order
+    }
+
+    /// The upstreams by smoothed round-trip time, fastest first
+    fn fastest_order(&self) -> Vec<usize> {
+        let mut order: Vec<usize> = (0..self.upstreams.len()).collect();
+        // unmeasured upstreams sort first, so each gets measured
+        order.sort_by_key(|&i| self.upstreams[i].srtt.load(Ordering::Relaxed));
+        order
+    }
+
+    /// Try the upstreams at the indexes in `order` one after another
+    fn in_turn(&self, query: &DnsPacket, order: Vec<usize>) -> Result<DnsPacket, ResolveError> {
+        let mut last = None;
+        for i in order {
+            let result = exchange(&self.upstreams[i], query, self.timeout);
+            if matches!(&result, Ok(response) if usable(response)) {
+                return result;
+            }
+            keep_best(&mut last, result);
+        }
+
+        last.expect("a forwarder has an upstream")
+    }
+
+    /**
+        Send the query to every upstream at once and return the first usable
+        response. The slower exchanges finish in the background, still
+        updating the round-trip times.
+    */
+    fn race(&self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let (sender, receiver) = mpsc::channel();
+        for i in 0..self.upstreams.len() {
+            let upstreams = self.upstreams.clone();
+            let query = query.clone();
+            let timeout = self.timeout;
+            let sender = sender.clone();
+            thread::spawn(move || {
+                // the receiver is gone once the race is won
+                let _ = sender.send(exchange(&upstreams[i], &query, timeout));
+            });
+        }
+        drop(sender);
+
+        let mut last = None;
+        for result in receiver {
+            if matches!(&result, Ok(response) if usable(response)) {
+                return result;
+            }
+            keep_best(&mut last, result);
+        }
+
+        last.expect("a forwarder has an upstream")
+    }
+}
+
+impl RequestHandler for Forwarder {
+    fn handle(&self, query: &DnsPacket) -> DnsPacket {
+        self.forward(query).unwrap_or_else(|_| {
+            DnsPacket::response_to(query)
+                .recursion_available(true)
+                .r_code(RCode::ServFail)
+                .build()
+        })
+    }
+}
+
+/**
+    Send `query` to one upstream and fold the round-trip time into its
+    smoothed one, weighting the new sample by 1/8 as TCP does (RFC 6298).
+    A failure counts as a sample of twice the timeout.
+*/
+fn exchange(
+    upstream: &Upstream,
+    query: &DnsPacket,
+    timeout: Duration,
+) -> Result<DnsPacket, ResolveError> {
+    let resolver = StubResolver::new(upstream.addr)
+        .timeout(timeout)
+        .attempts(1);
+    let start = Instant::now();
+    let result = resolver.query(query);
+
+    let sample = match &result {
+        Ok(_) => start.elapsed(),
+        Err(_) => timeout * 2,
+    };
+    let sample = (sample.as_micros() as u64).max(1);
+    let _ = upstream
+        .srtt
+        .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |srtt| match srtt {
+            0 => Some(sample),
+            srtt => Some((srtt * 7 + sample) / 8),
+        });
+
+    result
+}
+
+/// Whether a response settles the query, rather than calling for another upstream
+fn usable(response: &DnsPacket) -> bool {
+    matches!(response.header.r_code, RCode::NoError | RCode::NameErr)
+}
+
+/// Remember the result of a failed upstream, an error response over no response at all
+fn keep_best(
+    last: &mut Option<Result<DnsPacket, ResolveError>>,
+    result: Result<DnsPacket, ResolveError>,
+) {
+    if result.is_ok() || !matches!(last, Some(Ok(_))) {
+        *last = Some(result);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+    use std::sync::Mutex;
+
+    use super::*;
+    use crate::protocol::dns_name::Name;
+    use crate::protocol::question_type_enum::QuestionType;
+    use crate::protocol::resource_type_enum::ResourceType;
+    use crate::server::dns_server::DnsServer;
+
+    /// An upstream answering with `r_code` after `delay`, remembering the IDs it was asked under
+    struct Fake {
+        r_code: RCode,
+        delay: Duration,
+        ids: Arc<Mutex<Vec<u16>>>,
+    }
+
+    impl RequestHandler for Fake {
+        fn handle(&self, query: &DnsPacket) -> DnsPacket {
+            self.ids.lock().unwrap().push(query.header.id);
+            thread::sleep(self.delay);
+            DnsPacket::response_to(query).r_code(self.r_code).build()
+        }
+    }
+
+    /// Serve a `Fake` on a port of its own, returning its address and the IDs it is asked under
+    fn fake(r_code: RCode, delay: Duration) -> (SocketAddr, Arc<Mutex<Vec<u16>>>) {
+        let ids = Arc::new(Mutex::new(Vec::new()));
+        let fake = Fake {
+            r_code,
+            delay,
+            ids: ids.clone(),
+        };
+        let server = DnsServer::bind("127.0.0.1:0".parse().unwrap(), fake).unwrap();
+        let addr = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        (addr, ids)
+    }
+
+    /// An address nothing answers on
+    fn dead() -> SocketAddr {
+        UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+    }
+
+    fn query(id: u16) -> DnsPacket {
+        let name: Name = "www.example.com".parse().unwrap();
+        DnsPacket::query()
+            .id(id)
+            .question(&name, QuestionType::Base(ResourceType::A))
+            .build()
+    }
+
+    fn asked(ids: &Mutex<Vec<u16>>) -> usize {
+        ids.lock().unwrap().len()
+    }
+
+    #[test]
+    fn fails_over_dead_and_failing_upstreams() {
+        let (failing, failing_ids) = fake(RCode::ServFail, Duration::ZERO);
+        let (good, good_ids) = fake(RCode::NameErr, Duration::ZERO);
+        let forwarder =
+            Forwarder::new(vec![dead(), failing, good]).timeout(Duration::from_millis(200));
+
+        let response = forwarder.forward(&query(1)).unwrap();
+        assert_eq!(response.header.r_code, RCode::NameErr);
+        assert_eq!((asked(&failing_ids), asked(&good_ids)), (1, 1));
+    }
+
+    #[test]
+    fn passes_on_the_last_error_response() {
+        let (failing, _) = fake(RCode::Refused, Duration::ZERO);
+        let forwarder = Forwarder::new(vec![failing, dead()]).timeout(Duration::from_millis(200));
+
+        let response = forwarder.forward(&query(1)).unwrap();
+        assert_eq!(response.header.r_code, RCode::Refused);
+    }
+
+    #[test]
+    fn round_robin_starts_at_the_next_upstream() {
+        let fakes: Vec<_> = (0..3)
+            .map(|_| fake(RCode::NoError, Duration::ZERO))
+            .collect();
+        let forwarder = Forwarder::new(fakes.iter().map(|(addr, _)| *addr).collect())
+            .strategy(Strategy::RoundRobin);
+
+        for expected in [[1, 0, 0], [1, 1, 0], [1, 1, 1], [2, 1, 1]] {
+            forwarder.forward(&query(1)).unwrap();
+            let counts: Vec<usize> = fakes.iter().map(|(_, ids)| asked(ids)).collect();
+            assert_eq!(counts, expected);
+        }
+    }
+
+    #[test]
+    fn fastest_rtt_prefers_the_faster_upstream_once_measured() {
+        let (slow, slow_ids) = fake(RCode::NoError, Duration::from_millis(100));
+        let (fast, fast_ids) = fake(RCode::NoError, Duration::ZERO);
+        let forwarder = Forwarder::new(vec![slow, fast]).strategy(Strategy::FastestRtt);
+
+        // each unmeasured upstream is tried once first
+        forwarder.forward(&query(1)).unwrap();
+        forwarder.forward(&query(2)).unwrap();
+        assert_eq!((asked(&slow_ids), asked(&fast_ids)), (1, 1));
+
+        let times = forwarder.round_trip_times();
+        assert!(times[1].unwrap() < times[0].unwrap());
+        for id in 3..6 {
+            forwarder.forward(&query(id)).unwrap();
+        }
+        assert_eq!((asked(&slow_ids), asked(&fast_ids)), (1, 4));
+    }
+
+    #[test]
+    fn race_takes_the_first_usable_response() {
+        let (slow, _) = fake(RCode::NoError, Duration::from_millis(300));
+        let (failing, _) = fake(RCode::ServFail, Duration::ZERO);
+        let (fast, _) = fake(RCode::NameErr, Duration::from_millis(50));
+        let forwarder = Forwarder::new(vec![slow, failing, fast]).strategy(Strategy::Race);
+
+        let response = forwarder.forward(&query(1)).unwrap();
+        assert_eq!(response.header.r_code, RCode::NameErr);
+    }
+
+    #[test]
+    fn restores_the_client_id() {
+        let (upstream, ids) = fake(RCode::NoError, Duration::ZERO);
+        let forwarder = Forwarder::new(vec![upstream]);
+
+        for id in [0, 7, 0xffff] {
+            let response = forwarder.forward(&query(id)).unwrap();
+            assert_eq!(response.header.id, id);
+        }
+        // each query went upstream under an ID of its own
+        let ids = ids.lock().unwrap();
+        assert_eq!(ids.len(), 3);
+        assert!(ids.iter().any(|&id| ![0, 7, 0xffff].contains(&id)));
+    }
+}
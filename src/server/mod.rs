@@ -0,0 +1,4 @@
+pub mod authority;
+pub mod dns_server;
+pub mod forwarder;
+pub mod zone;
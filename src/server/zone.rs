@@ -0,0 +1,200 @@
+use std::collections::BTreeMap;
+
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::question_type_enum::QuestionType;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+
+/// The outcome of looking a name and type up in a zone (RFC 1034 section 4.3.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Lookup {
+    /// The records of the type at the name, owned by the name even if a wildcard matched
+    Answer(Vec<DnsRecord>),
+    /// The name is an alias; the CNAME record is to be followed
+    CName(DnsRecord),
+    /// The name exists but has no records of the type
+    NoData,
+    /// The name does not exist
+    NxDomain,
+    /// The name lies below a zone cut; these are the NS records of the cut
+    Referral(Vec<DnsRecord>),
+}
+
+/**
+    The records of one zone, kept in memory by owner name.
+
+    Owners keep the case they were given in, while lookups ignore case as
+    names do. Nodes are held in canonical order, which puts the names below
+    a node right after it.
+*/
+#[derive(Debug, Clone)]
+pub struct Zone {
+    origin: Name,
+    nodes: BTreeMap<Name, Vec<DnsRecord>>,
+}
+
+impl Zone {
+    pub fn new(origin: Name) -> Zone {
+        Zone {
+            origin,
+            nodes: BTreeMap::new(),
+        }
+    }
+
+    /// A zone of `records` whose origin is the owner of their SOA record
+    pub fn from_records(records: Vec<DnsRecord>) -> Option<Zone> {
+        let soa = records
+            .iter()
+            .find(|record| record.r_type == ResourceType::SOA)?;
+
+        let mut zone = Zone::new(soa.name.clone());
+        for record in records {
+            zone.insert(record);
+        }
+        Some(zone)
+    }
+
+    pub fn origin(&self) -> &Name {
+        &self.origin
+    }
+
+    /// Add `record`, unless its owner lies outside the zone
+    pub fn insert(&mut self, record: DnsRecord) -> bool {
+        if !self.contains(&record.name) {
+            return false;
+        }
+
+        self.nodes
+            .entry(record.name.clone())
+            .or_default()
+            .push(record);
+        true
+    }
+
+    /// Whether `name` is at or below the origin
+    pub fn contains(&self, name: &Name) -> bool {
+        name.is_subdomain_of(&self.origin)
+    }
+
+    /// The records owned by `name` exactly, without wildcard matching
+    pub fn get(&self, name: &Name) -> &[DnsRecord] {
+        self.nodes.get(name).map_or(&[], Vec::as_slice)
+    }
+
+    pub fn soa(&self) -> Option<&DnsRecord> {
+        self.get(&self.origin)
+            .iter()
+            .find(|record| record.r_type == ResourceType::SOA)
+    }
+
+    pub fn records(&self) -> impl Iterator<Item = &DnsRecord> {
+        self.nodes.values().flatten()
+    }
+
+    /// Whether `name` owns records or is an empty non-terminal above some that do
+    pub fn name_exists(&self, name: &Name) -> bool {
+        self.nodes
+            .range(name..)
+            .next()
+            .is_some_and(|(node, _)| node.is_subdomain_of(name))
+    }
+
+    /**
+        Look up `q_type` records of `name`, which must be in the zone.
+
+        Zone cuts on the way down from the origin turn the lookup into a
+        referral. A name that does not exist is matched against the
+        wildcard at its closest encloser (RFC 4592 section 3.3.1).
+    */
+    pub fn lookup(&self, name: &Name, q_type: QuestionType) -> Lookup {
+        for cut in self.ancestors(name).iter().rev() {
+            let ns = self.of_type(cut, ResourceType::NS);
+            if *cut != self.origin && !ns.is_empty() {
+                return Lookup::Referral(ns);
+            }
+        }
+
+        if let Some(records) = self.nodes.get(name) {
+            return Self::select(records, name, q_type);
+        }
+        if self.name_exists(name) {
+            return Lookup::NoData;
+        }
+
+        let ancestors = self.ancestors(name);
+        let encloser = ancestors
+            .iter()
+            .skip(1)
+            .find(|ancestor| self.name_exists(ancestor))
+            .unwrap_or(&self.origin);
+        let wildcard = match encloser.prepend(b"*") {
+            Ok(wildcard) => wildcard,
+            Err(_) => return Lookup::NxDomain,
+        };
+
+        match self.nodes.get(&wildcard) {
+            Some(records) => Self::select(records, name, q_type),
+            None => Lookup::NxDomain,
+        }
+    }
+
+    /// The records of `r_type` owned by `name` exactly
+    pub fn of_type(&self, name: &Name, r_type: ResourceType) -> Vec<DnsRecord> {
+        self.get(name)
+            .iter()
+            .filter(|record| record.r_type == r_type)
+            .cloned()
+            .collect()
+    }
+
+    /// The answer from the records of one node, owned by `owner`
+    fn select(records: &[DnsRecord], owner: &Name, q_type: QuestionType) -> Lookup {
+        let synthesize = |record: &DnsRecord| DnsRecord {
+            name: owner.clone(),
+            ..record.clone()
+        };
+
+        let matching: Vec<DnsRecord> = records
+            .iter()
+            .filter(|record| type_matches(q_type, record.r_type))
+            .map(synthesize)
+            .collect();
+        if !matching.is_empty() {
+            return Lookup::Answer(matching);
+        }
+
+        match records.iter().find(|r| matches!(r.r_data, RData::CName(_))) {
+            Some(cname) => Lookup::CName(synthesize(cname)),
+            None => Lookup::NoData,
+        }
+    }
+
+    /// `name` and each of its ancestors down to the origin, nearest first
+    fn ancestors(&self, name: &Name) -> Vec<Name> {
+        let mut ancestors = vec![name.clone()];
+        let mut current = name.clone();
+        while current != self.origin {
+            current = match current.parent() {
+                Some(parent) => parent,
+                None => break,
+            };
+            ancestors.push(current.clone());
+        }
+        ancestors
+    }
+}
+
+/// Whether a record of `r_type` answers a question of `q_type`
+pub fn type_matches(q_type: QuestionType, r_type: ResourceType) -> bool {
+    match q_type {
+        QuestionType::Base(t) => t == r_type,
+        QuestionType::All => true,
+        QuestionType::MailB => matches!(
+            r_type,
+            ResourceType::MB | ResourceType::MG | ResourceType::MR
+        ),
+        QuestionType::MailA => matches!(r_type, ResourceType::MD | ResourceType::MF),
+        QuestionType::AxfR => false,
+    }
+}
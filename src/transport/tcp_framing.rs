@@ -0,0 +1,344 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::error::ResolveError;
+
+/**
+    Messages sent over TCP are prefixed with a two byte length field which
+    gives the message length, excluding the two byte length field (RFC 1035
+    section 4.2.2).
+
+    ```text
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    |                     LENGTH                    |
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    /                    MESSAGE                    /
+    /                                               /
+    +--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+--+
+    ```
+
+    The prefix and the message are written together, so they leave in one
+    segment where they fit (RFC 7766 section 8).
+*/
+pub fn write_message<W: Write>(writer: &mut W, message: &[u8]) -> io::Result<()> {
+    writer.write_all(&frame(message)?)?;
+    writer.flush()
+}
+
+/**
+    Read one length-prefixed message. `None` means the peer closed the
+    connection between messages; closing it in the middle of one is an
+    `UnexpectedEof` error.
+*/
+pub fn read_message<R: Read>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut len = [0; 2];
+    match reader.read(&mut len[..1])? {
+        0 => return Ok(None),
+        _ => reader.read_exact(&mut len[1..])?,
+    }
+
+    let mut message = vec![0; u16::from_be_bytes(len) as usize];
+    reader.read_exact(&mut message)?;
+    Ok(Some(message))
+}
+
+/// The message preceded by its length
+fn frame(message: &[u8]) -> io::Result<Vec<u8>> {
+    let len = u16::try_from(message.len())
+        .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "message exceeds 65535 octets"))?;
+
+    let mut framed = Vec::with_capacity(message.len() + 2);
+    framed.extend_from_slice(&len.to_be_bytes());
+    framed.extend_from_slice(message);
+    Ok(framed)
+}
+
+/// Whether `e` is a read or write that ran out of time
+pub fn is_timeout(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+/**
+    A TCP connection carrying length-prefixed DNS messages, for clients and
+    servers alike.
+
+    Several queries may be written before any response is read, and a
+    server may answer them in any order (RFC 7766 section 6.2.1.1);
+    `pipeline` pairs them up again. The idle timeout bounds every read and
+    write, so a server can close connections its clients have stopped
+    using (RFC 7766 section 6.2.3).
+*/
+pub struct TcpConnection {
+    stream: TcpStream,
+}
+
+impl TcpConnection {
+    /// How many messages answering none of its queries `pipeline` skips before giving up
+    pub const MAX_UNMATCHED: usize = 16;
+
+    pub fn new(stream: TcpStream) -> TcpConnection {
+        TcpConnection { stream }
+    }
+
+    /// Connect to `addr`, giving up after `timeout`, which also becomes the idle timeout
+    pub fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<TcpConnection> {
+        let stream = TcpStream::connect_timeout(&addr, timeout)?;
+        stream.set_nodelay(true)?;
+
+        let mut connection = TcpConnection::new(stream);
+        connection.set_idle_timeout(Some(timeout))?;
+        Ok(connection)
+    }
+
+    /// Bound the time every read and write may take, or remove the bound with `None`
+    pub fn set_idle_timeout(&mut self, timeout: Option<Duration>) -> io::Result<()> {
+        self.stream.set_read_timeout(timeout)?;
+        self.stream.set_write_timeout(timeout)
+    }
+
+    pub fn peer_addr(&self) -> io::Result<SocketAddr> {
+        self.stream.peer_addr()
+    }
+
+    pub fn get_ref(&self) -> &TcpStream {
+        &self.stream
+    }
+
+    pub fn into_inner(self) -> TcpStream {
+        self.stream
+    }
+
+    pub fn write_message(&mut self, message: &[u8]) -> io::Result<()> {
+        write_message(&mut self.stream, message)
+    }
+
+    pub fn read_message(&mut self) -> io::Result<Option<Vec<u8>>> {
+        read_message(&mut self.stream)
+    }
+
+    pub fn write_packet(&mut self, packet: &DnsPacket) -> Result<(), ResolveError> {
+        Ok(self.write_message(&packet.to_bytes()?)?)
+    }
+
+    /**
+        Read and decode the next message. `None` means the peer closed the
+        connection; running into the idle timeout is a `Timeout` error.
+    */
+    pub fn read_packet(&mut self) -> Result<Option<DnsPacket>, ResolveError> {
+        match self.read_message() {
+            Ok(Some(message)) => Ok(Some(DnsPacket::from_bytes(&message)?)),
+            Ok(None) => Ok(None),
+            Err(e) if is_timeout(&e) => Err(ResolveError::Timeout { attempts: 1 }),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /**
+        Send all of `queries` at once, then read until every one has its
+        response, which are returned in the order of the queries. Up to
+        `MAX_UNMATCHED` messages that answer none of them are skipped, so a
+        peer cannot keep the connection busy forever; the queries should
+        carry distinct IDs for the responses to be told apart.
+    */
+    pub fn pipeline(&mut self, queries: &[DnsPacket]) -> Result<Vec<DnsPacket>, ResolveError> {
+        let mut frames = Vec::new();
+        for query in queries {
+            frames.extend_from_slice(&frame(&query.to_bytes()?)?);
+        }
+        self.stream.write_all(&frames)?;
+        self.stream.flush()?;
+
+        let mut responses: Vec<Option<DnsPacket>> = vec![None; queries.len()];
+        let mut outstanding = queries.len();
+        let mut unmatched = 0;
+        while outstanding > 0 {
+            let response = match self.read_packet()? {
+                Some(response) => response,
+                None => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            };
+
+            let slot = queries
+                .iter()
+                .zip(responses.iter_mut())
+                .find(|(query, slot)| slot.is_none() && response.is_response_to(query));
+            match slot {
+                Some((_, slot)) => {
+                    *slot = Some(response);
+                    outstanding -= 1;
+                }
+                None if unmatched < Self::MAX_UNMATCHED => unmatched += 1,
+                None => {
+                    let e =
+                        io::Error::new(io::ErrorKind::InvalidData, "too many unmatched messages");
+                    return Err(e.into());
+                }
+            }
+        }
+
+        Ok(responses.into_iter().flatten().collect())
+    }
+
+    /// Send `query` and read until its response arrives
+    pub fn exchange(&mut self, query: &DnsPacket) -> Result<DnsPacket, ResolveError> {
+        let mut responses = self.pipeline(std::slice::from_ref(query))?;
+        Ok(responses.remove(0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::net::TcpListener;
+    use std::thread;
+
+    use super::*;
+    use crate::protocol::dns_name::Name;
+    use crate::protocol::question_type_enum::QuestionType;
+    use crate::protocol::resource_type_enum::ResourceType;
+
+    fn query(id: u16, owner: &str) -> DnsPacket {
+        let name: Name = owner.parse().unwrap();
+        DnsPacket::query()
+            .id(id)
+            .question(&name, QuestionType::Base(ResourceType::A))
+            .build()
+    }
+
+    /// A connection to a server that runs `serve` on the accepted end
+    fn serve<F>(serve: F) -> TcpConnection
+    where
+        F: FnOnce(TcpConnection) + Send + 'static,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        thread::spawn(move || serve(TcpConnection::new(listener.accept().unwrap().0)));
+        TcpConnection::connect(addr, Duration::from_secs(5)).unwrap()
+    }
+
+    #[test]
+    fn frames_messages_with_their_length() {
+        let mut buf = Vec::new();
+        write_message(&mut buf, &[1, 2, 3]).unwrap();
+        write_message(&mut buf, &[]).unwrap();
+        assert_eq!(buf, [0, 3, 1, 2, 3, 0, 0]);
+
+        let mut reader = Cursor::new(buf);
+        assert_eq!(read_message(&mut reader).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(read_message(&mut reader).unwrap(), Some(Vec::new()));
+        // the connection closed between messages
+        assert_eq!(read_message(&mut reader).unwrap(), None);
+
+        let too_long = vec![0; 65536];
+        let e = write_message(&mut Vec::new(), &too_long).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[test]
+    fn closing_inside_a_message_is_an_error() {
+        for truncated in [&[0][..], &[0, 5, 1, 2]] {
+            let e = read_message(&mut Cursor::new(truncated)).unwrap_err();
+            assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+        }
+    }
+
+    #[test]
+    fn pipelines_responses_arriving_in_any_order() {
+        let queries = [query(1, "one.example"), query(2, "two.example")];
+        let mut connection = serve(|mut server| {
+            let first = server.read_packet().unwrap().unwrap();
+            let second = server.read_packet().unwrap().unwrap();
+
+            let unrelated = DnsPacket::response_to(&query(3, "three.example")).build();
+            server.write_packet(&unrelated).unwrap();
+            server
+                .write_packet(&DnsPacket::response_to(&second).build())
+                .unwrap();
+            server
+                .write_packet(&DnsPacket::response_to(&first).build())
+                .unwrap();
+        });
+
+        let responses = connection.pipeline(&queries).unwrap();
+        let ids: Vec<u16> = responses
+            .iter()
+            .map(|response| response.header.id)
+            .collect();
+        assert_eq!(ids, [1, 2]);
+    }
+
+    #[test]
+    fn gives_up_on_unmatched_messages() {
+        let mut connection = serve(|mut server| {
+            server.read_packet().unwrap();
+            let unrelated = DnsPacket::response_to(&query(3, "three.example")).build();
+            for _ in 0..=TcpConnection::MAX_UNMATCHED {
+                server.write_packet(&unrelated).unwrap();
+            }
+            // keep the connection open, so only the cap ends the pipeline
+            let _ = server.read_packet();
+        });
+
+        let result = connection.exchange(&query(1, "one.example"));
+        assert!(
+            matches!(result, Err(ResolveError::Io(e)) if e.kind() == io::ErrorKind::InvalidData)
+        );
+    }
+
+    #[test]
+    fn idle_reads_time_out() {
+        let mut connection = serve(|mut server| {
+            let _ = server.read_packet();
+        });
+        connection
+            .set_idle_timeout(Some(Duration::from_millis(100)))
+            .unwrap();
+
+        assert!(matches!(
+            connection.read_packet(),
+            Err(ResolveError::Timeout { attempts: 1 })
+        ));
+    }
+}
+
+#[cfg(feature = "tokio")]
+pub use self::tokio_framing::{read_message_async, write_message_async};
+
+#[cfg(feature = "tokio")]
+mod tokio_framing {
+    use std::io;
+
+    use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+    /// `write_message` for Tokio writers
+    pub async fn write_message_async<W: AsyncWrite + Unpin>(
+        writer: &mut W,
+        message: &[u8],
+    ) -> io::Result<()> {
+        writer.write_all(&super::frame(message)?).await?;
+        writer.flush().await
+    }
+
+    /**
+        `read_message` for Tokio readers. Dropping the future part way
+        through a message loses the octets read so far, so a connection
+        whose read was cancelled must not be read from again.
+    */
+    pub async fn read_message_async<R: AsyncRead + Unpin>(
+        reader: &mut R,
+    ) -> io::Result<Option<Vec<u8>>> {
+        let mut len = [0; 2];
+        match reader.read(&mut len[..1]).await? {
+            0 => return Ok(None),
+            _ => reader.read_exact(&mut len[1..]).await?,
+        };
+
+        let mut message = vec![0; u16::from_be_bytes(len) as usize];
+        reader.read_exact(&mut message).await?;
+        Ok(Some(message))
+    }
+}
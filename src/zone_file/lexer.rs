@@ -0,0 +1,192 @@
+use crate::error::ZoneFileErrorKind;
+
+/// A field of an entry, with its quotes removed but its escapes kept for the parser
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Token {
+    pub text: String,
+    /// Whether any of the token was quoted, which keeps `@` or `\#` from meaning anything
+    pub quoted: bool,
+}
+
+/// One logical line of a master file, which parentheses may spread over several lines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    /// The line the entry starts on, counting from 1
+    pub line: usize,
+    /// Whether the entry starts with blank space, leaving the owner to be inherited
+    pub blank_owner: bool,
+    pub tokens: Vec<Token>,
+}
+
+/**
+    Split master file text into entries (RFC 1035 section 5.1).
+
+    Fields are separated by blanks. `;` starts a comment running to the end
+    of the line, parentheses group fields over line breaks, and double
+    quotes let a field hold blanks and special characters. A backslash
+    escapes the next character, and stays in the token for the parser to
+    interpret, since `\.` means something else in a name than a `.` does.
+    Errors come with the line they occur on.
+*/
+pub fn entries(text: &str) -> Result<Vec<Entry>, (usize, ZoneFileErrorKind)> {
+    let syntax = |line, message: &str| (line, ZoneFileErrorKind::Syntax(message.to_string()));
+
+    let mut entries = Vec::new();
+    let mut entry: Option<Entry> = None;
+    let mut token: Option<Token> = None;
+
+    let mut line = 1;
+    let mut depth = 0;
+    let mut line_start = true;
+
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        let current = entry.get_or_insert_with(|| Entry {
+            line,
+            blank_owner: line_start && (c == ' ' || c == '\t'),
+            tokens: Vec::new(),
+        });
+        line_start = false;
+
+        match c {
+            '\n' => {
+                current.tokens.extend(token.take());
+                line += 1;
+                line_start = true;
+                if depth == 0 {
+                    let finished = entry.take().unwrap();
+                    if !finished.tokens.is_empty() {
+                        entries.push(finished);
+                    }
+                }
+            }
+            ' ' | '\t' | '\r' => current.tokens.extend(token.take()),
+            ';' => while chars.next_if(|&c| c != '\n').is_some() {},
+            '(' => {
+                current.tokens.extend(token.take());
+                depth += 1;
+            }
+            ')' => {
+                current.tokens.extend(token.take());
+                if depth == 0 {
+                    return Err(syntax(line, "unbalanced )"));
+                }
+                depth -= 1;
+            }
+            '"' => {
+                let token = token.get_or_insert_with(Token::default);
+                token.quoted = true;
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => {
+                            let escaped = chars
+                                .next()
+                                .ok_or_else(|| syntax(line, "escape at the end of the file"))?;
+                            if escaped == '\n' {
+                                line += 1;
+                            }
+                            token.text.push('\\');
+                            token.text.push(escaped);
+                        }
+                        Some('\n') | None => return Err(syntax(line, "unterminated quote")),
+                        Some(c) => token.text.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let escaped = chars
+                    .next()
+                    .ok_or_else(|| syntax(line, "escape at the end of the file"))?;
+                if escaped == '\n' {
+                    line += 1;
+                }
+                let token = token.get_or_insert_with(Token::default);
+                token.text.push('\\');
+                token.text.push(escaped);
+            }
+            c => token.get_or_insert_with(Token::default).text.push(c),
+        }
+    }
+
+    if let Some(mut last) = entry {
+        last.tokens.extend(token);
+        if depth != 0 {
+            return Err(syntax(last.line, "unbalanced ("));
+        }
+        if !last.tokens.is_empty() {
+            entries.push(last);
+        }
+    }
+
+    Ok(entries)
+}
+
+impl Token {
+    /// Whether the token is `text` as written, unquoted and unescaped
+    pub fn is(&self, text: &str) -> bool {
+        !self.quoted && self.text == text
+    }
+}
+
+/**
+    Resolve the escapes of a token into octets: `\DDD` is the octet with
+    decimal value DDD, and a backslash before any other character stands
+    for that character.
+*/
+pub fn unescape(text: &str) -> Result<Vec<u8>, ZoneFileErrorKind> {
+    let invalid = || ZoneFileErrorKind::InvalidField {
+        field: "escape",
+        value: text.to_string(),
+    };
+
+    let mut octets = Vec::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            let mut utf8 = [0; 4];
+            octets.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            continue;
+        }
+
+        match chars.next().ok_or_else(invalid)? {
+            d if d.is_ascii_digit() => {
+                let digits: String = [Some(d), chars.next(), chars.next()]
+                    .into_iter()
+                    .collect::<Option<String>>()
+                    .ok_or_else(invalid)?;
+                if !digits.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid());
+                }
+                octets.push(digits.parse::<u8>().map_err(|_| invalid())?);
+            }
+            c => {
+                let mut utf8 = [0; 4];
+                octets.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+            }
+        }
+    }
+
+    Ok(octets)
+}
+
+/// Split `text` at every `separator` that is not escaped, keeping the escapes
+pub fn split_unescaped(text: &str, separator: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    let mut escaped = false;
+
+    for (i, c) in text.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == separator {
+            parts.push(&text[start..i]);
+            start = i + c.len_utf8();
+        }
+    }
+    parts.push(&text[start..]);
+
+    parts
+}
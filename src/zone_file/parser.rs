@@ -0,0 +1,875 @@
+use std::fs;
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use crate::domain::dns_packet::DnsPacket;
+use crate::domain::dns_packet_buffer::DnsPacketBuffer;
+use crate::encoding::{base64_decode, hex_decode};
+use crate::error::{ZoneFileError, ZoneFileErrorKind};
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::resource_class_enum::ResourceClass;
+use crate::protocol::resource_data_enum::RData;
+use crate::protocol::resource_type_enum::ResourceType;
+use crate::protocol::svc_param_enum::SvcParam;
+use crate::zone_file::lexer::{entries, split_unescaped, unescape, Entry, Token};
+
+type Kind = ZoneFileErrorKind;
+
+/**
+    Reads master files (RFC 1035 section 5) into records.
+
+    Besides records, the control entries `$ORIGIN`, `$INCLUDE`, `$TTL`
+    (RFC 2308 section 4) and BIND's `$GENERATE` are understood. A record
+    may leave out its owner, TTL and class: the owner and class are those
+    of the record before, and the TTL is the `$TTL` default, or failing
+    that the last TTL given. RDATA of any type may be written in the
+    generic `\# length hex` form of RFC 3597 section 5.
+
+    Owner names keep the case they are written in; names compare without
+    regard to case wherever it matters.
+*/
+#[derive(Debug, Clone)]
+pub struct ZoneParser {
+    origin: Option<Name>,
+    default_ttl: Option<u32>,
+    last_ttl: Option<u32>,
+    last_class: ResourceClass,
+    last_owner: Option<Name>,
+    depth: usize,
+    records: Vec<DnsRecord>,
+}
+
+impl ZoneParser {
+    /// How deep `$INCLUDE` files may nest
+    pub const MAX_INCLUDE_DEPTH: usize = 16;
+    /// How many records one `$GENERATE` may produce
+    pub const MAX_GENERATE: usize = 65536;
+
+    pub fn new() -> ZoneParser {
+        ZoneParser {
+            origin: None,
+            default_ttl: None,
+            last_ttl: None,
+            last_class: ResourceClass::IN,
+            last_owner: None,
+            depth: 0,
+            records: Vec::new(),
+        }
+    }
+
+    /// The origin relative names are completed with until a `$ORIGIN` entry
+    pub fn origin(mut self, origin: Name) -> ZoneParser {
+        self.origin = Some(origin);
+        self
+    }
+
+    /// The TTL of records without one until a `$TTL` entry
+    pub fn default_ttl(mut self, ttl: u32) -> ZoneParser {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    /// Parse master file text, with `$INCLUDE` paths relative to the working directory
+    pub fn parse(mut self, text: &str) -> Result<Vec<DnsRecord>, ZoneFileError> {
+        self.parse_text(text, None)?;
+        Ok(self.records)
+    }
+
+    /// Parse a master file, with `$INCLUDE` paths relative to its directory
+    pub fn parse_file(mut self, path: impl AsRef<Path>) -> Result<Vec<DnsRecord>, ZoneFileError> {
+        let path = path.as_ref();
+        let text = fs::read_to_string(path).map_err(|e| ZoneFileError {
+            path: None,
+            line: 0,
+            kind: Kind::Include {
+                path: path.to_path_buf(),
+                message: e.to_string(),
+            },
+        })?;
+
+        self.parse_text(&text, Some(path))?;
+        Ok(self.records)
+    }
+
+    fn parse_text(&mut self, text: &str, path: Option<&Path>) -> Result<(), ZoneFileError> {
+        let error = |line, kind| ZoneFileError {
+            path: path.map(Path::to_path_buf),
+            line,
+            kind,
+        };
+
+        for entry in entries(text).map_err(|(line, kind)| error(line, kind))? {
+            let first = &entry.tokens[0];
+            if entry.blank_owner || first.quoted || !first.text.starts_with('$') {
+                self.record(entry.blank_owner, &entry.tokens)
+                    .map_err(|kind| error(entry.line, kind))?;
+                continue;
+            }
+
+            match first.text.to_ascii_uppercase().as_str() {
+                "$INCLUDE" => self.include(&entry, path)?,
+                directive => self
+                    .directive(directive, &entry.tokens[1..])
+                    .map_err(|kind| error(entry.line, kind))?,
+            }
+        }
+
+        Ok(())
+    }
+
+    fn directive(&mut self, directive: &str, tokens: &[Token]) -> Result<(), Kind> {
+        let mut fields = Fields::new(tokens);
+        match directive {
+            "$ORIGIN" => self.origin = Some(self.name(fields.next("origin")?)?),
+            "$TTL" => self.default_ttl = Some(ttl(fields.next("TTL")?)?),
+            "$GENERATE" => return self.generate(tokens),
+            _ => return Err(Kind::UnknownDirective(directive.to_string())),
+        }
+        fields.finish()
+    }
+
+    /**
+        Parse the file of a `$INCLUDE` entry in place. The origin it gives
+        applies to that file only, and the origin and owner of the
+        including file carry on after it (RFC 1035 section 5.1).
+    */
+    fn include(&mut self, entry: &Entry, path: Option<&Path>) -> Result<(), ZoneFileError> {
+        let error = |kind| ZoneFileError {
+            path: path.map(Path::to_path_buf),
+            line: entry.line,
+            kind,
+        };
+
+        let mut fields = Fields::new(&entry.tokens[1..]);
+        let file = fields.next("file name").map_err(error)?;
+        let file = String::from_utf8_lossy(&unescape(&file.text).map_err(error)?).into_owned();
+        let origin = match fields.next_if_any() {
+            Some(token) => Some(self.name(token).map_err(error)?),
+            None => None,
+        };
+        fields.finish().map_err(error)?;
+
+        if self.depth >= Self::MAX_INCLUDE_DEPTH {
+            return Err(error(Kind::IncludeDepth {
+                limit: Self::MAX_INCLUDE_DEPTH,
+            }));
+        }
+        let file = match path.and_then(Path::parent) {
+            Some(directory) => directory.join(&file),
+            None => PathBuf::from(&file),
+        };
+        let text = fs::read_to_string(&file).map_err(|e| {
+            error(Kind::Include {
+                path: file.clone(),
+                message: e.to_string(),
+            })
+        })?;
+
+        let saved_origin = self.origin.clone();
+        let saved_owner = self.last_owner.clone();
+        if origin.is_some() {
+            self.origin = origin;
+        }
+
+        self.depth += 1;
+        let result = self.parse_text(&text, Some(&file));
+        self.depth -= 1;
+
+        self.origin = saved_origin;
+        self.last_owner = saved_owner;
+        result
+    }
+
+    /**
+        `$GENERATE start-stop[/step] lhs [ttl] [class] type rhs` adds one
+        record for every value in the range, with `$` in the owner and the
+        RDATA replaced by the value. `${offset,width,base}` modifies it,
+        with base `d`, `o`, `x`, `X`, or `n`/`N` for nibbles in reverse
+        order as in ip6.arpa names, and `\$` stands for a dollar sign.
+    */
+    fn generate(&mut self, tokens: &[Token]) -> Result<(), Kind> {
+        let invalid = |message: &str| Kind::Generate(message.to_string());
+
+        let (range, template) = match tokens {
+            [range, lhs, rest @ ..] if !rest.is_empty() => (range, (lhs, rest)),
+            _ => return Err(invalid("expected a range, an owner and a record")),
+        };
+
+        let (bounds, step) = match range.text.split_once('/') {
+            Some((bounds, step)) => (bounds, step),
+            None => (range.text.as_str(), "1"),
+        };
+        let (start, stop) = bounds
+            .split_once('-')
+            .ok_or_else(|| invalid("range is not start-stop"))?;
+        let start: u32 = number(start, "range start")?;
+        let stop: u32 = number(stop, "range stop")?;
+        let step: u32 = number(step, "range step")?;
+        if start > stop || step == 0 {
+            return Err(invalid("empty range"));
+        }
+        if ((stop - start) / step) as usize >= Self::MAX_GENERATE {
+            return Err(invalid("range too large"));
+        }
+
+        let (lhs, rest) = template;
+        for value in (start..=stop).step_by(step as usize) {
+            let mut record = vec![substitute(lhs, value)?];
+            for token in rest {
+                record.push(substitute(token, value)?);
+            }
+            self.record(false, &record)?;
+        }
+
+        Ok(())
+    }
+
+    fn record(&mut self, blank_owner: bool, tokens: &[Token]) -> Result<(), Kind> {
+        let mut fields = Fields::new(tokens);
+
+        let owner = if blank_owner {
+            self.last_owner.clone().ok_or(Kind::MissingOwner)?
+        } else {
+            self.name(fields.next("owner")?)?
+        };
+
+        let mut explicit_ttl = None;
+        let mut explicit_class = None;
+        for _ in 0..2 {
+            let token = match fields.peek() {
+                Some(token) if !token.quoted => token,
+                _ => break,
+            };
+            if explicit_ttl.is_none() && token.text.starts_with(|c: char| c.is_ascii_digit()) {
+                explicit_ttl = Some(ttl(token)?);
+            } else if let Some(r_class) = explicit_class
+                .is_none()
+                .then(|| ResourceClass::from_mnemonic(&token.text))
+                .flatten()
+            {
+                explicit_class = Some(r_class);
+            } else {
+                break;
+            }
+            fields.next("")?;
+        }
+
+        let r_type = fields.next("type")?;
+        let r_type = ResourceType::from_mnemonic(&r_type.text)
+            .ok_or_else(|| Kind::UnknownType(r_type.text.clone()))?;
+
+        if let Some(ttl) = explicit_ttl {
+            self.last_ttl = Some(ttl);
+        }
+        let ttl = explicit_ttl
+            .or(self.default_ttl)
+            .or(self.last_ttl)
+            .ok_or(Kind::MissingTtl)?;
+        if let Some(r_class) = explicit_class {
+            self.last_class = r_class;
+        }
+        let r_class = self.last_class;
+
+        let r_data = self.r_data(r_type, r_class, &mut fields)?;
+        fields.finish()?;
+
+        self.last_owner = Some(owner.clone());
+        self.records.push(DnsRecord {
+            name: owner,
+            r_type,
+            r_class,
+            ttl,
+            rd_length: 0,
+            r_data,
+        });
+
+        Ok(())
+    }
+
+    fn r_data(
+        &self,
+        r_type: ResourceType,
+        r_class: ResourceClass,
+        fields: &mut Fields,
+    ) -> Result<RData, Kind> {
+        if fields.peek().is_some_and(|token| token.is("\\#")) {
+            fields.next("")?;
+            return generic(r_type, r_class, fields);
+        }
+
+        let r_data = match r_type {
+            ResourceType::A => RData::A(parse(fields.next("address")?, "IPv4 address")?),
+            ResourceType::NS => RData::NS(self.name(fields.next("name server")?)?),
+            ResourceType::MD => RData::MD(self.name(fields.next("mail destination")?)?),
+            ResourceType::MF => RData::MF(self.name(fields.next("mail forwarder")?)?),
+            ResourceType::CName => RData::CName(self.name(fields.next("canonical name")?)?),
+            ResourceType::SOA => RData::SOA {
+                m_name: self.name(fields.next("primary name server")?)?,
+                r_name: self.name(fields.next("responsible mailbox")?)?,
+                serial: parse(fields.next("serial")?, "serial")?,
+                refresh: ttl(fields.next("refresh")?)?,
+                retry: ttl(fields.next("retry")?)?,
+                expire: ttl(fields.next("expire")?)?,
+                minimum: ttl(fields.next("minimum")?)?,
+            },
+            ResourceType::MB => RData::MB(self.name(fields.next("mailbox host")?)?),
+            ResourceType::MG => RData::MG(self.name(fields.next("mail group member")?)?),
+            ResourceType::MR => RData::MR(self.name(fields.next("mailbox rename")?)?),
+            ResourceType::WKS => {
+                let address = parse(fields.next("address")?, "IPv4 address")?;
+                let protocol = fields.next("protocol")?;
+                let protocol = match protocol.text.to_ascii_lowercase().as_str() {
+                    "tcp" => 6,
+                    "udp" => 17,
+                    _ => parse(protocol, "protocol")?,
+                };
+
+                let mut bit_map: Vec<u8> = Vec::new();
+                while let Some(port) = fields.next_if_any() {
+                    let port: u16 = parse(port, "port")?;
+                    let index = port as usize / 8;
+                    if bit_map.len() <= index {
+                        bit_map.resize(index + 1, 0);
+                    }
+                    bit_map[index] |= 0x80 >> (port % 8);
+                }
+
+                RData::WKS {
+                    address,
+                    protocol,
+                    bit_map,
+                }
+            }
+            ResourceType::PTR => RData::PTR(self.name(fields.next("domain name")?)?),
+            ResourceType::HInfo => RData::HInfo {
+                cpu: character_string(fields.next("CPU")?)?,
+                os: character_string(fields.next("OS")?)?,
+            },
+            ResourceType::MInfo => RData::MInfo {
+                r_mail_bx: self.name(fields.next("responsible mailbox")?)?,
+                e_mail_bx: self.name(fields.next("error mailbox")?)?,
+            },
+            ResourceType::MX => RData::MX {
+                preference: parse(fields.next("preference")?, "preference")?,
+                exchange: self.name(fields.next("exchange")?)?,
+            },
+            ResourceType::Txt => {
+                let mut strings = vec![character_string(fields.next("text")?)?];
+                while let Some(token) = fields.next_if_any() {
+                    strings.push(character_string(token)?);
+                }
+                RData::Txt(strings)
+            }
+            ResourceType::AAAA => RData::AAAA(parse(fields.next("address")?, "IPv6 address")?),
+            ResourceType::SRV => RData::SRV {
+                priority: parse(fields.next("priority")?, "priority")?,
+                weight: parse(fields.next("weight")?, "weight")?,
+                port: parse(fields.next("port")?, "port")?,
+                target: self.name(fields.next("target")?)?,
+            },
+            ResourceType::NAPTR => RData::NAPTR {
+                order: parse(fields.next("order")?, "order")?,
+                preference: parse(fields.next("preference")?, "preference")?,
+                flags: character_string(fields.next("flags")?)?,
+                services: character_string(fields.next("services")?)?,
+                regexp: character_string(fields.next("regexp")?)?,
+                replacement: self.name(fields.next("replacement")?)?,
+            },
+            ResourceType::DName => RData::DName(self.name(fields.next("target")?)?),
+            ResourceType::SSHFP => RData::SSHFP {
+                algorithm: parse(fields.next("algorithm")?, "algorithm")?,
+                fp_type: parse(fields.next("fingerprint type")?, "fingerprint type")?,
+                fingerprint: hex(fields, "fingerprint")?,
+            },
+            ResourceType::TLSA => RData::TLSA {
+                cert_usage: parse(fields.next("certificate usage")?, "certificate usage")?,
+                selector: parse(fields.next("selector")?, "selector")?,
+                matching_type: parse(fields.next("matching type")?, "matching type")?,
+                cert_data: hex(fields, "certificate association data")?,
+            },
+            ResourceType::SVCB | ResourceType::HTTPS => {
+                let priority = parse(fields.next("priority")?, "priority")?;
+                let target = self.name(fields.next("target")?)?;
+                let mut params = Vec::new();
+                while let Some(token) = fields.next_if_any() {
+                    params.push(svc_param(token)?);
+                }
+
+                match r_type {
+                    ResourceType::SVCB => RData::SVCB {
+                        priority,
+                        target,
+                        params,
+                    },
+                    _ => RData::HTTPS {
+                        priority,
+                        target,
+                        params,
+                    },
+                }
+            }
+            ResourceType::CAA => RData::CAA {
+                flags: parse(fields.next("flags")?, "flags")?,
+                tag: unescape(&fields.next("tag")?.text)?,
+                value: unescape(&fields.next("value")?.text)?,
+            },
+            ResourceType::Null | ResourceType::OPT | ResourceType::Unknown(_) => {
+                return Err(Kind::MissingField("\\# generic RDATA"));
+            }
+        };
+
+        Ok(r_data)
+    }
+
+    /**
+        A domain name, completed with the origin unless it ends in an
+        unescaped dot. `@` is the origin itself.
+    */
+    fn name(&self, token: &Token) -> Result<Name, Kind> {
+        let origin = || {
+            self.origin.clone().ok_or_else(|| Kind::MissingOrigin {
+                name: token.text.clone(),
+            })
+        };
+        if token.is("@") {
+            return origin();
+        }
+
+        let (name, absolute) = Name::parse(&token.text).map_err(Kind::Name)?;
+        if absolute {
+            return Ok(name);
+        }
+        name.append(&origin()?).map_err(Kind::Name)
+    }
+}
+
+impl Default for ZoneParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The tokens of an entry, taken field by field
+struct Fields<'a> {
+    tokens: &'a [Token],
+}
+
+impl<'a> Fields<'a> {
+    fn new(tokens: &'a [Token]) -> Fields<'a> {
+        Fields { tokens }
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.first()
+    }
+
+    /// The next field, which the entry must have
+    fn next(&mut self, field: &'static str) -> Result<&'a Token, Kind> {
+        self.next_if_any().ok_or(Kind::MissingField(field))
+    }
+
+    fn next_if_any(&mut self) -> Option<&'a Token> {
+        let (first, rest) = self.tokens.split_first()?;
+        self.tokens = rest;
+        Some(first)
+    }
+
+    /// The remaining fields run together, as hexadecimal may be split by blanks
+    fn concat(&mut self) -> String {
+        let text = self
+            .tokens
+            .iter()
+            .map(|token| token.text.as_str())
+            .collect();
+        self.tokens = &[];
+        text
+    }
+
+    /// Fail if any field is left
+    fn finish(&self) -> Result<(), Kind> {
+        match self.peek() {
+            Some(token) => Err(Kind::TrailingData(token.text.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+/// An unsigned number in decimal, without the sign `parse` would allow
+fn number<T: FromStr>(text: &str, field: &'static str) -> Result<T, Kind> {
+    let invalid = || Kind::InvalidField {
+        field,
+        value: text.to_string(),
+    };
+    if text.is_empty() || !text.bytes().all(|c| c.is_ascii_digit()) {
+        return Err(invalid());
+    }
+    text.parse().map_err(|_| invalid())
+}
+
+/// A field parsed with `FromStr`, numbers restricted to plain decimal
+fn parse<T: FromStr>(token: &Token, field: &'static str) -> Result<T, Kind> {
+    if token.text.starts_with(|c: char| c.is_ascii_digit()) && !token.text.contains(['.', ':']) {
+        return number(&token.text, field);
+    }
+    token.text.parse().map_err(|_| Kind::InvalidField {
+        field,
+        value: token.text.clone(),
+    })
+}
+
+/**
+    A TTL in seconds, or in BIND's units, e.g. `1h30m`: `w`eeks, `d`ays,
+    `h`ours, `m`inutes and `s`econds.
+*/
+fn ttl(token: &Token) -> Result<u32, Kind> {
+    let text = token.text.as_str();
+    let invalid = || Kind::InvalidTtl(text.to_string());
+    if text.bytes().all(|c| c.is_ascii_digit()) {
+        return text.parse().map_err(|_| invalid());
+    }
+
+    let mut total: u64 = 0;
+    let mut value: Option<u64> = None;
+    for c in text.chars() {
+        if let Some(digit) = c.to_digit(10) {
+            value = Some(value.unwrap_or(0) * 10 + digit as u64);
+            if value > Some(u32::MAX as u64) {
+                return Err(invalid());
+            }
+            continue;
+        }
+
+        let unit = match c.to_ascii_lowercase() {
+            'w' => 604800,
+            'd' => 86400,
+            'h' => 3600,
+            'm' => 60,
+            's' => 1,
+            _ => return Err(invalid()),
+        };
+        total += value.take().ok_or_else(invalid)? * unit;
+    }
+    total += value.unwrap_or(0);
+
+    u32::try_from(total).map_err(|_| invalid())
+}
+
+/// A <character-string>, at most 255 octets once its escapes are resolved
+fn character_string(token: &Token) -> Result<Vec<u8>, Kind> {
+    let string = unescape(&token.text)?;
+    if string.len() > 0xFF {
+        return Err(Kind::InvalidField {
+            field: "character string",
+            value: token.text.clone(),
+        });
+    }
+    Ok(string)
+}
+
+/// The remaining fields as one hexadecimal string
+fn hex(fields: &mut Fields, field: &'static str) -> Result<Vec<u8>, Kind> {
+    let text = fields.concat();
+    hex_decode(&text).ok_or(Kind::InvalidField { field, value: text })
+}
+
+/**
+    RDATA in the generic form: its length in octets and then the octets in
+    hexadecimal. Known types are decoded from the octets, so the record is
+    the same as if it had been written in the type's own format.
+*/
+fn generic(
+    r_type: ResourceType,
+    r_class: ResourceClass,
+    fields: &mut Fields,
+) -> Result<RData, Kind> {
+    let length: u16 = parse(fields.next("RDATA length")?, "RDATA length")?;
+    let data = hex(fields, "RDATA")?;
+    if data.len() != length as usize {
+        return Err(Kind::InvalidField {
+            field: "RDATA length",
+            value: length.to_string(),
+        });
+    }
+
+    match r_type {
+        ResourceType::Unknown(_) => Ok(RData::Unknown { r_type, data }),
+        _ => {
+            let mut buffer = DnsPacketBuffer::new(&data);
+            DnsPacket::decode_r_data(&mut buffer, r_type, r_class, length).map_err(Kind::RData)
+        }
+    }
+}
+
+/// A SvcParam in the key=value form of RFC 9460 appendix A
+fn svc_param(token: &Token) -> Result<SvcParam, Kind> {
+    let (key, value) = match token.text.split_once('=') {
+        Some((key, value)) => (key, Some(unescape(value)?)),
+        None => (token.text.as_str(), None),
+    };
+    let invalid = || Kind::InvalidField {
+        field: "SvcParam",
+        value: token.text.clone(),
+    };
+
+    let key = svc_param_key(key).ok_or_else(invalid)?;
+    let value = match (key, value) {
+        (SvcParam::NO_DEFAULT_ALPN, None) => return Ok(SvcParam::NoDefaultAlpn),
+        (SvcParam::NO_DEFAULT_ALPN, Some(_)) | (_, None) => return Err(invalid()),
+        (_, Some(value)) => value,
+    };
+
+    let text = String::from_utf8_lossy(&value).into_owned();
+    let list = || split_unescaped(&text, ',');
+    let param = match key {
+        SvcParam::MANDATORY => SvcParam::Mandatory(
+            list()
+                .into_iter()
+                .map(svc_param_key)
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+        ),
+        SvcParam::ALPN => SvcParam::Alpn(
+            list()
+                .into_iter()
+                .map(|id| {
+                    unescape(id)
+                        .ok()
+                        .filter(|id| !id.is_empty() && id.len() <= 0xFF)
+                })
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+        ),
+        SvcParam::PORT => SvcParam::Port(number(&text, "port")?),
+        SvcParam::IPV4_HINT => SvcParam::Ipv4Hint(
+            list()
+                .into_iter()
+                .map(|a| Ipv4Addr::from_str(a).ok())
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+        ),
+        SvcParam::ECH => SvcParam::Ech(base64_decode(&text).ok_or_else(invalid)?),
+        SvcParam::IPV6_HINT => SvcParam::Ipv6Hint(
+            list()
+                .into_iter()
+                .map(|a| Ipv6Addr::from_str(a).ok())
+                .collect::<Option<_>>()
+                .ok_or_else(invalid)?,
+        ),
+        key => SvcParam::Unknown { key, value },
+    };
+
+    Ok(param)
+}
+
+/// The SvcParamKey a presentation name stands for
+fn svc_param_key(name: &str) -> Option<u16> {
+    let key = match name {
+        "mandatory" => SvcParam::MANDATORY,
+        "alpn" => SvcParam::ALPN,
+        "no-default-alpn" => SvcParam::NO_DEFAULT_ALPN,
+        "port" => SvcParam::PORT,
+        "ipv4hint" => SvcParam::IPV4_HINT,
+        "ech" => SvcParam::ECH,
+        "ipv6hint" => SvcParam::IPV6_HINT,
+        _ => return number(name.strip_prefix("key")?, "SvcParamKey").ok(),
+    };
+    Some(key)
+}
+
+/// A `$GENERATE` template with its `$` and `${...}` modifiers replaced for `value`
+fn substitute(token: &Token, value: u32) -> Result<Token, Kind> {
+    let invalid = || Kind::Generate(format!("bad substitution in {:?}", token.text));
+
+    let mut text = String::new();
+    let mut chars = token.text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                text.push(c);
+                text.extend(chars.next());
+            }
+            '$' if chars.peek() == Some(&'{') => {
+                chars.next();
+                let modifier: String = chars.by_ref().take_while(|&c| c != '}').collect();
+                let mut parts = modifier.split(',');
+
+                let offset: i64 = parts.next().unwrap_or("0").parse().map_err(|_| invalid())?;
+                let width: usize = match parts.next() {
+                    Some(width) => width.parse().map_err(|_| invalid())?,
+                    None => 0,
+                };
+                let base = parts.next().unwrap_or("d");
+                if parts.next().is_some() {
+                    return Err(invalid());
+                }
+
+                let value = u64::try_from(value as i64 + offset).map_err(|_| invalid())?;
+                let formatted = match base {
+                    "d" => format!("{:0width$}", value, width = width),
+                    "o" => format!("{:0width$o}", value, width = width),
+                    "x" => format!("{:0width$x}", value, width = width),
+                    "X" => format!("{:0width$X}", value, width = width),
+                    "n" | "N" => {
+                        let digits = format!("{:0width$x}", value, width = width);
+                        let nibbles: Vec<String> = digits.chars().rev().map(String::from).collect();
+                        match base {
+                            "N" => nibbles.join(".").to_ascii_uppercase(),
+                            _ => nibbles.join("."),
+                        }
+                    }
+                    _ => return Err(invalid()),
+                };
+                text.push_str(&formatted);
+            }
+            '$' => text.push_str(&value.to_string()),
+            c => text.push(c),
+        }
+    }
+
+    Ok(Token {
+        text,
+        quoted: token.quoted,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn parse(lines: &[&str]) -> Result<Vec<DnsRecord>, ZoneFileError> {
+        ZoneParser::new().parse(&lines.join("\n"))
+    }
+
+    #[test]
+    fn fills_in_left_out_fields() {
+        let records = parse(&[
+            "$ORIGIN example.com.",
+            "$TTL 3600",
+            "@ IN SOA ns hostmaster (",
+            "        1 ; serial",
+            "        7200 900 1209600 300 )",
+            "  NS ns.example.net.",
+            "www 60 A 192.0.2.1",
+            "    AAAA 2001:db8::1 ; the owner and class of the record before",
+            "mail.example.com. MX 10 www",
+        ])
+        .unwrap();
+
+        let owners: Vec<Name> = records.iter().map(|record| record.name.clone()).collect();
+        let ttls: Vec<u32> = records.iter().map(|record| record.ttl).collect();
+        assert_eq!(
+            owners,
+            [
+                "example.com",
+                "example.com",
+                "www.example.com",
+                "www.example.com",
+                "mail.example.com"
+            ]
+            .map(name)
+        );
+        assert_eq!(ttls, [3600, 3600, 60, 3600, 3600]);
+        assert_eq!(
+            records[0].r_data,
+            RData::SOA {
+                m_name: name("ns.example.com"),
+                r_name: name("hostmaster.example.com"),
+                serial: 1,
+                refresh: 7200,
+                retry: 900,
+                expire: 1209600,
+                minimum: 300,
+            }
+        );
+        assert_eq!(
+            records[4].r_data,
+            RData::MX {
+                preference: 10,
+                exchange: name("www.example.com"),
+            }
+        );
+    }
+
+    #[test]
+    fn reads_character_strings() {
+        let records = parse(&[
+            "$ORIGIN example.com.",
+            "$TTL 300",
+            "txt TXT \"v=spf1 -all\" plain \"a \\\"quote\\\"\" \"\\065\"",
+        ])
+        .unwrap();
+
+        assert_eq!(
+            records[0].r_data,
+            RData::Txt(vec![
+                b"v=spf1 -all".to_vec(),
+                b"plain".to_vec(),
+                b"a \"quote\"".to_vec(),
+                b"A".to_vec(),
+            ])
+        );
+    }
+
+    #[test]
+    fn reads_generic_rdata() {
+        let records = parse(&[
+            "$ORIGIN example.com.",
+            "$TTL 300",
+            "a A \\# 4 C0000201",
+            "b TYPE65280 \\# 2 abcd",
+        ])
+        .unwrap();
+
+        assert_eq!(records[0].r_data, RData::A(Ipv4Addr::new(192, 0, 2, 1)));
+        assert_eq!(
+            records[1].r_data,
+            RData::Unknown {
+                r_type: ResourceType::Unknown(65280),
+                data: vec![0xAB, 0xCD],
+            }
+        );
+    }
+
+    #[test]
+    fn generates_records() {
+        let records = parse(&[
+            "$ORIGIN example.com.",
+            "$TTL 300",
+            "$GENERATE 1-5/2 host$ A 192.0.2.$",
+        ])
+        .unwrap();
+
+        let owners: Vec<Name> = records.iter().map(|record| record.name.clone()).collect();
+        assert_eq!(
+            owners,
+            [
+                "host1.example.com",
+                "host3.example.com",
+                "host5.example.com"
+            ]
+            .map(name)
+        );
+        assert_eq!(records[2].r_data, RData::A(Ipv4Addr::new(192, 0, 2, 5)));
+    }
+
+    #[test]
+    fn reports_the_line_of_errors() {
+        let error = parse(&["$TTL 300", "  A 192.0.2.1"]).unwrap_err();
+        assert_eq!((error.line, error.kind), (2, Kind::MissingOwner));
+
+        let error = parse(&["www.example.com. A 192.0.2.1"]).unwrap_err();
+        assert_eq!(error.kind, Kind::MissingTtl);
+
+        let error = parse(&["$TTL 300", "www A 192.0.2.1"]).unwrap_err();
+        assert!(matches!(error.kind, Kind::MissingOrigin { .. }));
+
+        let error = parse(&["$TTL 300", "example.com. BOGUS 1"]).unwrap_err();
+        assert_eq!(error.kind, Kind::UnknownType("BOGUS".to_string()));
+    }
+}
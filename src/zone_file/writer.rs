@@ -0,0 +1,239 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use crate::protocol::dns_name::Name;
+use crate::protocol::dns_resource_record::DnsRecord;
+use crate::protocol::resource_data_enum::write_name;
+use crate::protocol::resource_type_enum::ResourceType;
+
+/// Owner names longer than this push their first line out of the column
+const OWNER_COLUMN: usize = 24;
+
+/**
+    Writes records as a master file that `ZoneParser` reads back into the
+    same records.
+
+    Records are grouped by owner, owners sorted in canonical order (RFC
+    4034 section 6.1) and the SOA put first among its owner's records.
+    Each owner is written once, on the first line of its records, relative
+    to the origin when it is below it. TTLs equal to the `$TTL` default,
+    by default the most common TTL, are left out.
+*/
+#[derive(Debug, Clone)]
+pub struct ZoneWriter {
+    origin: Name,
+    default_ttl: Option<u32>,
+}
+
+impl ZoneWriter {
+    pub fn new(origin: Name) -> ZoneWriter {
+        ZoneWriter {
+            origin,
+            default_ttl: None,
+        }
+    }
+
+    /// The TTL to write in `$TTL` instead of the most common one
+    pub fn default_ttl(mut self, ttl: u32) -> ZoneWriter {
+        self.default_ttl = Some(ttl);
+        self
+    }
+
+    pub fn write(&self, records: &[DnsRecord]) -> String {
+        let mut records: Vec<&DnsRecord> = records.iter().collect();
+        records.sort_by(|a, b| {
+            a.name
+                .cmp(&b.name)
+                .then_with(|| (b.r_type == ResourceType::SOA).cmp(&(a.r_type == ResourceType::SOA)))
+                .then_with(|| u16::from(a.r_type).cmp(&u16::from(b.r_type)))
+                .then_with(|| u16::from(a.r_class).cmp(&u16::from(b.r_class)))
+        });
+
+        let mut text = String::new();
+        writeln!(text, "$ORIGIN {}", self.origin).unwrap();
+
+        let default_ttl = self.default_ttl.or_else(|| most_common_ttl(&records));
+        if let Some(ttl) = default_ttl {
+            writeln!(text, "$TTL {}", ttl).unwrap();
+        }
+
+        let mut previous: Option<&Name> = None;
+        for record in records {
+            let mut owner = String::new();
+            let new_owner = previous != Some(&record.name);
+            if new_owner {
+                write_name(&mut owner, &record.name, Some(&self.origin), false).unwrap();
+                previous = Some(&record.name);
+            }
+            write!(text, "{:<width$}", owner, width = OWNER_COLUMN).unwrap();
+            if owner.len() >= OWNER_COLUMN {
+                text.push(' ');
+            }
+
+            if Some(record.ttl) != default_ttl {
+                write!(text, "{} ", record.ttl).unwrap();
+            }
+            writeln!(
+                text,
+                "{} {} {}",
+                record.r_class,
+                record.r_type,
+                record.r_data.relative_to(&self.origin)
+            )
+            .unwrap();
+        }
+
+        text
+    }
+}
+
+/// The TTL most records have, the lowest one among equally common TTLs
+fn most_common_ttl(records: &[&DnsRecord]) -> Option<u32> {
+    let mut counts: HashMap<u32, usize> = HashMap::new();
+    for record in records {
+        *counts.entry(record.ttl).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then(b.0.cmp(&a.0)))
+        .map(|(ttl, _)| ttl)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::{Ipv4Addr, Ipv6Addr};
+
+    use super::*;
+    use crate::protocol::resource_class_enum::ResourceClass;
+    use crate::protocol::resource_data_enum::RData;
+    use crate::protocol::svc_param_enum::SvcParam;
+    use crate::zone_file::parser::ZoneParser;
+
+    fn name(text: &str) -> Name {
+        text.parse().unwrap()
+    }
+
+    fn record(owner: &str, ttl: u32, r_data: RData) -> DnsRecord {
+        DnsRecord::with_r_data(name(owner), ttl, r_data)
+    }
+
+    fn records() -> Vec<DnsRecord> {
+        vec![
+            record(
+                "www.example.com",
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 1)),
+            ),
+            record(
+                "example.com",
+                3600,
+                RData::SOA {
+                    m_name: name("ns.example.com"),
+                    r_name: name("host\\.master.example.com"),
+                    serial: 2024010101,
+                    refresh: 7200,
+                    retry: 900,
+                    expire: 1209600,
+                    minimum: 300,
+                },
+            ),
+            record("example.com", 300, RData::NS(name("ns.example.net"))),
+            record("www.example.com", 300, RData::AAAA(Ipv6Addr::LOCALHOST)),
+            record(
+                "example.com",
+                300,
+                RData::Txt(vec![b"say \"hi\"; \\".to_vec(), vec![0, 255], Vec::new()]),
+            ),
+            record(
+                "odd\\032label.example.com",
+                60,
+                RData::CName(name("www.example.com")),
+            ),
+            record(
+                "example.com",
+                300,
+                RData::HTTPS {
+                    priority: 1,
+                    target: Name::root(),
+                    params: vec![
+                        SvcParam::Alpn(vec![b"h2".to_vec(), b"h3".to_vec()]),
+                        SvcParam::Port(8443),
+                    ],
+                },
+            ),
+            record(
+                "example.com",
+                300,
+                RData::CAA {
+                    flags: 0,
+                    tag: b"issue".to_vec(),
+                    value: b"ca.example.net".to_vec(),
+                },
+            ),
+            record(
+                "example.com",
+                300,
+                RData::Unknown {
+                    r_type: ResourceType::Unknown(65280),
+                    data: vec![0xDE, 0xAD],
+                },
+            ),
+            record(
+                "elsewhere.example.net",
+                300,
+                RData::A(Ipv4Addr::new(192, 0, 2, 2)),
+            ),
+            DnsRecord {
+                r_class: ResourceClass::CH,
+                ..record("version.example.com", 0, RData::Txt(vec![b"1.0".to_vec()]))
+            },
+        ]
+    }
+
+    #[test]
+    fn writes_what_the_parser_reads() {
+        let records = records();
+        let text = ZoneWriter::new(name("example.com")).write(&records);
+        let parsed = ZoneParser::new().parse(&text).unwrap();
+
+        let key = |record: &DnsRecord| {
+            (
+                record.name.to_string(),
+                record.ttl,
+                record.r_class,
+                record.r_data.clone(),
+            )
+        };
+        assert_eq!(parsed.len(), records.len(), "{}", text);
+        for record in &records {
+            assert!(
+                parsed.iter().any(|parsed| key(parsed) == key(record)),
+                "{:?} in\n{}",
+                record,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn writes_the_soa_first_and_owners_once() {
+        let text = ZoneWriter::new(name("example.com")).write(&records());
+        let lines: Vec<&str> = text.lines().collect();
+
+        assert_eq!(lines[0], "$ORIGIN example.com.");
+        assert_eq!(lines[1], "$TTL 300");
+        assert!(
+            lines[2].starts_with("@ ") && lines[2].contains(" SOA "),
+            "{}",
+            text
+        );
+        assert!(
+            lines[3].starts_with(' ') && lines[3].contains(" NS "),
+            "{}",
+            text
+        );
+        assert!(text.contains("\nwww "), "{}", text);
+        assert!(text.contains("elsewhere.example.net. "), "{}", text);
+        assert!(text.contains(" 3600 IN SOA "), "{}", text);
+    }
+}